@@ -3,7 +3,7 @@ use std::{collections::HashMap, mem};
 use log::debug;
 use nickel_lang::{
     identifier::Ident,
-    term::{MetaValue, RichTerm, Term},
+    term::{IndexMap, MetaValue, RichTerm, Term},
     typecheck::{
         linearization::{LinearizationState, Scope},
         TypeWrapper,
@@ -72,7 +72,7 @@ impl Building {
 
     pub(super) fn register_fields(
         &mut self,
-        record_fields: &HashMap<Ident, RichTerm>,
+        record_fields: &IndexMap<Ident, RichTerm>,
         record: usize,
         scope: Scope,
         env: &mut Environment,
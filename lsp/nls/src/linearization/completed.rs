@@ -130,12 +130,20 @@ impl Completed {
             ref types,
             ref contracts,
             priority,
+            ref deprecated,
+            ref array_merge,
             ..
         }) = item.meta.as_ref()
         {
             if let Some(doc) = doc {
                 extra.push(doc.to_owned());
             }
+            if let Some(msg) = deprecated {
+                extra.push(format!("Deprecated: {}", msg));
+            }
+            if let Some(strategy) = array_merge {
+                extra.push(format!("Array merge: {}", strategy));
+            }
             if let Some(types) = types {
                 extra.push(types.label.tag.to_string());
             }
@@ -149,7 +157,7 @@ impl Completed {
                 );
             }
 
-            extra.push(format!("Merge Priority: {:?}", priority));
+            extra.push(format!("Merge Priority: {}", priority));
         }
 
         (item.ty.to_owned(), extra)
@@ -1,13 +1,16 @@
 //! Entry point of the program.
-use nickel_lang::error::{Error, IOError};
-use nickel_lang::program::Program;
+use nickel_lang::cache::{Cache, InputFormat};
+use nickel_lang::error::{Error, IOError, SerializationError};
+use nickel_lang::package::{Packages, MANIFEST_FILE};
+use nickel_lang::program::{report_as, FieldOverride, Program};
 use nickel_lang::repl::query_print;
 #[cfg(feature = "repl")]
 use nickel_lang::repl::rustyline_frontend;
+use nickel_lang::report::ErrorFormat;
 use nickel_lang::term::{RichTerm, Term};
 use nickel_lang::{serialize, serialize::ExportFormat};
-use std::path::PathBuf;
-use std::{fs, process};
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
 // use std::ffi::OsStr;
 use directories::BaseDirs;
 use structopt::StructOpt;
@@ -15,9 +18,47 @@ use structopt::StructOpt;
 #[derive(StructOpt, Debug)]
 /// The interpreter of the Nickel language.
 struct Opt {
-    /// The input file. Standard input by default
-    #[structopt(short = "f", long, global = true, parse(from_os_str))]
-    file: Option<PathBuf>,
+    /// The input file. Standard input by default. If several files are given, they are merged
+    /// from left to right
+    #[structopt(
+        short = "f",
+        long,
+        global = true,
+        number_of_values = 1,
+        parse(from_os_str)
+    )]
+    file: Vec<PathBuf>,
+
+    /// Override a field of the program with a Nickel value, as in `server.port=8080`. Overrides
+    /// take precedence over any value defined by the program, and can be repeated
+    #[structopt(long = "override", global = true, number_of_values = 1)]
+    overrides: Vec<FieldOverride>,
+
+    /// Add a directory to the import search path. Imports which can't be found relatively to the
+    /// importing file are looked up in these directories, in order, and then in the ones listed in
+    /// `NICKEL_IMPORT_PATH`
+    #[structopt(
+        short = "I",
+        long,
+        global = true,
+        number_of_values = 1,
+        parse(from_os_str)
+    )]
+    import_path: Vec<PathBuf>,
+
+    /// The format of the input: `nickel`, `json`, `yaml`, `toml`, `json_schema` or `text`. Nickel by
+    /// default. Imports are not affected
+    #[structopt(long, global = true)]
+    input_format: Option<InputFormat>,
+
+    /// The package manifest declaring the dependencies of the program. By default, `nickel.toml`
+    /// is looked up in the directory of the program and its ancestors
+    #[structopt(long, global = true, parse(from_os_str))]
+    manifest_path: Option<PathBuf>,
+
+    /// The format of error reports: `text`, `json` (one diagnostic per line) or `sarif`
+    #[structopt(long, global = true, default_value = "text")]
+    error_format: ErrorFormat,
 
     #[cfg(debug_assertions)]
     /// Skip the standard library import, for debugging only, does not affect REPL
@@ -40,6 +81,18 @@ enum Command {
         #[structopt(short = "o", long)]
         #[structopt(parse(from_os_str))]
         output: Option<PathBuf>,
+        /// Export several files at once. The program must evaluate to a record mapping relative
+        /// paths to either strings, exported as is, or records `{format = `Yaml, content = ..}`
+        #[structopt(long, requires = "output-dir", conflicts_with_all = &["format", "output"])]
+        multi: bool,
+        /// The directory where the files of a multi-file export are written
+        #[structopt(long, requires = "multi", parse(from_os_str))]
+        output_dir: Option<PathBuf>,
+        /// Output the fields of records in alphabetical order, instead of the order of definition
+        #[structopt(long)]
+        sort_keys: bool,
+        #[structopt(flatten)]
+        inputs: Inputs,
     },
     /// Print the metadata attached to an attribute, given as a path
     Query {
@@ -54,9 +107,65 @@ enum Command {
         default: bool,
         #[structopt(long)]
         value: bool,
+        #[structopt(flatten)]
+        inputs: Inputs,
+    },
+    /// Show every merge operand which contributed to a field, given as a path, with its position,
+    /// priority and contracts
+    Explain {
+        path: String,
+        #[structopt(flatten)]
+        inputs: Inputs,
     },
     /// Typecheck a program, but do not run it
-    Typecheck,
+    Typecheck {
+        #[structopt(flatten)]
+        inputs: Inputs,
+    },
+    /// Validate a data file (Nickel, JSON, YAML or TOML) against a contract
+    Validate {
+        /// The data file to validate
+        #[structopt(parse(from_os_str))]
+        data: PathBuf,
+        /// The file defining the contract. The files given with `--file` are merged into it
+        #[structopt(long, parse(from_os_str))]
+        contract: PathBuf,
+        /// A dot separated path to the contract, if it is a field of the contract file
+        #[structopt(long)]
+        field: Option<String>,
+    },
+    /// Generate a JSON Schema from the contracts of a program
+    Schema {
+        /// Output file. Standard output by default
+        #[structopt(short = "o", long)]
+        #[structopt(parse(from_os_str))]
+        output: Option<PathBuf>,
+        #[structopt(flatten)]
+        inputs: Inputs,
+    },
+    /// Convert a JSON Schema to a Nickel contract
+    ConvertSchema {
+        /// Output file. Standard output by default
+        #[structopt(short = "o", long)]
+        #[structopt(parse(from_os_str))]
+        output: Option<PathBuf>,
+        /// The JSON Schema file. Standard input by default
+        #[structopt(parse(from_os_str))]
+        input: Option<PathBuf>,
+    },
+    /// Format the source code of a program. The input file is rewritten in place, or the result is
+    /// printed on the standard output if the program is read from the standard input
+    Format {
+        /// Do not write anything, but exit with an error if the program is not formatted
+        #[structopt(long)]
+        check: bool,
+        /// The file to format. Standard input by default
+        #[structopt(parse(from_os_str))]
+        input: Option<PathBuf>,
+    },
+    /// Compute the hashes of the files of the dependencies declared by the package manifest, and
+    /// write them to the lockfile
+    Lock,
     /// Start an REPL session
     Repl {
         #[structopt(long)]
@@ -64,6 +173,32 @@ enum Command {
     },
 }
 
+// The input files given as arguments to the commands which read a program. This is a regular
+// comment, as the doc comment of a flattened struct would become the description of each command.
+#[derive(StructOpt, Debug)]
+struct Inputs {
+    /// Input files, merged from left to right after the ones given with `--file`
+    #[structopt(parse(from_os_str))]
+    files: Vec<PathBuf>,
+}
+
+impl Command {
+    /// The input files given as arguments to the command.
+    fn files(&self) -> &[PathBuf] {
+        match self {
+            Command::Export { inputs, .. }
+            | Command::Query { inputs, .. }
+            | Command::Explain { inputs, .. }
+            | Command::Typecheck { inputs }
+            | Command::Schema { inputs, .. } => &inputs.files,
+            Command::ConvertSchema { input, .. } | Command::Format { input, .. } => {
+                input.as_ref().map(std::slice::from_ref).unwrap_or(&[])
+            }
+            Command::Validate { .. } | Command::Lock | Command::Repl { .. } => &[],
+        }
+    }
+}
+
 fn main() {
     let opts = Opt::from_args();
 
@@ -83,15 +218,102 @@ fn main() {
 
         #[cfg(not(feature = "repl"))]
         eprintln!("error: this executable was not compiled with REPL support");
+    } else if let Some(Command::Lock) = opts.command {
+        let result = env::current_dir()
+            .map_err(IOError::from)
+            .and_then(|dir| load_packages(opts.manifest_path, &dir))
+            .and_then(|packages| {
+                packages.ok_or_else(|| {
+                    IOError(format!(
+                        "could not find `{}` in the current directory or any of its parents",
+                        MANIFEST_FILE
+                    ))
+                })
+            })
+            .and_then(|mut packages| packages.write_lockfile());
+
+        if let Err(IOError(msg)) = result {
+            eprintln!("error: {}", msg);
+            process::exit(1);
+        }
     } else {
-        let mut program = opts
-            .file
-            .map(Program::new_from_file)
-            .unwrap_or_else(Program::new_from_stdin)
-            .unwrap_or_else(|err| {
-                eprintln!("Error when reading input: {}", err);
+        // When validating data, the program is the contract.
+        let inputs: Vec<PathBuf> = match opts.command {
+            Some(Command::Validate { ref contract, .. }) => std::iter::once(contract)
+                .chain(opts.file.iter())
+                .cloned()
+                .collect(),
+            Some(ref command) => opts.file.iter().chain(command.files()).cloned().collect(),
+            None => opts.file.clone(),
+        };
+
+        let single_input = matches!(
+            opts.command,
+            Some(Command::Format { .. }) | Some(Command::ConvertSchema { .. })
+        );
+        if single_input && inputs.len() > 1 {
+            eprintln!("error: this command expects a single input file");
+            process::exit(1);
+        }
+
+        // These commands don't evaluate the program, so overrides would be silently ignored.
+        let evaluates = !matches!(
+            opts.command,
+            Some(Command::Format { .. })
+                | Some(Command::ConvertSchema { .. })
+                | Some(Command::Validate { .. })
+        );
+        if !evaluates && !opts.overrides.is_empty() {
+            eprintln!("error: this command doesn't accept `--override`");
+            process::exit(1);
+        }
+
+        // The manifest is looked up from the directory of the main input.
+        let dir = match inputs.first().and_then(|file| file.parent()) {
+            Some(dir) if !dir.as_os_str().is_empty() => Ok(dir.to_path_buf()),
+            _ => env::current_dir(),
+        };
+        let packages = dir
+            .map_err(IOError::from)
+            .and_then(|dir| load_packages(opts.manifest_path.clone(), &dir));
+
+        let mut program = if inputs.is_empty() {
+            Program::new_from_stdin()
+        } else {
+            Program::new_from_files(&inputs)
+        }
+        .unwrap_or_else(|err| {
+            let err = IOError(format!("could not read the input: {}", err));
+            report_as(
+                &mut Cache::new(),
+                Vec::new(),
+                Some(err.into()),
+                opts.error_format,
+            );
+            process::exit(1)
+        });
+
+        match packages {
+            Ok(Some(packages)) => program.set_packages(packages),
+            Ok(None) => (),
+            Err(err) => {
+                program.report_as(Some(err.into()), opts.error_format);
                 process::exit(1)
-            });
+            }
+        }
+
+        if let Some(format) = opts.input_format {
+            if let Err(err) = program.set_input_format(format) {
+                program.report_as(Some(err), opts.error_format);
+                process::exit(1)
+            }
+        }
+
+        program.add_overrides(opts.overrides);
+        program.add_import_paths(opts.import_path);
+        if let Some(paths) = env::var_os("NICKEL_IMPORT_PATH") {
+            program.add_import_paths(env::split_paths(&paths));
+        }
 
         #[cfg(debug_assertions)]
         if opts.nostdlib {
@@ -99,7 +321,18 @@ fn main() {
         }
 
         let result = match opts.command {
-            Some(Command::Export { format, output }) => export(&mut program, format, output),
+            Some(Command::Export {
+                sort_keys,
+                multi: true,
+                output_dir: Some(dir),
+                ..
+            }) => export_multi(&mut program, dir, sort_keys),
+            Some(Command::Export {
+                format,
+                output,
+                sort_keys,
+                ..
+            }) => export(&mut program, format, output, sort_keys),
             Some(Command::Query {
                 path,
                 doc,
@@ -107,6 +340,7 @@ fn main() {
                 types,
                 default,
                 value,
+                ..
             }) => {
                 program.query(path).map(|term| {
                     // Print a default selection of attributes if no option is specified
@@ -125,26 +359,100 @@ fn main() {
                     query_print::write_query_result(&mut std::io::stdout(), &term, attrs).unwrap()
                 })
             }
-            Some(Command::Typecheck) => program.typecheck().map(|_| ()),
-            Some(Command::Repl { .. }) => unreachable!(),
+            Some(Command::Explain { path, .. }) => program.explain(path).map(|explanation| {
+                query_print::write_explanation(&mut std::io::stdout(), &explanation).unwrap()
+            }),
+            Some(Command::Typecheck { .. }) => program.typecheck().map(|_| ()),
+            Some(Command::Validate { data, field, .. }) => program.validate(data, field),
+            Some(Command::Schema { output, .. }) => schema(&mut program, output),
+            Some(Command::ConvertSchema { output, .. }) => convert_schema(&program, output),
+            Some(Command::Format { check, .. }) => {
+                format(&program, inputs.into_iter().next(), check)
+            }
+            Some(Command::Repl { .. }) | Some(Command::Lock) => unreachable!(),
             None => program
                 .eval_full()
                 .map(|t| println!("{}", Term::from(t).deep_repr())),
         };
 
-        if let Err(err) = result {
-            program.report(err);
+        // Warnings are reported even if the command succeeded.
+        let failed = result.is_err();
+        program.report_as(result.err(), opts.error_format);
+        if failed {
             process::exit(1)
         }
     }
 }
 
+/// Load the package manifest given on the command line, or look for one starting from `dir`.
+fn load_packages(manifest_path: Option<PathBuf>, dir: &Path) -> Result<Option<Packages>, IOError> {
+    match manifest_path {
+        Some(path) => Packages::load(path).map(Some),
+        None => Packages::find(dir),
+    }
+}
+
+fn schema(program: &mut Program, output: Option<PathBuf>) -> Result<(), Error> {
+    let schema = program.schema()?;
+
+    if let Some(file) = output {
+        let file = fs::File::create(&file).map_err(IOError::from)?;
+        serde_json::to_writer_pretty(file, &schema)
+    } else {
+        serde_json::to_writer_pretty(std::io::stdout(), &schema).map(|()| println!())
+    }
+    .map_err(|err| SerializationError::Other(err.to_string()))?;
+
+    Ok(())
+}
+
+fn convert_schema(program: &Program, output: Option<PathBuf>) -> Result<(), Error> {
+    let contract = program.convert_schema()?;
+
+    if let Some(file) = output {
+        fs::write(file, contract).map_err(IOError::from)?;
+    } else {
+        print!("{}", contract);
+    }
+
+    Ok(())
+}
+
+fn format(program: &Program, file: Option<PathBuf>, check: bool) -> Result<(), Error> {
+    let formatted = program.format()?;
+
+    if check {
+        if formatted != program.source() {
+            let name = file
+                .as_ref()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_else(|| String::from("<stdin>"));
+            eprintln!("{} is not formatted", name);
+            process::exit(1);
+        }
+    } else if let Some(path) = file {
+        if formatted != program.source() {
+            fs::write(path, formatted).map_err(IOError::from)?;
+        }
+    } else {
+        print!("{}", formatted);
+    }
+
+    Ok(())
+}
+
 fn export(
     program: &mut Program,
     format: Option<ExportFormat>,
     output: Option<PathBuf>,
+    sort_keys: bool,
 ) -> Result<(), Error> {
     let rt = program.eval_full().map(RichTerm::from)?;
+    let rt = if sort_keys {
+        serialize::sort_keys(rt)
+    } else {
+        rt
+    };
     let format = format.unwrap_or_default();
 
     serialize::validate(format, &rt)?;
@@ -158,3 +466,31 @@ fn export(
 
     Ok(())
 }
+
+fn export_multi(program: &mut Program, dir: PathBuf, sort_keys: bool) -> Result<(), Error> {
+    let rt = program.eval_full()?;
+    let rt = if sort_keys {
+        serialize::sort_keys(rt)
+    } else {
+        rt
+    };
+    let files = serialize::export_files(&rt)?;
+
+    // Validate everything before writing anything, so that an error doesn't leave the output
+    // directory half updated.
+    for file in files.iter() {
+        serialize::validate(file.format, &file.content)?;
+    }
+
+    for file in files {
+        let path = dir.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(IOError::from)?;
+        }
+
+        let out = fs::File::create(&path).map_err(IOError::from)?;
+        serialize::to_writer(out, file.format, &file.content)?;
+    }
+
+    Ok(())
+}
@@ -1,24 +1,27 @@
 //! Source cache.
 
 use crate::error::{Error, ImportError, ParseError, ParseErrors, TypecheckError};
+use crate::package::Packages;
 use crate::parser::lexer::Lexer;
-use crate::position::TermPos;
+use crate::position::{RawSpan, TermPos};
 use crate::stdlib as nickel_stdlib;
 use crate::term::{RichTerm, SharedTerm, Term};
 use crate::transform::import_resolution;
 use crate::typecheck;
 use crate::typecheck::{linearization::StubHost, type_check};
 use crate::types::UnboundTypeVariableError;
-use crate::{eval, parser, transform};
+use crate::{deserialize, eval, parser, schema, transform};
 use codespan::{FileId, Files};
 use io::Read;
 use std::collections::hash_map;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
+use std::fmt;
 use std::fs;
 use std::io;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::result::Result;
+use std::str::FromStr;
 use std::time::SystemTime;
 use void::Void;
 
@@ -29,20 +32,71 @@ pub enum InputFormat {
     Json,
     Yaml,
     Toml,
+    /// A JSON Schema, converted to a Nickel contract. See [`crate::schema::to_contract`].
+    JsonSchema,
+    /// Plain text, such as a script or a certificate, imported as a string as is.
+    Text,
 }
 
 impl InputFormat {
-    fn from_path_buf(path_buf: &Path) -> Option<InputFormat> {
+    /// Determine the format of a file from its extension.
+    pub fn from_path_buf(path_buf: &Path) -> Option<InputFormat> {
+        let is_schema = matches!(
+            path_buf.file_stem().and_then(OsStr::to_str),
+            Some(stem) if stem.ends_with(".schema")
+        );
+
         match path_buf.extension().and_then(OsStr::to_str) {
             Some("ncl") => Some(InputFormat::Nickel),
+            Some("json") if is_schema => Some(InputFormat::JsonSchema),
             Some("json") => Some(InputFormat::Json),
             Some("yaml") | Some("yml") => Some(InputFormat::Yaml),
             Some("toml") => Some(InputFormat::Toml),
+            Some("txt") | Some("md") | Some("sh") | Some("bash") | Some("pem") | Some("crt")
+            | Some("key") | Some("tpl") => Some(InputFormat::Text),
             _ => None,
         }
     }
 }
 
+impl fmt::Display for InputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputFormat::Nickel => write!(f, "nickel"),
+            InputFormat::Json => write!(f, "json"),
+            InputFormat::Yaml => write!(f, "yaml"),
+            InputFormat::Toml => write!(f, "toml"),
+            InputFormat::JsonSchema => write!(f, "json_schema"),
+            InputFormat::Text => write!(f, "text"),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseInputFormatError(String);
+
+impl fmt::Display for ParseInputFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unsupported input format {}", self.0)
+    }
+}
+
+impl FromStr for InputFormat {
+    type Err = ParseInputFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "nickel" => Ok(InputFormat::Nickel),
+            "json" => Ok(InputFormat::Json),
+            "yaml" => Ok(InputFormat::Yaml),
+            "toml" => Ok(InputFormat::Toml),
+            "json_schema" => Ok(InputFormat::JsonSchema),
+            "text" => Ok(InputFormat::Text),
+            _ => Err(ParseInputFormatError(String::from(s))),
+        }
+    }
+}
+
 /// File and terms cache.
 ///
 /// Manage a file database, which stores a set of sources (the original source code as string) and
@@ -68,6 +122,11 @@ pub struct Cache {
     terms: HashMap<FileId, CachedTerm>,
     /// The list of ids corresponding to the stdlib modules
     stdlib_ids: Option<Vec<FileId>>,
+    /// The directories where imports are searched, in order, when they can't be found relatively
+    /// to the importing file. See [`Cache::add_import_paths`].
+    import_paths: Vec<PathBuf>,
+    /// The dependencies declared by the package manifest of the program, if any.
+    packages: Option<Packages>,
 
     #[cfg(debug_assertions)]
     /// Skip loading the stdlib, used for debugging purpose
@@ -207,12 +266,57 @@ impl Cache {
             terms: HashMap::new(),
             imports: HashMap::new(),
             stdlib_ids: None,
+            import_paths: Vec::new(),
+            packages: None,
 
             #[cfg(debug_assertions)]
             skip_stdlib: false,
         }
     }
 
+    /// Add directories to the import search path. An import whose path is neither absolute nor
+    /// explicitly relative (that is, starting with `./` or `../`) is first looked up relatively to
+    /// the importing file, and then in each of these directories, in order.
+    pub fn add_import_paths<P>(&mut self, paths: impl IntoIterator<Item = P>)
+    where
+        P: Into<PathBuf>,
+    {
+        self.import_paths.extend(paths.into_iter().map(Into::into));
+    }
+
+    /// Set the dependencies of the program. Imports whose first component is the name of a
+    /// dependency, as in `import "k8s/deployment.ncl"`, are looked up in the directory of this
+    /// dependency when they can't be found relatively to the importing file. Every imported file
+    /// belonging to a dependency is checked against the lockfile. See [`crate::package`].
+    pub fn set_packages(&mut self, packages: Packages) {
+        self.packages = Some(packages);
+    }
+
+    /// Return the paths where an import may be found, by order of precedence. The first one is
+    /// always the path relative to the importing file. If the import path is eligible, it is
+    /// followed by the path inside the dependency it refers to, if any, and by the directories of
+    /// the import search path.
+    fn import_candidates(&self, path: &OsStr, parent: Option<PathBuf>) -> Vec<PathBuf> {
+        let local = with_parent(path, parent);
+        let path = Path::new(path);
+        let searchable = path.is_relative()
+            && !matches!(
+                path.components().next(),
+                Some(Component::CurDir) | Some(Component::ParentDir)
+            );
+
+        let mut candidates = vec![local];
+        if searchable {
+            candidates.extend(
+                self.packages
+                    .as_ref()
+                    .and_then(|packages| packages.resolve(path)),
+            );
+            candidates.extend(self.import_paths.iter().map(|dir| dir.join(path)));
+        }
+        candidates
+    }
+
     /// Load a file in the file database. Do not insert an entry in the name-id table.
     fn load_file(&mut self, path: impl Into<OsString>) -> io::Result<FileId> {
         let path = path.into();
@@ -278,6 +382,120 @@ impl Cache {
         self.get_or_add_file_(normalized, timestamp)
     }
 
+    /// Same as [Self::get_or_add_file], for a file imported in a given format. A file imported in
+    /// another format than the one given by its extension, as in `import "config" as yaml`, has a
+    /// distinct entry in the name-id table named `<path> as <format>`, such that the same file can
+    /// be imported in several formats.
+    fn get_or_add_import(
+        &mut self,
+        path: &Path,
+        format: InputFormat,
+    ) -> io::Result<CacheOp<FileId>> {
+        let timestamp = timestamp(path)?;
+        let mut name = normalize_path(path)?;
+        if InputFormat::from_path_buf(path).unwrap_or(InputFormat::Nickel) != format {
+            name.push(format!(" as {}", format));
+        }
+
+        if let Some(file_id) = self.id_of_file_(&name, timestamp) {
+            return Ok(CacheOp::Cached(file_id));
+        }
+
+        let mut buffer = String::new();
+        fs::File::open(path).and_then(|mut file| file.read_to_string(&mut buffer))?;
+        let file_id = self.files.add(name.clone(), buffer);
+        self.file_ids.insert(
+            name,
+            NameIdEntry {
+                id: file_id,
+                timestamp: Some(timestamp),
+            },
+        );
+        Ok(CacheOp::Done(file_id))
+    }
+
+    /// Same as [Self::get_or_add_import], for the import of a directory. The directory is converted
+    /// to the source of a record with one field for each Nickel, JSON, YAML or TOML file, named
+    /// after the file without its extension and defined as the import of this file. The fields are
+    /// sorted by name, and hidden files are ignored. The generated source is added to the file
+    /// database under the name of the directory.
+    fn get_or_add_dir(
+        &mut self,
+        import: &OsStr,
+        path: &Path,
+        pos: &TermPos,
+    ) -> Result<CacheOp<FileId>, ImportError> {
+        let io_err = |err: io::Error| {
+            ImportError::IOError(import.to_string_lossy().into_owned(), err.to_string(), *pos)
+        };
+
+        // The modification time of a directory changes when files are added, removed or renamed.
+        let timestamp = timestamp(path).map_err(io_err)?;
+        let name = normalize_path(path).map_err(io_err)?;
+        if let Some(file_id) = self.id_of_file_(&name, timestamp) {
+            return Ok(CacheOp::Cached(file_id));
+        }
+
+        let mut fields: BTreeMap<String, String> = BTreeMap::new();
+        for entry in fs::read_dir(path).map_err(io_err)? {
+            let file = entry.map_err(io_err)?.path();
+            let (stem, file_name) = match (
+                file.file_stem().and_then(OsStr::to_str),
+                file.file_name().and_then(OsStr::to_str),
+            ) {
+                (Some(stem), Some(file_name)) if !file_name.starts_with('.') => (stem, file_name),
+                _ => continue,
+            };
+
+            let supported = matches!(
+                InputFormat::from_path_buf(&file),
+                Some(InputFormat::Nickel)
+                    | Some(InputFormat::Json)
+                    | Some(InputFormat::JsonSchema)
+                    | Some(InputFormat::Yaml)
+                    | Some(InputFormat::Toml)
+            );
+            if !supported || !file.is_file() {
+                continue;
+            }
+
+            if let Some(other) = fields.insert(String::from(stem), String::from(file_name)) {
+                let (first, second) = if other.as_str() < file_name {
+                    (other, String::from(file_name))
+                } else {
+                    (String::from(file_name), other)
+                };
+                return Err(ImportError::DirectoryCollision(
+                    import.to_string_lossy().into_owned(),
+                    first,
+                    second,
+                    *pos,
+                ));
+            }
+        }
+
+        let dir = PathBuf::from(&name);
+        let mut source = String::from("{\n");
+        for (field, file_name) in fields {
+            source.push_str(&format!(
+                "  {} = import {},\n",
+                schema::field_name(&field),
+                schema::string_literal(&dir.join(file_name).to_string_lossy())
+            ));
+        }
+        source.push('}');
+
+        let file_id = self.files.add(name.clone(), source);
+        self.file_ids.insert(
+            name,
+            NameIdEntry {
+                id: file_id,
+                timestamp: Some(timestamp),
+            },
+        );
+        Ok(CacheOp::Done(file_id))
+    }
+
     /// Load a source and add it to the name-id table.
     ///
     /// Do not check if a source with the same name already exists: if it is the
@@ -363,7 +581,10 @@ impl Cache {
         if let Some(CachedTerm { parse_errs, .. }) = self.terms.get(&file_id) {
             Ok(CacheOp::Cached(parse_errs.clone()))
         } else {
-            let (term, parse_errs) = self.parse_nocache_multi(file_id, format)?;
+            let (term, parse_errs) = match format {
+                InputFormat::JsonSchema => self.parse_json_schema(file_id)?,
+                _ => self.parse_nocache_multi(file_id, format)?,
+            };
             self.terms.insert(
                 file_id,
                 CachedTerm {
@@ -382,6 +603,9 @@ impl Cache {
     }
 
     /// Parse a source without querying nor populating the cache. Support multiple formats.
+    ///
+    /// A JSON Schema is parsed as plain JSON data: the conversion to a contract adds the generated
+    /// source to the file database, and is only performed by [`Cache::parse_multi`].
     pub fn parse_nocache_multi(
         &self,
         file_id: FileId,
@@ -396,18 +620,52 @@ impl Cache {
 
                 Ok((t, parse_errs))
             }
-            InputFormat::Json => serde_json::from_str(self.files.source(file_id))
-                .map(|t| (t, ParseErrors::default()))
-                .map_err(|err| ParseError::from_serde_json(err, file_id, &self.files)),
-            InputFormat::Yaml => serde_yaml::from_str(self.files.source(file_id))
-                .map(|t| (t, ParseErrors::default()))
-                .map_err(|err| (ParseError::from_serde_yaml(err, file_id))),
-            InputFormat::Toml => toml::from_str(self.files.source(file_id))
-                .map(|t| (t, ParseErrors::default()))
-                .map_err(|err| (ParseError::from_toml(err, file_id, &self.files))),
+            InputFormat::Json | InputFormat::JsonSchema => {
+                deserialize::from_json(&self.files, file_id).map(|t| (t, ParseErrors::default()))
+            }
+            InputFormat::Yaml => {
+                deserialize::from_yaml(&self.files, file_id).map(|t| (t, ParseErrors::default()))
+            }
+            InputFormat::Toml => {
+                deserialize::from_toml(&self.files, file_id).map(|t| (t, ParseErrors::default()))
+            }
+            InputFormat::Text => {
+                let span = RawSpan {
+                    src_id: file_id,
+                    start: 0.into(),
+                    end: (buf.len() as u32).into(),
+                };
+                let t = RichTerm::new(Term::Str(buf.to_owned()), TermPos::Original(span));
+                Ok((t, ParseErrors::default()))
+            }
         }
     }
 
+    /// Convert a JSON Schema to the source of a Nickel contract. See [`crate::schema::to_contract`].
+    pub fn convert_json_schema(&self, file_id: FileId) -> Result<String, ParseError> {
+        let source = self.files.source(file_id);
+        let schema: serde_json::Value = serde_json::from_str(source)
+            .map_err(|err| ParseError::from_serde_json(err, file_id, &self.files))?;
+
+        schema::to_contract(&schema, source)
+            .map_err(|msg| ParseError::ExternalFormatError(String::from("json schema"), msg, None))
+    }
+
+    /// Convert a JSON Schema to a Nickel contract and parse it. The generated source is added to
+    /// the file database, under the name of the schema followed by `.ncl`, such that errors can
+    /// point to it.
+    fn parse_json_schema(
+        &mut self,
+        file_id: FileId,
+    ) -> Result<(RichTerm, ParseErrors), ParseError> {
+        let source = self.convert_json_schema(file_id)?;
+        let mut name = self.files.name(file_id).to_owned();
+        name.push(".ncl");
+        let generated_id = self.add_tmp(name, source);
+
+        self.parse_nocache(generated_id)
+    }
+
     /// Typecheck an entry of the cache and update its state accordingly, or do nothing if the
     /// entry has already been typechecked. Require that the corresponding source has been parsed.
     /// If the source contains imports, recursively typecheck on the imports too.
@@ -909,6 +1167,9 @@ pub trait ImportResolver {
     /// Read and store the content of an import, put it in the file cache (or get it from there if
     /// it is cached), then parse it and return the corresponding term and file id.
     ///
+    /// The format of the file is given by `format` if it is explicit, as in `import "config" as
+    /// yaml`, and determined by the extension of the file otherwise.
+    ///
     /// The term and the path are provided only if the import is processed for the first time.
     /// Indeed, at import resolution phase, the term of an import encountered for the first time is
     /// queued to be processed (e.g. having its own imports resolved). The path is needed to
@@ -918,6 +1179,7 @@ pub trait ImportResolver {
     fn resolve(
         &mut self,
         path: &OsStr,
+        format: Option<InputFormat>,
         parent: Option<PathBuf>,
         pos: &TermPos,
     ) -> Result<(ResolvedTerm, FileId), ImportError>;
@@ -932,20 +1194,52 @@ impl ImportResolver for Cache {
     fn resolve(
         &mut self,
         path: &OsStr,
+        format: Option<InputFormat>,
         parent: Option<PathBuf>,
         pos: &TermPos,
     ) -> Result<(ResolvedTerm, FileId), ImportError> {
-        let path_buf = with_parent(path, parent.clone());
-        let format = InputFormat::from_path_buf(&path_buf).unwrap_or(InputFormat::Nickel);
-        let id_op = self.get_or_add_file(&path_buf).map_err(|err| {
-            ImportError::IOError(
+        let candidates = self.import_candidates(path, parent.clone());
+        let path_buf = candidates
+            .iter()
+            .find(|candidate| candidate.exists())
+            .unwrap_or(&candidates[0])
+            .clone();
+        let is_dir = path_buf.is_dir();
+        if let (true, Some(format)) = (is_dir, format) {
+            return Err(ImportError::IOError(
                 path.to_string_lossy().into_owned(),
-                format!("{}", err),
+                format!("a directory can't be imported as {}", format),
                 *pos,
-            )
-        })?;
-        let file_id = match id_op {
-            CacheOp::Cached(id) => return Ok((ResolvedTerm::FromCache(), id)),
+            ));
+        }
+
+        // A directory is converted to a Nickel source (see `Cache::get_or_add_dir`).
+        let format = if is_dir {
+            InputFormat::Nickel
+        } else {
+            format
+                .or_else(|| InputFormat::from_path_buf(&path_buf))
+                .unwrap_or(InputFormat::Nickel)
+        };
+
+        let id_op = if is_dir {
+            self.get_or_add_dir(path, &path_buf, pos)?
+        } else {
+            self.get_or_add_import(&path_buf, format).map_err(|err| {
+                let mut msg = format!("{}", err);
+                if candidates.len() > 1 {
+                    let searched: Vec<_> = candidates
+                        .iter()
+                        .map(|candidate| candidate.to_string_lossy())
+                        .collect();
+                    msg.push_str(&format!(" (searched {})", searched.join(", ")));
+                }
+
+                ImportError::IOError(path.to_string_lossy().into_owned(), msg, *pos)
+            })?
+        };
+        let (file_id, cached) = match id_op {
+            CacheOp::Cached(id) => (id, true),
             CacheOp::Done(id) => {
                 if let Some(parent) = parent {
                     let parent_id = self.id_of(parent).unwrap();
@@ -957,10 +1251,24 @@ impl ImportResolver for Cache {
                         self.imports.insert(parent_id, imports);
                     }
                 }
-                id
+                (id, false)
             }
         };
 
+        // The record generated for a directory doesn't belong to any package: only the files it
+        // imports are checked.
+        if let (Some(packages), false) = (&self.packages, is_dir) {
+            packages
+                .verify(&path_buf, self.files.source(file_id).as_bytes())
+                .map_err(|msg| {
+                    ImportError::LockfileMismatch(path.to_string_lossy().into_owned(), msg, *pos)
+                })?;
+        }
+
+        if cached {
+            return Ok((ResolvedTerm::FromCache(), file_id));
+        }
+
         // We ignore non fatal parse errors while importing.
         self.parse_multi(file_id, format)
             .map_err(|err| ImportError::ParseErrors(err.into(), *pos))?;
@@ -1014,6 +1322,7 @@ pub mod resolvers {
         fn resolve(
             &mut self,
             _path: &OsStr,
+            _format: Option<InputFormat>,
             _parent: Option<PathBuf>,
             _pos: &TermPos,
         ) -> Result<(ResolvedTerm, FileId), ImportError> {
@@ -1058,6 +1367,7 @@ pub mod resolvers {
         fn resolve(
             &mut self,
             path: &OsStr,
+            _format: Option<InputFormat>,
             _parent: Option<PathBuf>,
             pos: &TermPos,
         ) -> Result<(ResolvedTerm, FileId), ImportError> {
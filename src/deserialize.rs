@@ -0,0 +1,1392 @@
+//! Deserialization of data files (JSON, YAML and TOML) to Nickel terms.
+//!
+//! As opposed to going through the `Deserialize` implementation of [`RichTerm`], the functions of
+//! this module keep track of where each value comes from: every term, as well as every record
+//! field name, is given a position in the original file. This way, errors involving data which
+//! has been imported, such as contract violations, can point to the offending value in the data
+//! file. The `deserialize` primitive operation uses the same functions (see [`from_string`]), such
+//! that data is read the same way whether it is imported or deserialized.
+//!
+//! The JSON parser is a simple hand-written recursive descent parser. YAML files are parsed by
+//! the event-based parser of `yaml_rust`, the same as `serde_yaml` uses, and scalars are
+//! resolved following the rules of `serde_yaml`. TOML files are parsed by the `toml` crate, which
+//! provides the spans of values through [`toml::Spanned`]. The tables defined by a header, as in
+//! `[table]`, don't have a span and are left without position.
+use crate::error::ParseError;
+use crate::identifier::Ident;
+use crate::number::{self, Number, NumberError};
+use crate::position::{RawSpan, TermPos};
+use crate::term::{RichTerm, Term};
+use codespan::{FileId, Files};
+use indexmap::IndexMap;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::collections::HashMap;
+use std::fmt;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle, TokenType};
+
+fn mk_pos(file_id: FileId, start: usize, end: usize) -> TermPos {
+    TermPos::Original(mk_span(file_id, start, end))
+}
+
+fn mk_span(file_id: FileId, start: usize, end: usize) -> RawSpan {
+    RawSpan {
+        src_id: file_id,
+        start: (start as u32).into(),
+        end: (end as u32).into(),
+    }
+}
+
+fn mk_record(fields: IndexMap<Ident, RichTerm>) -> Term {
+    Term::Record(fields, Default::default())
+}
+
+/// The maximum nesting depth of arrays and objects, the same as the default of `serde_json`.
+/// Deeper values are rejected, instead of overflowing the stack when parsing them or later
+/// when evaluating them.
+const MAX_DEPTH: usize = 128;
+
+/// The error returned when a value exceeds [`MAX_DEPTH`].
+const DEPTH_ERROR: &str = "recursion limit exceeded";
+
+/// Append the line and the column of an offset to an error message, as the messages of
+/// `serde_json`, `yaml_rust` and `toml` do, such that errors stay informative when the source is
+/// not a file, as for the `deserialize` primitive operation.
+fn with_location(msg: &str, source: &str, offset: usize) -> String {
+    let before = &source[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before[before.rfind('\n').map_or(0, |i| i + 1)..]
+        .chars()
+        .count()
+        + 1;
+
+    format!("{} at line {} column {}", msg, line, column)
+}
+
+/// Deserialize a string, as done by the `deserialize` primitive operation, using one of the
+/// functions of this module. Data is thus read the same way whether it is imported or
+/// deserialized. As the string is not a source file, positions are dropped.
+pub fn from_string(
+    parse: fn(&Files<String>, FileId) -> Result<RichTerm, ParseError>,
+    s: &str,
+) -> Result<RichTerm, String> {
+    let mut files = Files::new();
+    let file_id = files.add("<deserialize>", String::from(s));
+
+    match parse(&files, file_id) {
+        Ok(t) => Ok(drop_pos(t)),
+        Err(ParseError::ExternalFormatError(_, msg, _)) => Err(msg),
+        Err(err) => Err(format!("{:?}", err)),
+    }
+}
+
+/// Remove the positions of a deserialized term, including the ones of field names.
+fn drop_pos(rt: RichTerm) -> RichTerm {
+    let t = match rt.term.into_owned() {
+        Term::Record(fields, attrs) => Term::Record(
+            fields
+                .into_iter()
+                .map(|(id, t)| (Ident::from(id.label), drop_pos(t)))
+                .collect(),
+            attrs,
+        ),
+        Term::Array(elts) => Term::Array(elts.into_iter().map(drop_pos).collect()),
+        t => t,
+    };
+
+    t.into()
+}
+
+/// Deserialize a JSON file.
+pub fn from_json(files: &Files<String>, file_id: FileId) -> Result<RichTerm, ParseError> {
+    let mut parser = JsonParser {
+        file_id,
+        source: files.source(file_id),
+        offset: 0,
+        depth: 0,
+    };
+
+    let t = parser.value()?;
+    parser.skip_whitespace();
+
+    if parser.offset < parser.source.len() {
+        Err(parser.error("trailing characters"))
+    } else {
+        Ok(t)
+    }
+}
+
+/// A JSON parser which records the position of values.
+struct JsonParser<'a> {
+    file_id: FileId,
+    source: &'a str,
+    /// The current offset, in bytes, in the source.
+    offset: usize,
+    /// The number of arrays and objects being parsed.
+    depth: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    fn error(&self, msg: &str) -> ParseError {
+        let end = std::cmp::min(self.offset + 1, self.source.len());
+        self.error_at(msg, self.offset, end)
+    }
+
+    fn error_at(&self, msg: &str, start: usize, end: usize) -> ParseError {
+        ParseError::ExternalFormatError(
+            String::from("json"),
+            with_location(msg, self.source, start),
+            Some(mk_span(self.file_id, start, end)),
+        )
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.source.as_bytes().get(self.offset).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.offset += 1;
+        }
+    }
+
+    /// Consume a given byte, possibly preceded by whitespace.
+    fn expect(&mut self, byte: u8, msg: &str) -> Result<(), ParseError> {
+        self.skip_whitespace();
+
+        if self.peek() == Some(byte) {
+            self.offset += 1;
+            Ok(())
+        } else {
+            Err(self.error(msg))
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str, t: Term) -> Result<Term, ParseError> {
+        if self.source[self.offset..].starts_with(keyword) {
+            self.offset += keyword.len();
+            Ok(t)
+        } else {
+            Err(self.error("expected value"))
+        }
+    }
+
+    /// Parse an array or an object, checking the nesting depth.
+    fn nested(&mut self, f: fn(&mut Self) -> Result<Term, ParseError>) -> Result<Term, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error(DEPTH_ERROR));
+        }
+
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn value(&mut self) -> Result<RichTerm, ParseError> {
+        self.skip_whitespace();
+        let start = self.offset;
+
+        let t = match self.peek() {
+            Some(b'{') => self.nested(Self::object)?,
+            Some(b'[') => self.nested(Self::array)?,
+            Some(b'"') => Term::Str(self.string()?),
+            Some(b't') => self.keyword("true", Term::Bool(true))?,
+            Some(b'f') => self.keyword("false", Term::Bool(false))?,
+            Some(b'n') => self.keyword("null", Term::Null)?,
+            Some(b'-') | Some(b'0'..=b'9') => self.number()?,
+            None => return Err(self.error("EOF while parsing a value")),
+            Some(_) => return Err(self.error("expected value")),
+        };
+
+        Ok(RichTerm::new(t, mk_pos(self.file_id, start, self.offset)))
+    }
+
+    fn object(&mut self) -> Result<Term, ParseError> {
+        let mut fields = IndexMap::new();
+        self.offset += 1;
+        self.skip_whitespace();
+
+        if self.peek() == Some(b'}') {
+            self.offset += 1;
+            return Ok(mk_record(fields));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("key must be a string"));
+            }
+
+            let start = self.offset;
+            let label = self.string()?;
+            let id = Ident {
+                label,
+                pos: mk_pos(self.file_id, start, self.offset),
+            };
+
+            self.expect(b':', "expected `:`")?;
+            let value = self.value()?;
+            fields.insert(id, value);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b'}') => {
+                    self.offset += 1;
+                    return Ok(mk_record(fields));
+                }
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Term, ParseError> {
+        let mut elts = Vec::new();
+        self.offset += 1;
+        self.skip_whitespace();
+
+        if self.peek() == Some(b']') {
+            self.offset += 1;
+            return Ok(Term::Array(elts));
+        }
+
+        loop {
+            elts.push(self.value()?);
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.offset += 1,
+                Some(b']') => {
+                    self.offset += 1;
+                    return Ok(Term::Array(elts));
+                }
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    /// Consume a sequence of digits, and return `false` if it is empty.
+    fn digits(&mut self) -> bool {
+        let start = self.offset;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.offset += 1;
+        }
+        self.offset > start
+    }
+
+    fn number(&mut self) -> Result<Term, ParseError> {
+        let start = self.offset;
+
+        if self.peek() == Some(b'-') {
+            self.offset += 1;
+        }
+
+        // The integral part can't have leading zeros.
+        let integral = self.offset;
+        if !self.digits() {
+            return Err(self.error("invalid number"));
+        }
+        if self.offset - integral > 1 && &self.source[integral..=integral] == "0" {
+            return Err(self.error_at("invalid number", start, self.offset));
+        }
+
+        if self.peek() == Some(b'.') {
+            self.offset += 1;
+            if !self.digits() {
+                return Err(self.error("invalid number"));
+            }
+        }
+
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.offset += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.offset += 1;
+            }
+            if !self.digits() {
+                return Err(self.error("invalid number"));
+            }
+        }
+
+        number::parse(&self.source[start..self.offset])
+            .map(Term::Num)
+            .map_err(|err| match err {
+                NumberError::Invalid => self.error("invalid number"),
+                NumberError::TooLarge => self.error_at("number out of range", start, self.offset),
+            })
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let mut result = String::new();
+        self.offset += 1;
+        let mut run_start = self.offset;
+
+        loop {
+            match self.peek() {
+                Some(b'"') => {
+                    result.push_str(&self.source[run_start..self.offset]);
+                    self.offset += 1;
+                    return Ok(result);
+                }
+                Some(b'\\') => {
+                    result.push_str(&self.source[run_start..self.offset]);
+                    self.offset += 1;
+                    result.push(self.escape()?);
+                    run_start = self.offset;
+                }
+                Some(0..=0x1f) => return Err(self.error("control character in string")),
+                Some(_) => self.offset += 1,
+                None => return Err(self.error("EOF while parsing a string")),
+            }
+        }
+    }
+
+    /// Parse an escape sequence, after the backslash.
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.offset += 1;
+                let code = self.hex4()?;
+
+                // Characters outside of the basic multilingual plane are encoded as a surrogate
+                // pair.
+                let code = if (0xD800..0xDC00).contains(&code) {
+                    if !self.source[self.offset..].starts_with("\\u") {
+                        return Err(self.error("lone leading surrogate in hex escape"));
+                    }
+                    self.offset += 2;
+                    let low = self.hex4()?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error("invalid surrogate in hex escape"));
+                    }
+                    0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00)
+                } else {
+                    code
+                };
+
+                return char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"));
+            }
+            _ => return Err(self.error("invalid escape")),
+        };
+
+        self.offset += 1;
+        Ok(c)
+    }
+
+    fn hex4(&mut self) -> Result<u32, ParseError> {
+        let digits = self
+            .source
+            .get(self.offset..self.offset + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        let code = u32::from_str_radix(digits, 16).unwrap();
+
+        self.offset += 4;
+        Ok(code)
+    }
+}
+
+/// Deserialize a YAML file. A file with several documents is rejected.
+pub fn from_yaml(files: &Files<String>, file_id: FileId) -> Result<RichTerm, ParseError> {
+    let source = files.source(file_id);
+    let mut builder = YamlBuilder {
+        file_id,
+        source,
+        offsets: if source.is_ascii() {
+            None
+        } else {
+            Some(source.char_indices().map(|(offset, _)| offset).collect())
+        },
+        stack: Vec::new(),
+        anchors: HashMap::new(),
+        documents: Vec::new(),
+        error: None,
+    };
+
+    Parser::new(source.chars())
+        .load(&mut builder, true)
+        .map_err(|err| {
+            let start = builder.offset(err.marker());
+            yaml_error(err.to_string(), Some(mk_span(file_id, start, start + 1)))
+        })?;
+
+    if let Some(err) = builder.error {
+        return Err(err);
+    }
+
+    match builder.documents.len() {
+        0 => Ok(RichTerm::new(Term::Null, mk_pos(file_id, 0, 0))),
+        1 => Ok(builder.documents.pop().unwrap()),
+        _ => Err(yaml_error(
+            String::from(
+                "deserializing from YAML containing more than one document is not supported",
+            ),
+            None,
+        )),
+    }
+}
+
+fn yaml_error(msg: String, span: Option<RawSpan>) -> ParseError {
+    ParseError::ExternalFormatError(String::from("yaml"), msg, span)
+}
+
+/// Resolve the value of a YAML scalar, following the rules of `serde_yaml`.
+fn yaml_scalar(v: &str, style: TScalarStyle, tag: &Option<TokenType>) -> Result<Term, String> {
+    let invalid = |expected: &str| format!("invalid value: string {:?}, expected {}", v, expected);
+
+    match tag {
+        Some(TokenType::Tag(handle, suffix)) if handle == "!!" => match suffix.as_str() {
+            "bool" => v.parse().map(Term::Bool).map_err(|_| invalid("a boolean")),
+            "int" => v
+                .parse::<i64>()
+                .map(|n| Term::Num(Number::from(n)))
+                .map_err(|_| invalid("an integer")),
+            "float" => number::parse(v).map(Term::Num).map_err(|err| match err {
+                NumberError::Invalid => invalid("a float"),
+                NumberError::TooLarge => format!("number out of range: {}", v),
+            }),
+            "null" => match v {
+                "~" | "null" => Ok(Term::Null),
+                _ => Err(invalid("null")),
+            },
+            _ => Ok(Term::Str(String::from(v))),
+        },
+        Some(_) => Ok(Term::Str(String::from(v))),
+        None if style == TScalarStyle::Plain => yaml_plain_scalar(v),
+        None => Ok(Term::Str(String::from(v))),
+    }
+}
+
+/// Resolve the value of an untagged plain YAML scalar. Infinite and NaN floats are rejected, as
+/// they can't be represented by Nickel numbers.
+fn yaml_plain_scalar(v: &str) -> Result<Term, String> {
+    match v {
+        "~" | "null" => return Ok(Term::Null),
+        "true" => return Ok(Term::Bool(true)),
+        "false" => return Ok(Term::Bool(false)),
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" | "-.inf" | "-.Inf" | "-.INF"
+        | ".nan" | ".NaN" | ".NAN" => return Err(format!("unsupported number {}", v)),
+        _ => (),
+    }
+
+    let (negative, unsigned) = match v.as_bytes().first() {
+        Some(b'-') => (true, &v[1..]),
+        Some(b'+') => (false, &v[1..]),
+        _ => (false, v),
+    };
+
+    for (prefix, radix) in &[("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(digits) = unsigned.strip_prefix(prefix) {
+            if let Ok(n) = u64::from_str_radix(digits, *radix) {
+                let n = Number::from(n);
+                return Ok(Term::Num(if negative { -n } else { n }));
+            }
+        }
+    }
+
+    // Leading zeros followed by digits make a string, according to the YAML 1.2 spec.
+    if unsigned.len() > 1
+        && unsigned.starts_with('0')
+        && unsigned.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Ok(Term::Str(String::from(v)));
+    }
+
+    match number::parse(v) {
+        Ok(n) => Ok(Term::Num(n)),
+        Err(NumberError::Invalid) => Ok(Term::Str(String::from(v))),
+        Err(NumberError::TooLarge) => Err(format!("number out of range: {}", v)),
+    }
+}
+
+/// A YAML collection being built.
+enum YamlNode {
+    Sequence {
+        start: usize,
+        anchor: usize,
+        elts: Vec<RichTerm>,
+    },
+    Mapping {
+        start: usize,
+        anchor: usize,
+        fields: IndexMap<Ident, RichTerm>,
+        /// The key of the field whose value is being parsed.
+        key: Option<Ident>,
+    },
+}
+
+/// Build terms from the events of the YAML parser.
+struct YamlBuilder<'a> {
+    file_id: FileId,
+    source: &'a str,
+    /// The byte offset of each character of the source, as YAML positions are expressed in
+    /// characters. `None` if the source is ASCII.
+    offsets: Option<Vec<usize>>,
+    stack: Vec<YamlNode>,
+    /// The terms corresponding to anchored nodes.
+    anchors: HashMap<usize, RichTerm>,
+    documents: Vec<RichTerm>,
+    /// The first error encountered, after which events are ignored.
+    error: Option<ParseError>,
+}
+
+impl<'a> YamlBuilder<'a> {
+    fn offset(&self, mark: &Marker) -> usize {
+        match &self.offsets {
+            Some(offsets) => offsets
+                .get(mark.index())
+                .copied()
+                .unwrap_or(self.source.len()),
+            None => mark.index(),
+        }
+    }
+
+    /// Determine if the current collection uses the flow style, as in `[1, 2]`.
+    fn in_flow(&self) -> bool {
+        match self.stack.last() {
+            Some(YamlNode::Sequence { start, .. }) | Some(YamlNode::Mapping { start, .. }) => {
+                matches!(self.source.as_bytes().get(*start), Some(b'[') | Some(b'{'))
+            }
+            None => false,
+        }
+    }
+
+    fn expects_key(&self) -> bool {
+        matches!(self.stack.last(), Some(YamlNode::Mapping { key: None, .. }))
+    }
+
+    /// Compute the end of a scalar starting at a given offset. The YAML parser only gives the
+    /// start of nodes, so the end is found by scanning the source, which is only approximate for
+    /// multi-line scalars: the span of block scalars and of plain scalars only covers their first
+    /// line of content.
+    fn scalar_end(&self, start: usize, style: TScalarStyle) -> usize {
+        let rest = &self.source[start..];
+
+        let len = match style {
+            TScalarStyle::DoubleQuoted => {
+                let mut escaped = false;
+                rest.char_indices()
+                    .skip(1)
+                    .find(|(_, c)| {
+                        let found = !escaped && *c == '"';
+                        escaped = !escaped && *c == '\\';
+                        found
+                    })
+                    .map(|(i, _)| i + 1)
+            }
+            TScalarStyle::SingleQuoted => {
+                let bytes = rest.as_bytes();
+                let mut i = 1;
+                loop {
+                    match bytes.get(i) {
+                        Some(b'\'') if bytes.get(i + 1) == Some(&b'\'') => i += 2,
+                        Some(b'\'') => break Some(i + 1),
+                        Some(_) => i += 1,
+                        None => break None,
+                    }
+                }
+            }
+            _ => {
+                let flow = self.in_flow();
+                let line = rest.split(['\n', '\r']).next().unwrap_or("");
+                let bytes = line.as_bytes();
+                let end = (0..bytes.len())
+                    .find(|&i| {
+                        let next_is_blank =
+                            matches!(bytes.get(i + 1), None | Some(b' ') | Some(b'\t'));
+                        (bytes[i] == b':' && (next_is_blank || flow))
+                            || (bytes[i] == b'#' && i > 0 && bytes[i - 1] == b' ')
+                            || (flow && matches!(bytes[i], b',' | b'[' | b']' | b'{' | b'}'))
+                    })
+                    .unwrap_or(bytes.len());
+                Some(line[..end].trim_end().len())
+            }
+        };
+
+        start + len.unwrap_or(rest.len())
+    }
+
+    /// Compute the end of a collection from the end of its last element. Flow collections extend
+    /// to their closing bracket.
+    fn collection_end(&self, start: usize, last: usize) -> usize {
+        let closing = match self.source.as_bytes().get(start) {
+            Some(b'[') => b']',
+            Some(b'{') => b'}',
+            _ => return last,
+        };
+
+        self.source.as_bytes()[std::cmp::max(last, start + 1)..]
+            .iter()
+            .position(|b| *b == closing)
+            .map(|i| std::cmp::max(last, start + 1) + i + 1)
+            .unwrap_or(last)
+    }
+
+    fn fail(&mut self, msg: &str, start: usize, end: usize) {
+        self.error = Some(yaml_error(
+            with_location(msg, self.source, start),
+            Some(mk_span(self.file_id, start, end)),
+        ));
+    }
+
+    /// Add a term, corresponding to a complete node, to the current collection.
+    fn push(&mut self, t: RichTerm, anchor: usize) {
+        if anchor > 0 {
+            self.anchors.insert(anchor, t.clone());
+        }
+
+        match self.stack.last_mut() {
+            None => self.documents.push(t),
+            Some(YamlNode::Sequence { elts, .. }) => elts.push(t),
+            Some(YamlNode::Mapping { fields, key, .. }) => match key.take() {
+                Some(key) => {
+                    fields.insert(key, t);
+                }
+                None => {
+                    let span = t.pos.unwrap();
+                    self.fail(
+                        "mapping keys must be scalars",
+                        span.start.to_usize(),
+                        span.end.to_usize(),
+                    );
+                }
+            },
+        }
+    }
+
+    /// The end of the last term of a collection, if any.
+    fn last_end(&self, t: Option<&RichTerm>) -> Option<usize> {
+        t.and_then(|t| t.pos.into_opt())
+            .map(|span| span.end.to_usize())
+    }
+}
+
+impl<'a> MarkedEventReceiver for YamlBuilder<'a> {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let start = self.offset(&mark);
+
+        match ev {
+            Event::Scalar(v, style, anchor, tag) => {
+                let end = self.scalar_end(start, style);
+
+                if self.expects_key() {
+                    if let Some(YamlNode::Mapping { key, .. }) = self.stack.last_mut() {
+                        *key = Some(Ident {
+                            label: v,
+                            pos: mk_pos(self.file_id, start, end),
+                        });
+                    }
+                    return;
+                }
+
+                match yaml_scalar(&v, style, &tag) {
+                    Ok(t) => self.push(RichTerm::new(t, mk_pos(self.file_id, start, end)), anchor),
+                    Err(msg) => self.fail(&msg, start, end),
+                }
+            }
+            Event::Alias(anchor) => {
+                if self.expects_key() {
+                    return self.fail("mapping keys must be scalars", start, start + 1);
+                }
+
+                match self.anchors.get(&anchor).cloned() {
+                    Some(t) => self.push(t, 0),
+                    None => self.fail("unknown anchor", start, start + 1),
+                }
+            }
+            Event::SequenceStart(_) | Event::MappingStart(_) if self.stack.len() == MAX_DEPTH => {
+                self.fail(DEPTH_ERROR, start, start + 1)
+            }
+            Event::SequenceStart(anchor) => self.stack.push(YamlNode::Sequence {
+                start,
+                anchor,
+                elts: Vec::new(),
+            }),
+            Event::MappingStart(anchor) => self.stack.push(YamlNode::Mapping {
+                start,
+                anchor,
+                fields: IndexMap::new(),
+                key: None,
+            }),
+            Event::SequenceEnd => {
+                if let Some(YamlNode::Sequence {
+                    start,
+                    anchor,
+                    elts,
+                }) = self.stack.pop()
+                {
+                    let last = self.last_end(elts.last()).unwrap_or(start + 1);
+                    let end = self.collection_end(start, last);
+                    self.push(
+                        RichTerm::new(Term::Array(elts), mk_pos(self.file_id, start, end)),
+                        anchor,
+                    );
+                }
+            }
+            Event::MappingEnd => {
+                if let Some(YamlNode::Mapping {
+                    start,
+                    anchor,
+                    fields,
+                    ..
+                }) = self.stack.pop()
+                {
+                    let last = fields
+                        .values()
+                        .filter_map(|t| self.last_end(Some(t)))
+                        .max()
+                        .unwrap_or(start + 1);
+                    let end = self.collection_end(start, last);
+                    self.push(
+                        RichTerm::new(mk_record(fields), mk_pos(self.file_id, start, end)),
+                        anchor,
+                    );
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Deserialize a TOML file.
+pub fn from_toml(files: &Files<String>, file_id: FileId) -> Result<RichTerm, ParseError> {
+    let source = files.source(file_id);
+
+    toml::from_str::<TomlNode>(source)
+        .map(|node| node.into_term(file_id, source))
+        .map_err(|err| ParseError::from_toml(err, file_id, files))
+}
+
+// The `toml` crate gives the span of a value when it is deserialized as a struct with these
+// special names, which is what `toml::Spanned` does. `toml::Spanned` can't be used directly, as
+// some values don't have a span, such as tables defined by a header or the elements of an array
+// of tables, in which case it fails.
+const TOML_SPANNED: &str = "$__toml_private_Spanned";
+const TOML_START: &str = "$__toml_private_start";
+const TOML_END: &str = "$__toml_private_end";
+const TOML_VALUE: &str = "$__toml_private_value";
+const TOML_SPANNED_FIELDS: &[&str] = &[TOML_START, TOML_END, TOML_VALUE];
+/// The field used by the `toml` crate to deserialize datetimes, which are converted to strings.
+const TOML_DATETIME: &str = "$__toml_private_datetime";
+
+/// A TOML value, together with its span if it is known.
+struct TomlNode {
+    span: Option<(usize, usize)>,
+    value: TomlValue,
+}
+
+enum TomlValue {
+    Bool(bool),
+    Num(Number),
+    Str(String),
+    Array(Vec<TomlNode>),
+    Table(Vec<(TomlKey, TomlNode)>),
+}
+
+/// A TOML key, together with its span if it is known.
+struct TomlKey {
+    span: Option<(usize, usize)>,
+    label: String,
+}
+
+fn toml_pos(file_id: FileId, span: Option<(usize, usize)>) -> TermPos {
+    span.map(|(start, end)| mk_pos(file_id, start, end))
+        .unwrap_or(TermPos::None)
+}
+
+impl TomlNode {
+    fn into_term(self, file_id: FileId, source: &str) -> RichTerm {
+        let mut span = self.span;
+
+        // The span of a float given by the `toml` crate starts after the decimal point.
+        if let (TomlValue::Num(_), Some((start, _))) = (&self.value, &mut span) {
+            while *start > 0
+                && matches!(
+                    source.as_bytes()[*start - 1],
+                    b'0'..=b'9' | b'_' | b'.' | b'+' | b'-'
+                )
+            {
+                *start -= 1;
+            }
+        }
+
+        let t = match self.value {
+            TomlValue::Bool(b) => Term::Bool(b),
+            TomlValue::Num(n) => Term::Num(n),
+            TomlValue::Str(s) => Term::Str(s),
+            TomlValue::Array(elts) => Term::Array(
+                elts.into_iter()
+                    .map(|elt| elt.into_term(file_id, source))
+                    .collect(),
+            ),
+            TomlValue::Table(entries) => mk_record(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| {
+                        let id = Ident {
+                            label: key.label,
+                            pos: toml_pos(file_id, key.span),
+                        };
+                        (id, value.into_term(file_id, source))
+                    })
+                    .collect(),
+            ),
+        };
+
+        // Tables defined by a header have the span `0..0`.
+        let span = span.filter(|(_, end)| *end > 0);
+        RichTerm::new(t, toml_pos(file_id, span))
+    }
+}
+
+/// Read the start, the end and the value of a spanned value from the special map produced by
+/// the `toml` crate, the first key of which has already been consumed.
+fn toml_spanned<'de, A, T>(mut map: A) -> Result<((usize, usize), T), A::Error>
+where
+    A: MapAccess<'de>,
+    T: Deserialize<'de>,
+{
+    let start = map.next_value()?;
+    map.next_key::<String>()?;
+    let end = map.next_value()?;
+    map.next_key::<String>()?;
+    let value = map.next_value()?;
+
+    Ok(((start, end), value))
+}
+
+impl<'de> Deserialize<'de> for TomlNode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(TOML_SPANNED, TOML_SPANNED_FIELDS, TomlVisitor)
+    }
+}
+
+/// A TOML value deserialized without asking for its span, which is the inner value of a spanned
+/// value.
+struct UnspannedNode(TomlNode);
+
+impl<'de> Deserialize<'de> for UnspannedNode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(TomlVisitor).map(UnspannedNode)
+    }
+}
+
+struct TomlVisitor;
+
+impl TomlVisitor {
+    fn unspanned<E>(value: TomlValue) -> Result<TomlNode, E> {
+        Ok(TomlNode { span: None, value })
+    }
+}
+
+impl<'de> Visitor<'de> for TomlVisitor {
+    type Value = TomlNode;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a TOML value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<TomlNode, E> {
+        Self::unspanned(TomlValue::Bool(b))
+    }
+
+    fn visit_i64<E>(self, n: i64) -> Result<TomlNode, E> {
+        Self::unspanned(TomlValue::Num(Number::from(n)))
+    }
+
+    fn visit_u64<E>(self, n: u64) -> Result<TomlNode, E> {
+        Self::unspanned(TomlValue::Num(Number::from(n)))
+    }
+
+    fn visit_f64<E: de::Error>(self, n: f64) -> Result<TomlNode, E> {
+        let n =
+            number::from_f64(n).ok_or_else(|| E::custom(format!("unsupported number {}", n)))?;
+        Self::unspanned(TomlValue::Num(n))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<TomlNode, E> {
+        Self::unspanned(TomlValue::Str(String::from(s)))
+    }
+
+    fn visit_string<E>(self, s: String) -> Result<TomlNode, E> {
+        Self::unspanned(TomlValue::Str(s))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<TomlNode, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut elts = Vec::new();
+        while let Some(elt) = seq.next_element()? {
+            elts.push(elt);
+        }
+        Self::unspanned(TomlValue::Array(elts))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<TomlNode, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut entries = Vec::new();
+
+        while let Some(key) = map.next_key::<TomlKey>()? {
+            match key.label.as_str() {
+                TOML_START if entries.is_empty() => {
+                    let (span, UnspannedNode(node)) = toml_spanned(map)?;
+                    return Ok(TomlNode {
+                        span: Some(span),
+                        value: node.value,
+                    });
+                }
+                TOML_DATETIME if entries.is_empty() => {
+                    return Self::unspanned(TomlValue::Str(map.next_value()?));
+                }
+                _ => entries.push((key, map.next_value()?)),
+            }
+        }
+
+        Self::unspanned(TomlValue::Table(entries))
+    }
+}
+
+impl<'de> Deserialize<'de> for TomlKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct KeyVisitor;
+
+        impl<'de> Visitor<'de> for KeyVisitor {
+            type Value = TomlKey;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a TOML key")
+            }
+
+            fn visit_str<E>(self, label: &str) -> Result<TomlKey, E> {
+                Ok(TomlKey {
+                    span: None,
+                    label: String::from(label),
+                })
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<TomlKey, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                match map.next_key::<String>()? {
+                    Some(key) if key == TOML_START => {
+                        let (span, label) = toml_spanned(map)?;
+                        Ok(TomlKey {
+                            span: Some(span),
+                            label,
+                        })
+                    }
+                    _ => Err(de::Error::custom("expected a spanned key")),
+                }
+            }
+        }
+
+        deserializer.deserialize_struct(TOML_SPANNED, TOML_SPANNED_FIELDS, KeyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The source text of the value at a given path of a deserialized term.
+    fn text_at<'a>(source: &'a str, t: &RichTerm, path: &[&str]) -> &'a str {
+        let mut t = t.clone();
+
+        for key in path {
+            t = match t.as_ref() {
+                Term::Record(fields, _) => fields.get(&Ident::from(*key)).unwrap().clone(),
+                Term::Array(elts) => elts[key.parse::<usize>().unwrap()].clone(),
+                _ => panic!("invalid path"),
+            };
+        }
+
+        let span = t.pos.unwrap();
+        &source[span.start.to_usize()..span.end.to_usize()]
+    }
+
+    /// The source text of the key of a field.
+    fn key_at<'a>(source: &'a str, t: &RichTerm, key: &str) -> &'a str {
+        match t.as_ref() {
+            Term::Record(fields, _) => {
+                let (id, _) = fields.get_key_value(&Ident::from(key)).unwrap();
+                let span = id.pos.unwrap();
+                &source[span.start.to_usize()..span.end.to_usize()]
+            }
+            _ => panic!("not a record"),
+        }
+    }
+
+    fn parse(
+        f: fn(&Files<String>, FileId) -> Result<RichTerm, ParseError>,
+        source: &str,
+    ) -> Result<RichTerm, ParseError> {
+        let mut files = Files::new();
+        let file_id = files.add("<test>", String::from(source));
+        f(&files, file_id)
+    }
+
+    #[test]
+    fn json() {
+        let source = r#"{
+            "name": "caf\u00e9 \"noir\"",
+            "sizes": [1, -2.5e1, 0],
+            "nested": { "ok": true, "none": null, "empty": [] },
+            "emoji": "\ud83d\ude00 é"
+        }"#;
+        let t = parse(from_json, source).unwrap();
+
+        assert_eq!(
+            t.clone().without_pos(),
+            serde_json::from_str::<RichTerm>(source)
+                .unwrap()
+                .without_pos()
+        );
+        assert_eq!(text_at(source, &t, &["name"]), r#""caf\u00e9 \"noir\"""#);
+        assert_eq!(text_at(source, &t, &["sizes", "1"]), "-2.5e1");
+        assert_eq!(text_at(source, &t, &["nested", "none"]), "null");
+        assert_eq!(text_at(source, &t, &["nested", "empty"]), "[]");
+        assert_eq!(key_at(source, &t, "emoji"), r#""emoji""#);
+
+        for invalid in &[
+            "",
+            "[1,]",
+            "{\"a\" 1}",
+            "01",
+            "[1] 2",
+            "\"\\x\"",
+            "{1: 2}",
+            "tru",
+        ] {
+            assert!(parse(from_json, invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    /// The message and the source text of the span of a parse error.
+    fn error_at(source: &str, err: ParseError) -> (String, &str) {
+        match err {
+            ParseError::ExternalFormatError(_, msg, Some(span)) => {
+                (msg, &source[span.start.to_usize()..span.end.to_usize()])
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+
+    #[test]
+    fn json_escapes() {
+        let source = r#"["\\ \/ \" \b\f\n\r\t", "\u0041\u00e9\u20AC", "\ud83d\ude00", ""]"#;
+        let t = parse(from_json, source).unwrap();
+
+        assert_eq!(
+            t.without_pos(),
+            Term::Array(vec![
+                Term::Str(String::from("\\ / \" \u{8}\u{c}\n\r\t")).into(),
+                Term::Str(String::from("Aé€")).into(),
+                Term::Str(String::from("😀")).into(),
+                Term::Str(String::new()).into(),
+            ])
+            .into()
+        );
+
+        for (invalid, msg, at) in &[
+            (r#""\ud83d""#, "lone leading surrogate in hex escape", "\""),
+            (r#""\ud83d\u0041""#, "invalid surrogate in hex escape", "\""),
+            (r#""\u12G4""#, "invalid unicode escape", "1"),
+            (r#""\q""#, "invalid escape", "q"),
+            ("\"a\tb\"", "control character in string", "\t"),
+            ("\"abc", "EOF while parsing a string", ""),
+        ] {
+            let (err_msg, err_at) = error_at(invalid, parse(from_json, invalid).unwrap_err());
+            assert!(err_msg.starts_with(msg), "{}: {}", invalid, err_msg);
+            assert_eq!(err_at, *at, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn json_errors() {
+        let source = "{\n  \"a\": [1,\n  ]\n}";
+        let (msg, at) = error_at(source, parse(from_json, source).unwrap_err());
+        assert_eq!(msg, "expected value at line 3 column 3");
+        assert_eq!(at, "]");
+
+        let source = r#"{"é": 1 "b": 2}"#;
+        let (msg, at) = error_at(source, parse(from_json, source).unwrap_err());
+        assert_eq!(msg, "expected `,` or `}` at line 1 column 9");
+        assert_eq!(at, "\"");
+
+        for (invalid, at) in &[
+            ("01", "01"),
+            ("1.", ""),
+            ("-", ""),
+            (".5", "."),
+            ("1e+", ""),
+            ("nul", "n"),
+            ("[1 2]", "2"),
+            ("{\"a\": 1,}", "}"),
+        ] {
+            let (_, err_at) = error_at(invalid, parse(from_json, invalid).unwrap_err());
+            assert_eq!(err_at, *at, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn json_numbers() {
+        let source = "[-0, 1.5E+2, 2e-3, 1e400]";
+        let t = parse(from_json, source).unwrap();
+
+        assert_eq!(text_at(source, &t, &["1"]), "1.5E+2");
+        assert_eq!(text_at(source, &t, &["3"]), "1e400");
+        assert_eq!(
+            t.without_pos(),
+            Term::Array(
+                ["0", "150", "0.002", "1e400"]
+                    .iter()
+                    .map(|n| Term::Num(number::parse(n).unwrap()).into())
+                    .collect()
+            )
+            .into()
+        );
+
+        let source = "{\"a\": 1e1000000000}";
+        let (msg, at) = error_at(source, parse(from_json, source).unwrap_err());
+        assert_eq!(msg, "number out of range at line 1 column 7");
+        assert_eq!(at, "1e1000000000");
+    }
+
+    #[test]
+    fn nesting_limit() {
+        let nested = |depth| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+
+        assert!(parse(from_json, &nested(MAX_DEPTH)).is_ok());
+        assert!(parse(from_yaml, &nested(MAX_DEPTH)).is_ok());
+
+        let source = nested(MAX_DEPTH + 1);
+        let (msg, at) = error_at(&source, parse(from_json, &source).unwrap_err());
+        assert_eq!(
+            msg,
+            format!(
+                "recursion limit exceeded at line 1 column {}",
+                MAX_DEPTH + 1
+            )
+        );
+        assert_eq!(at, "[");
+
+        // Deep values are rejected without overflowing the stack.
+        assert!(parse(from_json, &"[".repeat(100_000)).is_err());
+        assert!(parse(from_json, &"{\"a\":".repeat(100_000)).is_err());
+
+        let source = (0..=MAX_DEPTH)
+            .map(|depth| format!("{}a:\n", " ".repeat(depth)))
+            .collect::<String>();
+        let (msg, _) = error_at(&source, parse(from_yaml, &source).unwrap_err());
+        assert!(msg.starts_with("recursion limit exceeded"), "{}", msg);
+    }
+
+    #[test]
+    fn yaml() {
+        let source = "\
+name: 'it''s' # comment
+ports: [80, \"443\", {a: 1}]
+é: &anchor
+  nested: 0x1F
+  list:
+    - plain text
+    - ~
+copy: *anchor
+octal: 0755
+";
+        let t = parse(from_yaml, source).unwrap();
+
+        assert_eq!(
+            t.clone().without_pos(),
+            serde_yaml::from_str::<RichTerm>(source)
+                .unwrap()
+                .without_pos()
+        );
+        assert_eq!(text_at(source, &t, &["name"]), "'it''s'");
+        assert_eq!(text_at(source, &t, &["ports"]), "[80, \"443\", {a: 1}]");
+        assert_eq!(text_at(source, &t, &["ports", "1"]), "\"443\"");
+        assert_eq!(text_at(source, &t, &["ports", "2", "a"]), "1");
+        assert_eq!(text_at(source, &t, &["é", "nested"]), "0x1F");
+        assert_eq!(text_at(source, &t, &["é", "list", "0"]), "plain text");
+        assert_eq!(text_at(source, &t, &["copy", "list", "1"]), "~");
+        assert_eq!(key_at(source, &t, "é"), "é");
+        assert_eq!(key_at(source, &t, "octal"), "octal");
+
+        assert!(parse(from_yaml, "a: [1\n").is_err());
+        assert!(parse(from_yaml, "a: 1\n---\nb: 2\n").is_err());
+        assert!(parse(from_yaml, "? [a]\n: 1\n").is_err());
+    }
+
+    #[test]
+    fn yaml_positions() {
+        let source = "\
+- \"a \\\" b\"
+- key: value # comment
+  other: {x: [1, 2], y: 'z'}
+- |
+  block
+  text
+- - nested
+  - 1.5
+";
+        let t = parse(from_yaml, source).unwrap();
+
+        assert_eq!(text_at(source, &t, &["0"]), "\"a \\\" b\"");
+        assert_eq!(text_at(source, &t, &["1", "key"]), "value");
+        assert_eq!(text_at(source, &t, &["1", "other"]), "{x: [1, 2], y: 'z'}");
+        assert_eq!(text_at(source, &t, &["1", "other", "x"]), "[1, 2]");
+        assert_eq!(text_at(source, &t, &["1", "other", "x", "1"]), "2");
+        assert_eq!(text_at(source, &t, &["1", "other", "y"]), "'z'");
+        assert_eq!(text_at(source, &t, &["2"]), "block");
+        assert_eq!(text_at(source, &t, &["3", "1"]), "1.5");
+        match t.as_ref() {
+            Term::Array(elts) => assert_eq!(key_at(source, &elts[1], "other"), "other"),
+            _ => panic!("not an array"),
+        }
+    }
+
+    #[test]
+    fn yaml_errors() {
+        for (invalid, msg, at) in &[
+            (
+                "a: &x 1\n*x : 2\n",
+                "mapping keys must be scalars at line 2 column 1",
+                "*",
+            ),
+            (
+                "a: !!int abc\n",
+                "invalid value: string \"abc\", expected an integer at line 1 column 10",
+                "abc",
+            ),
+            (
+                "a: 1e1000000000\n",
+                "number out of range: 1e1000000000 at line 1 column 4",
+                "1e1000000000",
+            ),
+        ] {
+            let (err_msg, err_at) = error_at(invalid, parse(from_yaml, invalid).unwrap_err());
+            assert_eq!(err_msg, *msg, "{}", invalid);
+            assert_eq!(err_at, *at, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn deserialize_string() {
+        let source = "{\"a\": [1e400, 123456789012345678901234567890]}";
+        let mut files = Files::new();
+        let file_id = files.add("<test>", String::from(source));
+
+        let t = from_string(from_json, source).unwrap();
+        assert_eq!(t, t.clone().without_pos());
+        assert_eq!(t, from_json(&files, file_id).unwrap().without_pos());
+
+        match t.as_ref() {
+            Term::Record(fields, _) => {
+                assert!(fields.keys().all(|id| id.pos == TermPos::None))
+            }
+            _ => panic!("not a record"),
+        }
+
+        assert_eq!(
+            from_string(from_json, "[1,\n2,]"),
+            Err(String::from("expected value at line 2 column 3"))
+        );
+        assert!(from_string(from_yaml, "a: [1\n").is_err());
+        assert!(from_string(from_toml, "a = \n").is_err());
+    }
+
+    #[test]
+    fn toml() {
+        let source = r#"
+name = "web"
+float = 2.5
+date = 1979-05-27T07:32:00Z
+inline = { a = 1, b = [true, 2.5] }
+
+[server]
+port = 8080
+
+[[users]]
+id = "a"
+
+[[users]]
+id = "b"
+"#;
+        let t = parse(from_toml, source).unwrap();
+
+        assert_eq!(text_at(source, &t, &["name"]), "\"web\"");
+        assert_eq!(text_at(source, &t, &["inline", "b", "0"]), "true");
+        assert_eq!(text_at(source, &t, &["float"]), "2.5");
+        assert_eq!(text_at(source, &t, &["server", "port"]), "8080");
+        assert_eq!(text_at(source, &t, &["users", "1", "id"]), "\"b\"");
+        assert_eq!(key_at(source, &t, "name"), "name");
+        assert_eq!(
+            t.clone().without_pos(),
+            serde_json::from_value::<RichTerm>(serde_json::json!({
+                "name": "web",
+                "float": 2.5,
+                "date": "1979-05-27T07:32:00Z",
+                "inline": { "a": 1, "b": [true, 2.5] },
+                "server": { "port": 8080 },
+                "users": [{ "id": "a" }, { "id": "b" }],
+            }))
+            .unwrap()
+            .without_pos()
+        );
+
+        assert!(parse(from_toml, "a = \n").is_err());
+    }
+
+    #[test]
+    fn exact_numbers() {
+        let big = || Term::Num(number::parse("123456789012345678901234567890").unwrap());
+        let dec = || Term::Num(number::parse("0.1").unwrap());
+
+        let json = parse(from_json, "[123456789012345678901234567890, 0.1]").unwrap();
+        let yaml = parse(from_yaml, "[123456789012345678901234567890, 0.1]").unwrap();
+        let toml = parse(from_toml, "a = 9007199254740993\nb = 0.1").unwrap();
+
+        assert_eq!(
+            json.without_pos(),
+            Term::Array(vec![big().into(), dec().into()]).into()
+        );
+        assert_eq!(
+            yaml.without_pos(),
+            Term::Array(vec![big().into(), dec().into()]).into()
+        );
+        assert_eq!(
+            toml.without_pos(),
+            crate::mk_record!(
+                ("a", Term::Num(Number::from(9007199254740993u64))),
+                ("b", dec())
+            )
+            .without_pos()
+        );
+        assert!(parse(from_yaml, "a: .nan").is_err());
+    }
+}
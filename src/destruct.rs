@@ -3,9 +3,12 @@
 
 use crate::identifier::Ident;
 use crate::label::Label;
+use crate::number::Number;
 use crate::position::RawSpan;
-use crate::term::{Contract, MetaValue, RecordAttrs, RichTerm, Term};
+use crate::term::make as mk_term;
+use crate::term::{BinaryOp, Contract, MetaValue, RecordAttrs, RichTerm, Term, UnaryOp};
 use crate::types::{AbsType, Types};
+use crate::{mk_app, mk_fun};
 
 /// A match field in a `Destruct` pattern.
 /// every field can contain a `MetaValue` either simply because they are annotated either because
@@ -20,34 +23,88 @@ pub enum Match {
     Simple(Ident, MetaValue),
 }
 
-/// Last match field of a `Destruct`.
+/// An element of an array pattern `[...]`. An element is either a simple binding, which can be
+/// annotated and have a default value (`a | Num ? 0`), or a nested destruct pattern, possibly
+/// bound to a variable (`x @ {a, b}`).
 #[derive(Debug, PartialEq, Clone)]
-pub enum LastMatch {
+pub struct ArrayMatch {
+    /// The annotations and the default value of the element.
+    pub meta: MetaValue,
+    /// The variable bound to the element, if any.
+    pub id: Option<Ident>,
+    /// The nested pattern. `Destruct::Empty` for a simple binding.
+    pub pattern: Destruct,
+}
+
+impl ArrayMatch {
+    /// Does this element have a default value, that is, can it be missing from the array?
+    pub fn has_default(&self) -> bool {
+        self.meta.value.is_some()
+    }
+}
+
+/// Last match field of a `Destruct`. `M` is the type of the matches: `Match` for record patterns
+/// and `ArrayMatch` for array patterns.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LastMatch<M = Match> {
     /// The last field is a normal match. In this case the pattern is "closed" so every record
-    /// fields should be matched.
-    Match(Match),
+    /// fields (or array elements) should be matched.
+    Match(M),
     /// The pattern is "open" `, ..}`. Optionaly you can bind a record containing the remaining
-    /// fields to an `Identifier` using the syntax `, ..y}`.
+    /// fields to an `Identifier` using the syntax `, ..y}`. For arrays, `, ..y]` binds the array
+    /// of the remaining elements.
     Ellipsis(Option<Ident>),
 }
 
 /// A destructuring pattern without the `x @` part.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Destruct {
-    /// A record pattern, the only one implemented for now.
+    /// A record pattern.
     Record {
         matches: Vec<Match>,
         open: bool,
         rest: Option<Ident>,
         span: RawSpan,
     },
-    /// An array destructuring. Not implemented.
-    Array { matches: Vec<Match>, span: RawSpan },
+    /// An array pattern. The elements are matched by position.
+    Array {
+        matches: Vec<ArrayMatch>,
+        open: bool,
+        rest: Option<Ident>,
+        span: RawSpan,
+    },
+    /// A constant pattern: a number, a string, a boolean, `null` or an enum tag. It matches values
+    /// which are equal to the constant, and doesn't bind anything.
+    Constant { value: RichTerm, span: RawSpan },
+    /// An enum variant pattern `` `Tag pat ``. It matches an enum variant with the given tag, whose
+    /// argument is matched by the nested pattern.
+    Variant {
+        tag: Ident,
+        arg: Box<(Option<Ident>, Destruct)>,
+        span: RawSpan,
+    },
     /// An empty destructuring. In this case, the pattern is a clasical `let var = something in
     /// body` form.
     Empty,
 }
 
+/// A case of a `match` expression: `pattern if guard => body`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchCase {
+    /// The pattern, with the optional `x @` part. A wildcard `_` is `(None, Destruct::Empty)`.
+    pub pattern: (Option<Ident>, Destruct),
+    /// An optional boolean guard. The case is only selected if the guard evaluates to `true`.
+    pub guard: Option<RichTerm>,
+    pub body: RichTerm,
+}
+
+impl MatchCase {
+    /// Does this case match any value, that is, is it an unguarded variable or wildcard?
+    pub fn is_catch_all(&self) -> bool {
+        self.pattern.1.is_empty() && self.guard.is_none()
+    }
+}
+
 impl Destruct {
     /// generate the metavalue containing the contract representing this pattern.
     pub fn as_contract(self) -> MetaValue {
@@ -56,37 +113,110 @@ impl Destruct {
     }
 
     fn as_contract_with_lbl(self, label: Label) -> MetaValue {
-        let open = self.is_open();
         MetaValue {
             contracts: vec![Contract {
-                types: Types(AbsType::Flat(
-                    Term::Record(
-                        self.inner()
-                            .into_iter()
-                            .map(|m| m.as_meta_field())
-                            .collect(),
-                        RecordAttrs { open },
-                    )
-                    .into(),
-                )),
+                types: Types(AbsType::Flat(self.contract_term())),
                 label,
             }],
             ..Default::default()
         }
     }
 
-    /// Get the inner vector of `Matches` of the pattern. If `Empty` return a empty vector.
+    /// Generate the term of the contract representing this pattern.
+    fn contract_term(self) -> RichTerm {
+        match self {
+            Destruct::Record { matches, open, .. } => Term::Record(
+                matches.into_iter().map(|m| m.as_meta_field()).collect(),
+                RecordAttrs {
+                    open,
+                    ..Default::default()
+                },
+            )
+            .into(),
+            // The contract of an array pattern only checks the length of the array. The
+            // contracts of the elements are applied when binding them (see
+            // `transform::desugar_destructuring`).
+            Destruct::Array { .. } => {
+                let (min, max) = self.length_bounds().unwrap();
+                let max = max.map_or(Term::Null, |max| Term::Num(Number::from(max)));
+                mk_app!(
+                    mk_term::var("$array_pattern"),
+                    Term::Num(Number::from(min)),
+                    max
+                )
+            }
+            Destruct::Constant { value, .. } => {
+                mk_app!(mk_term::var("$constant_pattern"), value)
+            }
+            // The argument of the variant is checked by the contract of the nested pattern, which
+            // is applied lazily by rebuilding the variant.
+            Destruct::Variant { tag, arg, .. } => {
+                let (_, pat) = *arg;
+                let arg_label = pat.label();
+                let arg_contract = match pat {
+                    Destruct::Empty => mk_term::var("$dyn"),
+                    pat => pat.contract_term(),
+                };
+                let wrap = mk_fun!(
+                    "c",
+                    "l",
+                    "x",
+                    Term::EnumVariant(
+                        tag.clone(),
+                        mk_app!(
+                            mk_term::op2(BinaryOp::Assume(), mk_term::var("c"), mk_term::var("l")),
+                            mk_term::op1(UnaryOp::EnumGetArg(), mk_term::var("x"))
+                        )
+                    )
+                );
+
+                mk_app!(
+                    mk_term::var("$variant_pattern"),
+                    Term::Enum(tag),
+                    arg_contract,
+                    Term::Lbl(arg_label),
+                    wrap
+                )
+            }
+            Destruct::Empty => Term::Record(Default::default(), Default::default()).into(),
+        }
+    }
+
+    /// Get the inner vector of `Matches` of a record pattern. Otherwise, return an empty vector.
     pub fn inner(self) -> Vec<Match> {
         match self {
-            Destruct::Record { matches, .. } | Destruct::Array { matches, .. } => matches,
-            Destruct::Empty => vec![],
+            Destruct::Record { matches, .. } => matches,
+            Destruct::Array { .. }
+            | Destruct::Constant { .. }
+            | Destruct::Variant { .. }
+            | Destruct::Empty => vec![],
+        }
+    }
+
+    /// For an array pattern, return the minimum and the maximum (if the pattern is closed) length
+    /// of an array matching this pattern. Elements with a default value may be missing, but only
+    /// if all the elements after them may be missing too.
+    pub fn length_bounds(&self) -> Option<(usize, Option<usize>)> {
+        match self {
+            Destruct::Array { matches, open, .. } => {
+                let min = matches
+                    .iter()
+                    .rposition(|m| !m.has_default())
+                    .map_or(0, |i| i + 1);
+                let max = if *open { None } else { Some(matches.len()) };
+                Some((min, max))
+            }
+            _ => None,
         }
     }
 
     // Generate a label for this `Destruct`. if `Empty`, return default label.
     fn label(&self) -> Label {
         match *self {
-            Destruct::Record { span, .. } | Destruct::Array { span, .. } => Label {
+            Destruct::Record { span, .. }
+            | Destruct::Array { span, .. }
+            | Destruct::Constant { span, .. }
+            | Destruct::Variant { span, .. } => Label {
                 span,
                 ..Default::default()
             },
@@ -96,13 +226,40 @@ impl Destruct {
 
     /// Is this pattern open? Does it finish with `, ..}` form?
     pub fn is_open(&self) -> bool {
-        matches!(self, Destruct::Record { open: true, .. })
+        matches!(
+            self,
+            Destruct::Record { open: true, .. } | Destruct::Array { open: true, .. }
+        )
     }
 
     /// check if the pattern is empty.
     pub fn is_empty(&self) -> bool {
         matches!(self, Destruct::Empty)
     }
+
+    /// Does this pattern match any value of the expected type? This is the case of variables and
+    /// wildcards, and of open record and array patterns whose sub-patterns are irrefutable. An
+    /// array pattern must also accept the empty array, that is, all its elements have a default
+    /// value.
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Destruct::Empty => true,
+            Destruct::Record { matches, open, .. } => {
+                *open
+                    && matches.iter().all(|m| match m {
+                        Match::Simple(..) => true,
+                        Match::Assign(_, _, (_, pat)) => pat.is_irrefutable(),
+                    })
+            }
+            Destruct::Array { matches, open, .. } => {
+                *open
+                    && matches
+                        .iter()
+                        .all(|m| m.has_default() && m.pattern.is_irrefutable())
+            }
+            Destruct::Constant { .. } | Destruct::Variant { .. } => false,
+        }
+    }
 }
 
 impl Match {
@@ -119,7 +276,17 @@ impl Match {
             //
             // - extra field on the destructuring `d`
             // - missing field on the `id`
-            Match::Assign(id, m, (_, d @ Destruct::Record { .. })) => {
+            Match::Assign(
+                id,
+                m,
+                (
+                    _,
+                    d @ (Destruct::Record { .. }
+                    | Destruct::Array { .. }
+                    | Destruct::Constant { .. }
+                    | Destruct::Variant { .. }),
+                ),
+            ) => {
                 let label @ Label { span, .. } = d.label();
                 let span = RawSpan::fuse(id.pos.unwrap(), span).unwrap();
                 let label = Label { span, ..label };
@@ -128,7 +295,6 @@ impl Match {
                     Term::MetaValue(MetaValue::flatten(m, d.as_contract_with_lbl(label))).into(),
                 )
             }
-            Match::Assign(_id, _m, (_, _d @ Destruct::Array { .. })) => unimplemented!(),
         }
     }
 }
@@ -22,7 +22,7 @@ use crate::{
     position::{RawSpan, TermPos},
     repl,
     serialize::ExportFormat,
-    term::RichTerm,
+    term::{MergePriority, RichTerm},
     types::Types,
 };
 
@@ -38,6 +38,18 @@ pub enum Error {
     ReplError(ReplError),
 }
 
+/// A warning, which is reported to the user but doesn't stop the execution.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// A field annotated with `deprecated` has been accessed, or defined by merging.
+    DeprecatedField(
+        /* field identifier, if known */ Option<Ident>,
+        /* deprecation message */ String,
+        /* position of the access or of the definition */ TermPos,
+        /* position of the deprecated field */ TermPos,
+    ),
+}
+
 /// An error occurring during evaluation.
 #[derive(Debug, Clone, PartialEq)]
 pub enum EvalError {
@@ -78,6 +90,7 @@ pub enum EvalError {
         /* left operand */ RichTerm,
         /* right operand */ RichTerm,
         /* original merge */ TermPos,
+        /* merge priority of both operands */ MergePriority,
     ),
     /// An unbound identifier was referenced.
     UnboundIdentifier(Ident, TermPos),
@@ -91,6 +104,11 @@ pub enum EvalError {
         String,  /* error message */
         TermPos, /* position of the call to deserialize */
     ),
+    /// No case of a `match` expression matched the tested value.
+    NonExhaustiveMatch(
+        /* the tested value */ RichTerm,
+        /* the match expression */ TermPos,
+    ),
     /// An unexpected internal error.
     InternalError(String, TermPos),
     /// Errors occurring rarely enough to not deserve a dedicated variant.
@@ -197,6 +215,9 @@ pub enum TypecheckError {
         /* the error on the subtype unification */ Box<TypecheckError>,
         TermPos,
     ),
+    /// A `match` expression on an enum doesn't handle some of the enum tags, and doesn't have a
+    /// catch-all case.
+    NonExhaustiveMatch(/* the unhandled tags */ Vec<Ident>, TermPos),
 }
 
 #[derive(Debug, PartialEq, Clone, Default)]
@@ -306,6 +327,11 @@ pub enum ParseError {
         RawSpan, /* tail position */
         RawSpan, /* whole record position */
     ),
+    /// An unknown or ill-formed strategy in an `array_merge` annotation. The valid strategies are
+    /// `concat`, `union`, `union_by "<field>"` and `replace`.
+    InvalidArrayMerge(RawSpan),
+    /// An unknown format in an import `import "<path>" as <format>`.
+    InvalidImportFormat(RawSpan),
 }
 
 /// An error occurring during the resolution of an import.
@@ -322,6 +348,21 @@ pub enum ImportError {
         /* error */ ParseErrors,
         /* import position */ TermPos,
     ),
+    /// Two files of an imported directory have the same name up to the extension, such as
+    /// `a.json` and `a.yaml`, and would define the same field.
+    DirectoryCollision(
+        /* imported directory */ String,
+        /* first file */ String,
+        /* second file */ String,
+        /* import position */ TermPos,
+    ),
+    /// An imported file belonging to a package doesn't match the lockfile. See
+    /// [`crate::package`].
+    LockfileMismatch(
+        /* imported file */ String,
+        /* error message */ String,
+        /* import position */ TermPos,
+    ),
 }
 
 /// An error occurred during serialization.
@@ -329,10 +370,19 @@ pub enum ImportError {
 pub enum SerializationError {
     /// Encountered a null value for a format that doesn't support them.
     UnsupportedNull(ExportFormat, RichTerm),
+    /// Encountered a number which can't be written exactly, or in the range of floats, in a
+    /// given format.
+    UnsupportedNumber(ExportFormat, RichTerm),
     /// Tried exporting something else than a `Str` to raw format.
     NotAString(RichTerm),
     /// A term contains constructs that cannot be serialized.
     NonSerializable(RichTerm),
+    /// An entry of a multi-file export is not a valid file description.
+    InvalidExportFile(
+        /* file path */ String,
+        /* reason */ String,
+        RichTerm,
+    ),
     Other(String),
 }
 
@@ -394,6 +444,66 @@ impl From<IOError> for Error {
     }
 }
 
+impl Error {
+    /// A stable identifier of the category of the error, used by machine-readable error reports
+    /// (see [`crate::report`]).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::EvalError(EvalError::BlameError(..)) => "blame_error",
+            Error::EvalError(_) => "eval_error",
+            Error::TypecheckError(_) => "typecheck_error",
+            Error::ParseErrors(_) => "parse_error",
+            Error::ImportError(_) => "import_error",
+            Error::SerializationError(_) => "serialization_error",
+            Error::IOError(_) => "io_error",
+            Error::ReplError(_) => "repl_error",
+        }
+    }
+}
+
+impl Warning {
+    /// A stable identifier of the category of the warning. See [`Error::kind`].
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Warning::DeprecatedField(..) => "deprecation_warning",
+        }
+    }
+}
+
+/// The sink collecting the warnings emitted during evaluation, which is passed to the evaluator
+/// together with the import resolver. Identical warnings, as emitted by a field accessed several
+/// times at the same place, are only recorded once.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Warnings(Vec<Warning>);
+
+impl Warnings {
+    pub fn new() -> Self {
+        Warnings(Vec::new())
+    }
+
+    /// Record a warning, unless an identical one is already pending. A deprecated field used
+    /// without a position, as when it is exported, is not reported again if it has already been
+    /// used elsewhere.
+    pub fn warn(&mut self, warning: Warning) {
+        let redundant = self.0.iter().any(|pending| match (pending, &warning) {
+            (
+                Warning::DeprecatedField(id1, _, _, decl1),
+                Warning::DeprecatedField(id2, _, TermPos::None, decl2),
+            ) => id1 == id2 && decl1 == decl2,
+            _ => false,
+        });
+
+        if !redundant && !self.0.contains(&warning) {
+            self.0.push(warning);
+        }
+    }
+
+    /// Take the pending warnings, leaving none.
+    pub fn take(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.0)
+    }
+}
+
 impl From<std::io::Error> for IOError {
     fn from(error: std::io::Error) -> IOError {
         IOError(error.to_string())
@@ -462,6 +572,10 @@ impl ParseError {
                 InternalParseError::InvalidUniRecord(illegal_pos, tail_pos, pos) => {
                     ParseError::InvalidUniRecord(illegal_pos, tail_pos, pos)
                 }
+                InternalParseError::InvalidArrayMerge(span) => ParseError::InvalidArrayMerge(span),
+                InternalParseError::InvalidImportFormat(span) => {
+                    ParseError::InvalidImportFormat(span)
+                }
             },
         }
     }
@@ -687,6 +801,9 @@ fn report_ty_path(l: &label::Label, files: &mut Files<String>) -> (Label<FileId>
         match l.path.last() {
             Some(ty_path::Elem::Array) => (String::from("expected array element type"), Vec::new()),
             Some(ty_path::Elem::Field(_)) => (String::from("expected field type"), Vec::new()),
+            Some(ty_path::Elem::Variant(_)) => {
+                (String::from("expected variant argument type"), Vec::new())
+            }
             _ => unreachable!(),
         }
     }
@@ -707,7 +824,7 @@ fn report_ty_path(l: &label::Label, files: &mut Files<String>) -> (Label<FileId>
             ],
         )
     } else {
-        // We ignore the `Field` and `Array` elements of the path, since they do not impact
+        // We ignore the `Field`, `Array` and `Variant` elements of the path, since they do not impact
         // polarity, and only consider "higher-order" elements to customize error messages.
         let last = l
             .path
@@ -1087,19 +1204,36 @@ impl ToDiagnostic<FileId> for EvalError {
                     .with_labels(labels)
                     .with_notes(notes)]
             }
-            EvalError::MergeIncompatibleArgs(t1, t2, span_opt) => {
+            EvalError::MergeIncompatibleArgs(t1, t2, span_opt, priority) => {
+                let with_priority = |msg: &str| format!("{} (priority {})", msg, priority);
+
                 let mut labels = vec![
-                    primary_term(t1, files).with_message("cannot merge this expression"),
-                    primary_term(t2, files).with_message("with this expression"),
+                    primary_term(t1, files)
+                        .with_message(with_priority("cannot merge this expression")),
+                    primary_term(t2, files).with_message(with_priority("with this expression")),
                 ];
 
                 if let TermPos::Original(span) | TermPos::Inherited(span) = span_opt {
                     labels.push(secondary(span).with_message("merged here"));
                 }
 
+                let note = match priority {
+                    MergePriority::Top => {
+                        "Both values are forced. Remove one of the `force` annotations."
+                    }
+                    MergePriority::Override => {
+                        "Both values are set by an override. Override each field only once."
+                    }
+                    _ => {
+                        "Both values have the same merge priority. Use a higher priority, with \
+                        `priority` or `force`, to override one value with the other."
+                    }
+                };
+
                 vec![Diagnostic::error()
                     .with_message("non mergeable terms")
-                    .with_labels(labels)]
+                    .with_labels(labels)
+                    .with_notes(vec![String::from(note)])]
             }
             EvalError::UnboundIdentifier(ident, span_opt) => vec![Diagnostic::error()
                 .with_message("unbound identifier")
@@ -1127,6 +1261,20 @@ impl ToDiagnostic<FileId> for EvalError {
 
                 vec![Diagnostic::error().with_message(msg).with_labels(labels)]
             }
+            EvalError::NonExhaustiveMatch(t, span_opt) => {
+                let mut labels = vec![primary_term(t, files).with_message("this value")];
+
+                if let Some(span) = span_opt.as_opt_ref() {
+                    labels.push(secondary(span).with_message("doesn't match any case here"));
+                }
+
+                vec![Diagnostic::error()
+                    .with_message("non-exhaustive match")
+                    .with_labels(labels)
+                    .with_notes(vec![String::from(
+                        "Add a catch-all case `_ => ...` to handle the remaining values.",
+                    )])]
+            }
             EvalError::InternalError(msg, span_opt) => {
                 let labels = span_opt
                     .as_opt_ref()
@@ -1220,6 +1368,18 @@ impl ToDiagnostic<FileId> for ParseError {
                     String::from("Using a polymorphic tail in a record `{ ..; a}` requires the rest of the record to be only composed of type annotations, of the form `<field>: <type>`."),
                     String::from("Value assignements, such as `<field> = <expr>`, metadata, etc. are forbidden."),
                 ]),
+            ParseError::InvalidArrayMerge(span) => Diagnostic::error()
+                .with_message("invalid array merge strategy")
+                .with_labels(vec![primary(span)])
+                .with_notes(vec![String::from(
+                    "Expected one of `concat`, `union`, `union_by \"<field>\"` or `replace`.",
+                )]),
+            ParseError::InvalidImportFormat(span) => Diagnostic::error()
+                .with_message("invalid import format")
+                .with_labels(vec![primary(span)])
+                .with_notes(vec![String::from(
+                    "Expected one of `nickel`, `json`, `yaml`, `toml`, `json_schema` or `text`.",
+                )]),
         };
 
         vec![diagnostic]
@@ -1434,6 +1594,17 @@ impl ToDiagnostic<FileId> for TypecheckError {
 
                 diags
             }
+            TypecheckError::NonExhaustiveMatch(tags, span_opt) => {
+                let tags: Vec<String> = tags.iter().map(|tag| format!("`{}", tag)).collect();
+
+                vec![Diagnostic::error()
+                    .with_message("non-exhaustive match")
+                    .with_labels(mk_expr_label(span_opt))
+                    .with_notes(vec![format!(
+                        "The following cases are not handled: {}",
+                        tags.join(", ")
+                    )])]
+            }
         }
     }
 }
@@ -1471,6 +1642,37 @@ impl ToDiagnostic<FileId> for ImportError {
 
                 diagnostic
             }
+            ImportError::DirectoryCollision(path, file1, file2, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![secondary(span).with_message("imported here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "import of {} failed: the files `{}` and `{}` would define the same field",
+                        path, file1, file2
+                    ))
+                    .with_labels(labels)
+                    .with_notes(vec![String::from(
+                        "The fields of an imported directory are named after its files without \
+                        their extension, which must be unique.",
+                    )])]
+            }
+            ImportError::LockfileMismatch(path, error, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![secondary(span).with_message("imported here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("import of {} failed: {}", path, error))
+                    .with_labels(labels)
+                    .with_notes(vec![String::from(
+                        "if the dependencies have been changed on purpose, run `nickel lock` to \
+                        create or update the lockfile",
+                    )])]
+            }
         }
     }
 }
@@ -1493,9 +1695,20 @@ impl ToDiagnostic<FileId> for SerializationError {
             SerializationError::UnsupportedNull(format, rt) => vec![Diagnostic::error()
                 .with_message(format!("{} doesn't support null values", format))
                 .with_labels(vec![primary_term(rt, files)])],
+            SerializationError::UnsupportedNumber(format, rt) => vec![Diagnostic::error()
+                .with_message(format!("{} can't represent this number", format))
+                .with_labels(vec![primary_term(rt, files)])
+                .with_notes(vec![String::from(
+                    "integers are exported exactly only if they fit in 128 bits (64 bits for \
+                    TOML), and other numbers must be in the range of 64 bits floats",
+                )])],
             SerializationError::NonSerializable(rt) => vec![Diagnostic::error()
                 .with_message("non serializable term")
                 .with_labels(vec![primary_term(rt, files)])],
+            SerializationError::InvalidExportFile(path, reason, rt) => vec![Diagnostic::error()
+                .with_message(format!("invalid export file `{}`", path))
+                .with_labels(vec![primary_term(rt, files)])
+                .with_notes(vec![reason.clone()])],
             SerializationError::Other(msg) => vec![Diagnostic::error()
                 .with_message("error during serialization")
                 .with_notes(vec![msg.clone()])],
@@ -1503,6 +1716,36 @@ impl ToDiagnostic<FileId> for SerializationError {
     }
 }
 
+impl ToDiagnostic<FileId> for Warning {
+    fn to_diagnostic(
+        &self,
+        _files: &mut Files<String>,
+        _contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        match self {
+            Warning::DeprecatedField(id, msg, usage, decl) => {
+                let message = match id {
+                    Some(id) => format!("use of deprecated field `{}`", id),
+                    None => String::from("use of a deprecated field"),
+                };
+
+                let mut labels = Vec::new();
+                if let Some(span) = usage.into_opt() {
+                    labels.push(primary(&span).with_message("used here"));
+                }
+                if let Some(span) = decl.into_opt() {
+                    labels.push(secondary(&span).with_message("deprecated here"));
+                }
+
+                vec![Diagnostic::warning()
+                    .with_message(message)
+                    .with_labels(labels)
+                    .with_notes(vec![msg.clone()])]
+            }
+        }
+    }
+}
+
 impl ToDiagnostic<FileId> for IOError {
     fn to_diagnostic(
         &self,
@@ -219,6 +219,11 @@ impl Thunk {
         self.ident_kind
     }
 
+    /// Determine if two thunks are the same, that is if they share their data.
+    pub fn ptr_eq(this: &Thunk, that: &Thunk) -> bool {
+        Rc::ptr_eq(&this.data, &that.data)
+    }
+
     /// Consume the thunk and return an owned closure. Avoid cloning if this thunk is the only
     /// reference to the inner closure.
     pub fn into_closure(self) -> Closure {
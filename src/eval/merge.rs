@@ -52,13 +52,16 @@
 //! - *Contract check*: merging a `Contract` or a `ContractDefault` with a simple value `t`
 //! evaluates to a contract check, that is an `Assume(..., t)`
 use super::*;
-use crate::error::EvalError;
+use crate::error::{EvalError, Warning, Warnings};
 use crate::label::Label;
 use crate::position::TermPos;
+use crate::stdlib::array_merge;
 use crate::term::{
-    make as mk_term, BinaryOp, Contract, MetaValue, RecordAttrs, RichTerm, SharedTerm, Term,
+    make as mk_term, ArrayMerge, BinaryOp, Contract, MergePriority, MetaValue, RecordAttrs,
+    RichTerm, SharedTerm, StrChunk, Term,
 };
 use crate::transform::Closurizable;
+use indexmap::IndexMap;
 use std::collections::HashMap;
 
 /// Merging mode. Merging is used both to combine standard data and to apply contracts defined as
@@ -84,6 +87,11 @@ impl Default for MergeMode {
 ///
 /// In [`MergeMode::Contract`] mode, `t1` must be the value and `t2` must be the contract. It is
 /// important as `merge` is not commutative in this mode.
+///
+/// # Warnings
+///
+/// When merging records, defining a value for a field marked as `deprecated` on the other side
+/// emits a warning.
 pub fn merge(
     t1: RichTerm,
     mut env1: Environment,
@@ -91,6 +99,7 @@ pub fn merge(
     mut env2: Environment,
     pos_op: TermPos,
     mode: MergeMode,
+    warnings: &mut Warnings,
 ) -> Result<Closure, EvalError> {
     // Merging a simple value and a metavalue is equivalent to first wrapping the simple value in a
     // new metavalue (with no attribute set excepted the value), and then merging the two
@@ -140,11 +149,12 @@ pub fn merge(
                         pos: pos2,
                     },
                     pos_op,
+                    MergePriority::default(),
                 ))
             }
         }
         (Term::Num(n1), Term::Num(n2)) => {
-            if (n1 - n2).abs() < f64::EPSILON {
+            if n1 == n2 {
                 Ok(Closure::atomic_closure(RichTerm::new(
                     Term::Num(n1),
                     pos_op.into_inherited(),
@@ -160,6 +170,7 @@ pub fn merge(
                         pos: pos2,
                     },
                     pos_op,
+                    MergePriority::default(),
                 ))
             }
         }
@@ -180,6 +191,7 @@ pub fn merge(
                         pos: pos2,
                     },
                     pos_op,
+                    MergePriority::default(),
                 ))
             }
         }
@@ -200,6 +212,7 @@ pub fn merge(
                         pos: pos2,
                     },
                     pos_op,
+                    MergePriority::default(),
                 ))
             }
         }
@@ -220,6 +233,7 @@ pub fn merge(
                         pos: pos2,
                     },
                     pos_op,
+                    MergePriority::default(),
                 ))
             }
         }
@@ -236,6 +250,10 @@ pub fn merge(
                 types: types1,
                 contracts: contracts1,
                 priority: priority1,
+                not_exported: not_exported1,
+                optional: optional1,
+                deprecated: deprecated1,
+                array_merge: array_merge1,
                 value: value1,
             } = meta1;
             let MetaValue {
@@ -243,10 +261,28 @@ pub fn merge(
                 types: types2,
                 contracts: contracts2,
                 priority: priority2,
+                not_exported: not_exported2,
+                optional: optional2,
+                deprecated: deprecated2,
+                array_merge: array_merge2,
                 value: value2,
             } = meta2;
 
             let doc = merge_doc(doc1, doc2);
+            let array_merge = array_merge1.or(array_merge2);
+
+            // Two distinct constants of the same priority can't be merged. This conflict is
+            // reported right away, as the priorities are lost once the values are merged.
+            if priority1 == priority2 {
+                let c1 = value1.as_ref().and_then(|v| constant(v, &env1));
+                let c2 = value2.as_ref().and_then(|v| constant(v, &env2));
+
+                if let (Some(c1), Some(c2)) = (c1, c2) {
+                    if c1.term != c2.term {
+                        return Err(EvalError::MergeIncompatibleArgs(c1, c2, pos_op, priority1));
+                    }
+                }
+            }
 
             // If:
             // 1. meta1 has a value
@@ -293,11 +329,13 @@ pub fn merge(
             let (value, priority, mut env) = match (value1, value2) {
                 (Some(t1), Some(t2)) if priority1 == priority2 => {
                     let mut env = Environment::new();
-                    (
-                        Some(merge_closurize(&mut env, t1, val_env1, t2, val_env2)),
-                        priority1,
-                        env,
-                    )
+                    let value = match array_merge {
+                        Some(ref strategy) => {
+                            merge_arrays_closurize(&mut env, strategy, t1, val_env1, t2, val_env2)
+                        }
+                        None => merge_closurize(&mut env, t1, val_env1, t2, val_env2),
+                    };
+                    (Some(value), priority1, env)
                 }
                 (Some(t1), _) if priority1 > priority2 => (Some(t1), priority1, val_env1),
                 (Some(t1), None) => (Some(t1), priority1, val_env1),
@@ -340,6 +378,11 @@ pub fn merge(
                 types,
                 contracts,
                 priority,
+                not_exported: not_exported1 || not_exported2,
+                // A field is optional only if it is optional on both sides.
+                optional: optional1 && optional2,
+                deprecated: deprecated1.or(deprecated2),
+                array_merge,
                 value,
             };
 
@@ -368,10 +411,21 @@ pub fn merge(
             let m1_values: Vec<_> = m1.values().cloned().collect();
             let m2_values: Vec<_> = m2.values().cloned().collect();
 
+            // The fields of the result are ordered as in the left operand, followed by the fields
+            // only defined in the right one.
+            let order: Vec<Ident> = m1.keys().chain(m2.keys()).cloned().collect();
+
+            let open2 = attrs2.open;
+            let attrs = RecordAttrs::merge(
+                attrs1,
+                attrs2,
+                |id| m1.contains_key(id),
+                |id| m2.contains_key(id),
+            );
             let (left, center, right) = hashmap::split(m1, m2);
 
             match mode {
-                MergeMode::Contract(mut lbl) if !attrs2.open && !left.is_empty() => {
+                MergeMode::Contract(mut lbl) if !open2 && !left.is_empty() => {
                     let fields: Vec<String> =
                         left.keys().map(|field| format!("`{}`", field)).collect();
                     let plural = if fields.len() == 1 { "" } else { "s" };
@@ -393,12 +447,18 @@ pub fn merge(
             }
 
             for (field, (t1, t2)) in center.into_iter() {
+                warn_deprecated_def(&field, &t1, &env1, &t2, &env2, warnings);
                 m.insert(
                     field,
                     merge_closurize(&mut env, t1, env1.clone(), t2, env2.clone()),
                 );
             }
 
+            let m: IndexMap<Ident, RichTerm> = order
+                .into_iter()
+                .filter_map(|id| m.remove(&id).map(|t| (id, t)))
+                .collect();
+
             let rec_env = fixpoint::rec_env(m.iter(), &env)?;
             m1_values
                 .iter()
@@ -408,10 +468,7 @@ pub fn merge(
                 .try_for_each(|rt| fixpoint::patch_field(rt, &rec_env, &env2))?;
 
             Ok(Closure {
-                body: RichTerm::new(
-                    Term::Record(m, RecordAttrs::merge(attrs1, attrs2)),
-                    pos_op.into_inherited(),
-                ),
+                body: RichTerm::new(Term::Record(m, attrs), pos_op.into_inherited()),
                 env,
             })
         }
@@ -426,6 +483,7 @@ pub fn merge(
                 pos: pos2,
             },
             pos_op,
+            MergePriority::default(),
         )),
     }
 }
@@ -462,6 +520,31 @@ fn cross_apply_contracts<'a>(
     Ok((result, env))
 }
 
+/// Return the value of a metavalue if it is a constant, that is a term which can only be merged
+/// with itself, possibly behind one variable. String literals are returned as strings.
+fn constant(t: &RichTerm, env: &Environment) -> Option<RichTerm> {
+    fn go(t: RichTerm) -> Option<RichTerm> {
+        match t.as_ref() {
+            Term::Null | Term::Bool(_) | Term::Num(_) | Term::Str(_) | Term::Enum(_) => Some(t),
+            Term::StrChunks(chunks) => chunks
+                .iter()
+                .rev()
+                .map(|chunk| match chunk {
+                    StrChunk::Literal(s) => Some(s.as_str()),
+                    StrChunk::Expr(..) => None,
+                })
+                .collect::<Option<String>>()
+                .map(|s| RichTerm::new(Term::Str(s), t.pos)),
+            _ => None,
+        }
+    }
+
+    match t.as_ref() {
+        Term::Var(id) => go(env.get(id)?.get_owned().body),
+        _ => go(t.clone()),
+    }
+}
+
 /// Merge the two optional documentations of a metavalue.
 fn merge_doc(doc1: Option<String>, doc2: Option<String>) -> Option<String> {
     //FIXME: how to merge documentation? Just concatenate?
@@ -486,6 +569,148 @@ fn merge_closurize(
     body.closurize(env, local_env)
 }
 
+/// Combine two values with an array merge strategy, closurize the result and return the
+/// corresponding variable.
+fn merge_arrays_closurize(
+    env: &mut Environment,
+    strategy: &ArrayMerge,
+    t1: RichTerm,
+    env1: Environment,
+    t2: RichTerm,
+    env2: Environment,
+) -> RichTerm {
+    let mut local_env = Environment::new();
+    let t1 = t1.closurize(&mut local_env, env1);
+    let t2 = t2.closurize(&mut local_env, env2);
+
+    let body = match strategy {
+        ArrayMerge::Concat => mk_term::op2(BinaryOp::ArrayConcat(), t1, t2),
+        ArrayMerge::Union => mk_app!(array_merge::union(), t1, t2),
+        ArrayMerge::UnionBy(key) => {
+            mk_app!(
+                array_merge::union_by(),
+                Term::Str(key.label.clone()),
+                t1,
+                t2
+            )
+        }
+        ArrayMerge::Replace => t2,
+    };
+    body.closurize(env, local_env)
+}
+
+/// Return the operands of a (possibly unevaluated) merge expression, from left to right.
+///
+/// Merge expressions are built by the interpreter as trees of [`BinaryOp::Merge`] whose leaves
+/// are variables pointing to the original terms. This function flattens such a tree, following
+/// variables through the environment, and returns each leaf as a closure. A term which is not a
+/// merge is returned as the only operand. This is used to explain where the value of a field
+/// comes from, and must be called before the field is evaluated.
+pub fn operands(clos: Closure, global_env: &Environment) -> Vec<Closure> {
+    fn go(clos: Closure, global_env: &Environment, seen: &mut Vec<Ident>, acc: &mut Vec<Closure>) {
+        let Closure { body, env } = clos;
+
+        match body.as_ref() {
+            Term::Op2(BinaryOp::Merge(), t1, t2) => {
+                go(
+                    Closure {
+                        body: t1.clone(),
+                        env: env.clone(),
+                    },
+                    global_env,
+                    seen,
+                    acc,
+                );
+                go(
+                    Closure {
+                        body: t2.clone(),
+                        env,
+                    },
+                    global_env,
+                    seen,
+                    acc,
+                );
+            }
+            // Recursive fields may refer to themselves: stop at the first repeated variable.
+            Term::Var(id) if !seen.contains(id) => {
+                match env.get(id).or_else(|| global_env.get(id)) {
+                    Some(thunk) => {
+                        seen.push(id.clone());
+                        go(thunk.get_owned(), global_env, seen, acc);
+                        seen.pop();
+                    }
+                    None => acc.push(Closure { body, env }),
+                }
+            }
+            _ => acc.push(Closure { body, env }),
+        }
+    }
+
+    let mut acc = Vec::new();
+    go(clos, global_env, &mut Vec::new(), &mut acc);
+    acc
+}
+
+/// Return the deprecation message of a record field, together with the position of the
+/// deprecated definition, if one of its merge operands (see [`operands`]) is annotated with
+/// `deprecated`.
+pub fn deprecation(clos: Closure) -> Option<(String, TermPos)> {
+    operands(clos, &Environment::new())
+        .into_iter()
+        .find_map(|clos| match clos.body.as_ref() {
+            Term::MetaValue(MetaValue {
+                deprecated: Some(msg),
+                ..
+            }) => Some((msg.clone(), clos.body.pos)),
+            _ => None,
+        })
+}
+
+/// Return the position of the first merge operand of a field (see [`operands`]) which defines a
+/// value, if any.
+fn definition(clos: Closure) -> Option<TermPos> {
+    operands(clos, &Environment::new())
+        .into_iter()
+        .find_map(|clos| match clos.body.as_ref() {
+            Term::MetaValue(MetaValue { value: Some(v), .. }) => Some(v.pos),
+            Term::MetaValue(_) => None,
+            _ => Some(clos.body.pos),
+        })
+}
+
+/// Emit a warning if a field marked as `deprecated` on one side of a merge is given a value by
+/// the other side, with the position of the definition and the position of the deprecated field.
+fn warn_deprecated_def(
+    id: &Ident,
+    t1: &RichTerm,
+    env1: &Environment,
+    t2: &RichTerm,
+    env2: &Environment,
+    warnings: &mut Warnings,
+) {
+    let clos1 = Closure {
+        body: t1.clone(),
+        env: env1.clone(),
+    };
+    let clos2 = Closure {
+        body: t2.clone(),
+        env: env2.clone(),
+    };
+
+    for (deprecated, other) in [(&clos1, &clos2), (&clos2, &clos1)] {
+        if let (Some((msg, decl_pos)), Some(def_pos)) =
+            (deprecation(deprecated.clone()), definition(other.clone()))
+        {
+            warnings.warn(Warning::DeprecatedField(
+                Some(id.clone()),
+                msg,
+                def_pos,
+                decl_pos,
+            ));
+        }
+    }
+}
+
 fn rev_thunks<'a, I: Iterator<Item = &'a mut RichTerm>>(map: I, env: &mut Environment) {
     use crate::transform::fresh_var;
 
@@ -503,24 +728,25 @@ fn rev_thunks<'a, I: Iterator<Item = &'a mut RichTerm>>(map: I, env: &mut Enviro
 }
 
 pub mod hashmap {
-    use std::collections::HashMap;
+    use indexmap::IndexMap;
 
-    /// Split two hashmaps m1 and m2 in three parts (left,center,right), where left holds bindings
+    /// Split two maps m1 and m2 in three parts (left,center,right), where left holds bindings
     /// `(key,value)` where key is not in `m2.keys()`, right is the dual (keys of m2 that are not
-    /// in m1), and center holds bindings for keys that are both in m1 and m2.
+    /// in m1), and center holds bindings for keys that are both in m1 and m2. Each part preserves
+    /// the relative order of the keys in the original maps.
     pub fn split<K, V1, V2>(
-        m1: HashMap<K, V1>,
-        m2: HashMap<K, V2>,
-    ) -> (HashMap<K, V1>, HashMap<K, (V1, V2)>, HashMap<K, V2>)
+        m1: IndexMap<K, V1>,
+        m2: IndexMap<K, V2>,
+    ) -> (IndexMap<K, V1>, IndexMap<K, (V1, V2)>, IndexMap<K, V2>)
     where
         K: std::hash::Hash + Eq,
     {
-        let mut left = HashMap::new();
-        let mut center = HashMap::new();
+        let mut left = IndexMap::new();
+        let mut center = IndexMap::new();
         let mut right = m2;
 
         for (key, value) in m1 {
-            if let Some(v2) = right.remove(&key) {
+            if let Some(v2) = right.shift_remove(&key) {
                 center.insert(key, (value, v2));
             } else {
                 left.insert(key, value);
@@ -536,8 +762,8 @@ pub mod hashmap {
 
         #[test]
         fn all_left() -> Result<(), String> {
-            let mut m1 = HashMap::new();
-            let m2 = HashMap::<isize, isize>::new();
+            let mut m1 = IndexMap::new();
+            let m2 = IndexMap::<isize, isize>::new();
 
             m1.insert(1, 1);
             let (mut left, center, right) = split(m1, m2);
@@ -555,8 +781,8 @@ pub mod hashmap {
 
         #[test]
         fn all_right() -> Result<(), String> {
-            let m1 = HashMap::<isize, isize>::new();
-            let mut m2 = HashMap::new();
+            let m1 = IndexMap::<isize, isize>::new();
+            let mut m2 = IndexMap::new();
 
             m2.insert(1, 1);
             let (left, center, mut right) = split(m1, m2);
@@ -576,8 +802,8 @@ pub mod hashmap {
 
         #[test]
         fn all_center() -> Result<(), String> {
-            let mut m1 = HashMap::new();
-            let mut m2 = HashMap::new();
+            let mut m1 = IndexMap::new();
+            let mut m2 = IndexMap::new();
 
             m1.insert(1, 1);
             m2.insert(1, 2);
@@ -598,8 +824,8 @@ pub mod hashmap {
 
         #[test]
         fn mixed() -> Result<(), String> {
-            let mut m1 = HashMap::new();
-            let mut m2 = HashMap::new();
+            let mut m1 = IndexMap::new();
+            let mut m2 = IndexMap::new();
 
             m1.insert(1, 1);
             m1.insert(2, 1);
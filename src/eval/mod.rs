@@ -91,7 +91,7 @@
 use crate::{
     cache::ImportResolver,
     environment::Environment as GenericEnvironment,
-    error::EvalError,
+    error::{EvalError, Warnings},
     identifier::Ident,
     match_sharedterm, mk_app,
     term::{
@@ -183,11 +183,19 @@ pub fn eval<R>(
     t0: RichTerm,
     global_env: &Environment,
     resolver: &mut R,
+    warnings: &mut Warnings,
 ) -> Result<RichTerm, EvalError>
 where
     R: ImportResolver,
 {
-    eval_closure(Closure::atomic_closure(t0), global_env, resolver, true).map(|(term, _)| term)
+    eval_closure(
+        Closure::atomic_closure(t0),
+        global_env,
+        resolver,
+        warnings,
+        true,
+    )
+    .map(|(term, _)| term)
 }
 
 /// Fully evaluate a Nickel term: the result is not a WHNF but to a value with all variables substituted.
@@ -195,11 +203,13 @@ pub fn eval_full<R>(
     t0: RichTerm,
     global_env: &Environment,
     resolver: &mut R,
+    warnings: &mut Warnings,
 ) -> Result<RichTerm, EvalError>
 where
     R: ImportResolver,
 {
-    eval_deep_closure(t0, global_env, resolver).map(|(term, env)| subst(term, global_env, &env))
+    eval_deep_closure(t0, global_env, resolver, warnings)
+        .map(|(term, env)| subst(term, global_env, &env))
 }
 
 /// Fully evaluates a Nickel term like `eval_full`, but does not substitute all variables.
@@ -207,17 +217,19 @@ pub fn eval_deep<R>(
     t0: RichTerm,
     global_env: &Environment,
     resolver: &mut R,
+    warnings: &mut Warnings,
 ) -> Result<RichTerm, EvalError>
 where
     R: ImportResolver,
 {
-    eval_deep_closure(t0, global_env, resolver).map(|(term, _)| term)
+    eval_deep_closure(t0, global_env, resolver, warnings).map(|(term, _)| term)
 }
 
 fn eval_deep_closure<R>(
     t0: RichTerm,
     global_env: &Environment,
     resolver: &mut R,
+    warnings: &mut Warnings,
 ) -> Result<(RichTerm, Environment), EvalError>
 where
     R: ImportResolver,
@@ -234,7 +246,13 @@ where
             Term::Var(var)
         ),
     );
-    eval_closure(Closure::atomic_closure(wrapper), global_env, resolver, true)
+    eval_closure(
+        Closure::atomic_closure(wrapper),
+        global_env,
+        resolver,
+        warnings,
+        true,
+    )
 }
 
 /// Evaluate a Nickel Term, stopping when a meta value is encountered at the top-level without
@@ -246,17 +264,36 @@ pub fn eval_meta<R>(
     t: RichTerm,
     global_env: &Environment,
     resolver: &mut R,
+    warnings: &mut Warnings,
 ) -> Result<RichTerm, EvalError>
 where
     R: ImportResolver,
 {
-    let (mut rt, env) = eval_closure(Closure::atomic_closure(t), global_env, resolver, false)?;
+    eval_meta_closure(Closure::atomic_closure(t), global_env, resolver, warnings)
+}
+
+/// Same as [`eval_meta`], but for a term in an environment.
+pub fn eval_meta_closure<R>(
+    clos: Closure,
+    global_env: &Environment,
+    resolver: &mut R,
+    warnings: &mut Warnings,
+) -> Result<RichTerm, EvalError>
+where
+    R: ImportResolver,
+{
+    let (mut rt, env) = eval_closure(clos, global_env, resolver, warnings, false)?;
 
     match *SharedTerm::make_mut(&mut rt.term) {
         Term::MetaValue(ref mut meta) => {
             if let Some(t) = meta.value.take() {
-                let (evaluated, env) =
-                    eval_closure(Closure { body: t, env }, global_env, resolver, true)?;
+                let (evaluated, env) = eval_closure(
+                    Closure { body: t, env },
+                    global_env,
+                    resolver,
+                    warnings,
+                    true,
+                )?;
                 let substituted = subst(evaluated, global_env, &env);
 
                 meta.value = Some(substituted);
@@ -280,6 +317,7 @@ where
 /// - `global_env`: the global environment containing the builtin functions of the language. Accessible from anywhere in the
 /// program.
 /// - `resolver`: the interface to fetch imports.
+/// - `warnings`: the sink collecting the warnings emitted during evaluation.
 /// - `enriched_strict`: if evaluation is strict with respect to enriched values (metavalues).
 ///   Standard evaluation should be strict, but set to false when extracting the metadata of value.
 ///
@@ -292,6 +330,7 @@ pub fn eval_closure<R>(
     mut clos: Closure,
     global_env: &Environment,
     resolver: &mut R,
+    warnings: &mut Warnings,
     mut enriched_strict: bool,
 ) -> Result<(RichTerm, Environment), EvalError>
 where
@@ -594,7 +633,7 @@ where
                     ));
                 }
             }
-            Term::Import(path) => {
+            Term::Import(path, _) => {
                 return Err(EvalError::InternalError(
                     format!("Unresolved import ({})", path.to_string_lossy()),
                     pos,
@@ -613,7 +652,7 @@ where
                     update_thunks(&mut stack, &clos);
                     clos
                 } else {
-                    continuate_operation(clos, &mut stack, &mut call_stack)?
+                    continuate_operation(clos, &mut stack, &mut call_stack, warnings)?
                 }
             }
             // Function call
@@ -690,7 +729,7 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
             | v @ Term::Sym(_)
             | v @ Term::Var(_)
             | v @ Term::Enum(_)
-            | v @ Term::Import(_)
+            | v @ Term::Import(..)
             | v @ Term::ResolvedImport(_) => RichTerm::new(v, pos),
             Term::Let(id, t1, t2, btype) => {
                 let t1 = subst_(t1, global_env, env, Cow::Borrowed(bound.as_ref()));
@@ -700,6 +739,7 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
             }
             p @ Term::LetPattern(..) => panic!("Pattern {:?} has not been transformed before evaluation", p),
             p @ Term::FunPattern(..) => panic!("Pattern {:?} has not been transformed before evaluation", p),
+            p @ Term::Match(..) => panic!("Pattern {:?} has not been transformed before evaluation", p),
             Term::App(t1, t2) => {
                 let t1 = subst_(t1, global_env, env, Cow::Borrowed(bound.as_ref()));
                 let t2 = subst_(t2, global_env, env, bound);
@@ -746,6 +786,11 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
 
                 RichTerm::new(Term::Wrapped(i, t), pos)
             }
+            Term::EnumVariant(tag, t) => {
+                let t = subst_(t, global_env, env, bound);
+
+                RichTerm::new(Term::EnumVariant(tag, t), pos)
+            }
             Term::Record(map, attrs) => {
                 let map = map
                     .into_iter()
@@ -14,10 +14,12 @@ use super::{
 };
 
 use crate::{
-    error::EvalError,
+    deserialize,
+    error::{EvalError, Warning, Warnings},
     identifier::Ident,
     label::ty_path,
     match_sharedterm, mk_app, mk_fun, mk_opn, mk_record,
+    number::{self, Number, NumberError},
     position::TermPos,
     serialize,
     serialize::ExportFormat,
@@ -27,6 +29,7 @@ use crate::{
 };
 use md5::digest::Digest;
 use simple_counter::*;
+use std::convert::TryFrom;
 use std::iter::Extend;
 
 generate_counter!(FreshVariableCounter, usize);
@@ -94,12 +97,13 @@ pub fn continuate_operation(
     mut clos: Closure,
     stack: &mut Stack,
     call_stack: &mut CallStack,
+    warnings: &mut Warnings,
 ) -> Result<Closure, EvalError> {
     let (cont, cs_len, pos) = stack.pop_op_cont().expect("Condition already checked");
     call_stack.truncate(cs_len);
     match cont {
         OperationCont::Op1(u_op, arg_pos) => {
-            process_unary_operation(u_op, clos, arg_pos, stack, call_stack, pos)
+            process_unary_operation(u_op, clos, arg_pos, stack, call_stack, pos, warnings)
         }
         OperationCont::Op2First(b_op, mut snd_clos, fst_pos) => {
             std::mem::swap(&mut clos, &mut snd_clos);
@@ -111,7 +115,13 @@ pub fn continuate_operation(
             Ok(clos)
         }
         OperationCont::Op2Second(b_op, fst_clos, fst_pos, snd_pos) => process_binary_operation(
-            b_op, fst_clos, fst_pos, clos, snd_pos, stack, call_stack, pos,
+            b_op,
+            (fst_clos, fst_pos),
+            (clos, snd_pos),
+            stack,
+            call_stack,
+            pos,
+            warnings,
         ),
         OperationCont::OpN {
             op,
@@ -136,12 +146,70 @@ pub fn continuate_operation(
 
                 Ok(next)
             } else {
-                process_nary_operation(op, evaluated, stack, pos)
+                process_nary_operation(op, evaluated, stack, pos, warnings)
             }
         }
     }
 }
 
+/// Return the SHA-256 hash of some data as a hexadecimal string, as computed by `%hash%`. Also
+/// used to check the content of packages (see [`crate::package`]).
+pub fn sha256(data: impl AsRef<[u8]>) -> String {
+    let mut hasher = sha2::Sha256::new();
+    hasher.update(data);
+    format!("{:x}", hasher.finalize())
+}
+
+/// The error raised when the result of an arithmetic operation exceeds the size limits of numbers
+/// (see [`number::MAX_BITS`]).
+fn too_large(op: &str, pos_op: TermPos) -> EvalError {
+    EvalError::Other(
+        format!(
+            "{}: the result is too large (numbers are limited to {} bits)",
+            op,
+            number::MAX_BITS
+        ),
+        pos_op,
+    )
+}
+
+/// Determine if a field inserted in a record is an optional field without a value (see
+/// [`Term::is_empty_optional`]), looking through the variable introduced for the field by the
+/// share normal form transformation.
+fn is_empty_optional(clos: &Closure) -> bool {
+    match clos.body.as_ref() {
+        Term::Var(id) if id.is_generated() => clos
+            .env
+            .get(id)
+            .map(|thunk| thunk.borrow().body.as_ref().is_empty_optional())
+            .unwrap_or(false),
+        t => t.is_empty_optional(),
+    }
+}
+
+/// Emit a warning if a field being accessed is deprecated. See [`merge::deprecation`].
+fn warn_deprecated(
+    id: &Ident,
+    t: &RichTerm,
+    env: &Environment,
+    pos_op: TermPos,
+    warnings: &mut Warnings,
+) {
+    let clos = Closure {
+        body: t.clone(),
+        env: env.clone(),
+    };
+
+    if let Some((msg, decl_pos)) = merge::deprecation(clos) {
+        warnings.warn(Warning::DeprecatedField(
+            Some(id.clone()),
+            msg,
+            pos_op,
+            decl_pos,
+        ));
+    }
+}
+
 /// Evaluate a unary operation.
 ///
 /// The argument is expected to be evaluated (in WHNF). `pos_op` corresponds to the whole
@@ -153,6 +221,7 @@ fn process_unary_operation(
     stack: &mut Stack,
     call_stack: &mut CallStack,
     pos_op: TermPos,
+    warnings: &mut Warnings,
 ) -> Result<Closure, EvalError> {
     let Closure {
         body: RichTerm { term: t, pos },
@@ -298,6 +367,10 @@ fn process_unary_operation(
                 ))
             }
         }
+        UnaryOp::NonExhaustiveMatch() => Err(EvalError::NonExhaustiveMatch(
+            RichTerm { term: t, pos },
+            pos_op,
+        )),
         UnaryOp::Switch(has_default) => {
             let (cases_closure, ..) = stack.pop_arg().expect("missing arg for switch");
             let default = if has_default {
@@ -459,6 +532,7 @@ fn process_unary_operation(
             if let Term::Record(static_map, ..) = &*t {
                 match static_map.get(&id) {
                     Some(e) => {
+                        warn_deprecated(&id, e, &env, pos_op, warnings);
                         call_stack.enter_field(id, pos, e.pos, pos_op);
                         Ok(Closure {
                             body: e.clone(),
@@ -482,8 +556,12 @@ fn process_unary_operation(
             }
         }
         UnaryOp::FieldsOf() => match_sharedterm! {t, with {
-                Term::Record(map, ..) => {
-                    let mut fields: Vec<String> = map.into_keys().map(|id| id.to_string()).collect();
+                Term::Record(map, attrs) => {
+                    let mut fields: Vec<String> = map
+                        .into_keys()
+                        .filter(|id| !attrs.empty_optional.contains(id))
+                        .map(|id| id.to_string())
+                        .collect();
                     fields.sort();
                     let terms = fields.into_iter().map(mk_term::string).collect();
                     Ok(Closure::atomic_closure(RichTerm::new(
@@ -501,8 +579,11 @@ fn process_unary_operation(
             }
         },
         UnaryOp::ValuesOf() => match_sharedterm! {t, with {
-                Term::Record(map, ..) => {
-                    let mut values: Vec<_> = map.into_iter().collect();
+                Term::Record(map, attrs) => {
+                    let mut values: Vec<_> = map
+                        .into_iter()
+                        .filter(|(id, _)| !attrs.empty_optional.contains(id))
+                        .collect();
                     // Although it seems that sort_by_key would be easier here, it would actually
                     // require to copy the identifiers because of the lack of HKT. See
                     // https://github.com/rust-lang/rust/issues/34162.
@@ -562,17 +643,8 @@ fn process_unary_operation(
                 .pop_arg()
                 .ok_or_else(|| EvalError::NotEnoughArgs(2, String::from("generate"), pos_op))?;
 
-            if let Term::Num(n) = *t {
-                let n_int = n as usize;
-                if n < 0.0 || n.fract() != 0.0 {
-                    Err(EvalError::Other(
-                        format!(
-                            "generate: expected the 1st agument to be a positive integer, got {}",
-                            n
-                        ),
-                        pos_op,
-                    ))
-                } else {
+            if let Term::Num(ref n) = *t {
+                if let Ok(n_int) = usize::try_from(n) {
                     let mut shared_env = Environment::new();
                     let f_as_var = f.body.closurize(&mut env, f.env);
 
@@ -581,7 +653,7 @@ fn process_unary_operation(
                     // currently, variables).
                     let ts = (0..n_int)
                         .map(|n| {
-                            mk_app!(f_as_var.clone(), Term::Num(n as f64))
+                            mk_app!(f_as_var.clone(), Term::Num(Number::from(n)))
                                 .closurize(&mut shared_env, env.clone())
                         })
                         .collect();
@@ -590,6 +662,14 @@ fn process_unary_operation(
                         body: RichTerm::new(Term::Array(ts), pos_op_inh),
                         env: shared_env,
                     })
+                } else {
+                    Err(EvalError::Other(
+                        format!(
+                            "generate: expected the 1st agument to be a positive integer, got {}",
+                            number::format(n)
+                        ),
+                        pos_op,
+                    ))
                 }
             } else {
                 Err(EvalError::TypeError(
@@ -607,12 +687,16 @@ fn process_unary_operation(
 
             match_sharedterm! {t, with {
                     Term::Record(rec, attr) => {
+                        let mut attr = attr;
                         let mut shared_env = Environment::new();
                         let f_as_var = f.body.closurize(&mut env, f.env);
 
-                        // As for `ArrayMap` (see above), we closurize the content of fields
+                        // As for `ArrayMap` (see above), we closurize the content of fields.
+                        // Optional fields without a value are absent, and are not mapped.
+                        let empty_optional = std::mem::take(&mut attr.empty_optional);
                         let rec = rec
                             .into_iter()
+                            .filter(|(id, _)| !empty_optional.contains(id))
                             .map(|e| {
                                 let (id, t) = e;
                                 let pos = t.pos.into_inherited();
@@ -673,8 +757,26 @@ fn process_unary_operation(
                 Closure { body, env }
             }
 
-            match t.into_owned() {
+            let t = match t.into_owned() {
+                // Optional fields without a value are absent, and must not be forced.
+                Term::Record(map, mut attrs) => {
+                    let empty_optional = std::mem::take(&mut attrs.empty_optional);
+                    let map = map
+                        .into_iter()
+                        .filter(|(id, _)| !empty_optional.contains(id))
+                        .collect();
+                    Term::Record(map, attrs)
+                }
+                t => t,
+            };
+
+            match t {
                 Term::Record(map, _) if !map.is_empty() => {
+                    // Forcing a deprecated field, as when exporting it, is a use of the field.
+                    for (id, t) in map.iter() {
+                        warn_deprecated(id, t, &env, pos_op, warnings);
+                    }
+
                     let pos_record = pos;
                     let pos_access = pos_op;
                     let terms = map.into_iter().map(|(id, t)| {
@@ -693,6 +795,7 @@ fn process_unary_operation(
                 Term::Array(ts) if !ts.is_empty() => {
                     Ok(seq_terms(ts.into_iter().map(|t| (None, t)), env, pos_op))
                 }
+                Term::EnumVariant(_, t) => Ok(seq_terms(std::iter::once((None, t)), env, pos_op)),
                 _ => {
                     if let Some((next, ..)) = stack.pop_arg() {
                         Ok(next)
@@ -746,7 +849,7 @@ fn process_unary_operation(
             if let Term::Array(ts) = &*t {
                 // A num does not have any free variable so we can drop the environment
                 Ok(Closure {
-                    body: RichTerm::new(Term::Num(ts.len() as f64), pos_op_inh),
+                    body: RichTerm::new(Term::Num(Number::from(ts.len())), pos_op_inh),
                     env: Environment::new(),
                 })
             } else {
@@ -842,9 +945,9 @@ fn process_unary_operation(
         UnaryOp::CharCode() => {
             if let Term::Str(s) = &*t {
                 if s.len() == 1 {
-                    let code = (s.chars().next().unwrap() as u32) as f64;
+                    let code = s.chars().next().unwrap() as u32;
                     Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(code),
+                        Term::Num(Number::from(code)),
                         pos_op_inh,
                     )))
                 } else {
@@ -863,12 +966,12 @@ fn process_unary_operation(
             }
         }
         UnaryOp::CharFromCode() => {
-            if let Term::Num(code) = *t {
-                if code.fract() != 0.0 {
-                    Err(EvalError::Other(format!("charFromCode: expected the agument to be an integer, got the floating-point value {}", code), pos_op))
-                } else if code < 0.0 || code > (u32::MAX as f64) {
+            if let Term::Num(ref code) = *t {
+                if !code.is_integer() {
+                    Err(EvalError::Other(format!("charFromCode: expected the agument to be an integer, got the floating-point value {}", number::format(code)), pos_op))
+                } else if u32::try_from(code).is_err() {
                     Err(EvalError::Other(format!("charFromCode: code out of bounds. Expected a value between 0 and {}, got {}", u32::MAX, code), pos_op))
-                } else if let Some(car) = std::char::from_u32(code as u32) {
+                } else if let Some(car) = u32::try_from(code).ok().and_then(std::char::from_u32) {
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Str(String::from(car)),
                         pos_op_inh,
@@ -921,7 +1024,7 @@ fn process_unary_operation(
         UnaryOp::StrLength() => {
             if let Term::Str(s) = &*t {
                 Ok(Closure::atomic_closure(RichTerm::new(
-                    Term::Num(s.len() as f64),
+                    Term::Num(Number::from(s.len())),
                     pos_op_inh,
                 )))
             } else {
@@ -935,7 +1038,7 @@ fn process_unary_operation(
         }
         UnaryOp::ToStr() => {
             let result = match &*t {
-                Term::Num(n) => Ok(Term::Str(n.to_string())),
+                Term::Num(n) => Ok(Term::Str(number::format(n))),
                 Term::Str(s) => Ok(Term::Str(s.clone())),
                 Term::Bool(b) => Ok(Term::Str(b.to_string())),
                 Term::Enum(id) => Ok(Term::Str(id.to_string())),
@@ -951,8 +1054,14 @@ fn process_unary_operation(
         }
         UnaryOp::NumFromStr() => {
             if let Term::Str(s) = &*t {
-                let n = s.parse::<f64>().map_err(|_| {
-                    EvalError::Other(format!("numFrom: invalid num literal `{}`", s), pos)
+                let n = number::parse(s).map_err(|err| match err {
+                    NumberError::Invalid => {
+                        EvalError::Other(format!("numFrom: invalid num literal `{}`", s), pos)
+                    }
+                    NumberError::TooLarge => EvalError::Other(
+                        format!("numFrom: the num literal `{}` is too large", s),
+                        pos,
+                    ),
                 })?;
                 Ok(Closure::atomic_closure(RichTerm::new(
                     Term::Num(n),
@@ -982,6 +1091,36 @@ fn process_unary_operation(
                 ))
             }
         }
+        UnaryOp::EnumIsVariant() => Ok(Closure::atomic_closure(RichTerm::new(
+            Term::Bool(matches!(*t, Term::EnumVariant(..))),
+            pos_op_inh,
+        ))),
+        UnaryOp::EnumGetTag() => match &*t {
+            Term::Enum(id) | Term::EnumVariant(id, _) => Ok(Closure::atomic_closure(
+                RichTerm::new(Term::Enum(id.clone()), pos_op_inh),
+            )),
+            _ => Err(EvalError::TypeError(
+                String::from("Enum"),
+                String::from("enum_get_tag"),
+                arg_pos,
+                RichTerm { term: t, pos },
+            )),
+        },
+        UnaryOp::EnumGetArg() => {
+            if let Term::EnumVariant(_, arg) = &*t {
+                Ok(Closure {
+                    body: arg.clone(),
+                    env,
+                })
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Enum"),
+                    String::from("enum_get_arg"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
     }
 }
 
@@ -991,13 +1130,12 @@ fn process_unary_operation(
 /// operation position, that may be needed for error reporting.
 fn process_binary_operation(
     b_op: BinaryOp,
-    fst_clos: Closure,
-    fst_pos: TermPos,
-    clos: Closure,
-    snd_pos: TermPos,
+    (fst_clos, fst_pos): (Closure, TermPos),
+    (clos, snd_pos): (Closure, TermPos),
     stack: &mut Stack,
     call_stack: &mut CallStack,
     pos_op: TermPos,
+    warnings: &mut Warnings,
 ) -> Result<Closure, EvalError> {
     let Closure {
         body: RichTerm {
@@ -1017,8 +1155,8 @@ fn process_binary_operation(
 
     match b_op {
         BinaryOp::Plus() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
+            if let Term::Num(ref n1) = *t1 {
+                if let Term::Num(ref n2) = *t2 {
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Num(n1 + n2),
                         pos_op_inh,
@@ -1047,8 +1185,8 @@ fn process_binary_operation(
             }
         }
         BinaryOp::Sub() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
+            if let Term::Num(ref n1) = *t1 {
+                if let Term::Num(ref n2) = *t2 {
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Num(n1 - n2),
                         pos_op_inh,
@@ -1077,10 +1215,11 @@ fn process_binary_operation(
             }
         }
         BinaryOp::Mult() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
+            if let Term::Num(ref n1) = *t1 {
+                if let Term::Num(ref n2) = *t2 {
+                    let result = number::check_size(n1 * n2).map_err(|_| too_large("*", pos_op))?;
                     Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(n1 * n2),
+                        Term::Num(result),
                         pos_op_inh,
                     )))
                 } else {
@@ -1107,13 +1246,15 @@ fn process_binary_operation(
             }
         }
         BinaryOp::Div() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    if n2 == 0.0 {
+            if let Term::Num(ref n1) = *t1 {
+                if let Term::Num(ref n2) = *t2 {
+                    if n2.is_zero() {
                         Err(EvalError::Other(String::from("division by zero"), pos_op))
                     } else {
+                        let result =
+                            number::check_size(n1 / n2).map_err(|_| too_large("/", pos_op))?;
                         Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Num(n1 / n2),
+                            Term::Num(result),
                             pos_op_inh,
                         )))
                     }
@@ -1141,12 +1282,16 @@ fn process_binary_operation(
             }
         }
         BinaryOp::Modulo() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(n1 % n2),
-                        pos_op_inh,
-                    )))
+            if let Term::Num(ref n1) = *t1 {
+                if let Term::Num(ref n2) = *t2 {
+                    if let Some(result) = number::rem(n1, n2) {
+                        Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Num(result),
+                            pos_op_inh,
+                        )))
+                    } else {
+                        Err(EvalError::Other(String::from("division by zero"), pos_op))
+                    }
                 } else {
                     Err(EvalError::TypeError(
                         String::from("Num"),
@@ -1171,12 +1316,23 @@ fn process_binary_operation(
             }
         }
         BinaryOp::Pow() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(n1.powf(n2)),
-                        pos_op_inh,
-                    )))
+            if let Term::Num(ref n1) = *t1 {
+                if let Term::Num(ref n2) = *t2 {
+                    match number::pow(n1, n2) {
+                        Ok(result) => Ok(Closure::atomic_closure(RichTerm::new(
+                            Term::Num(result),
+                            pos_op_inh,
+                        ))),
+                        Err(NumberError::Invalid) => Err(EvalError::Other(
+                            format!(
+                                "pow: {} to the power {} is not a number",
+                                number::format(n1),
+                                number::format(n2)
+                            ),
+                            pos_op,
+                        )),
+                        Err(NumberError::TooLarge) => Err(too_large("pow", pos_op)),
+                    }
                 } else {
                     Err(EvalError::TypeError(
                         String::from("Num"),
@@ -1416,8 +1572,8 @@ fn process_binary_operation(
             }
         }
         BinaryOp::LessThan() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
+            if let Term::Num(ref n1) = *t1 {
+                if let Term::Num(ref n2) = *t2 {
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Bool(n1 < n2),
                         pos_op_inh,
@@ -1446,8 +1602,8 @@ fn process_binary_operation(
             }
         }
         BinaryOp::LessOrEq() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
+            if let Term::Num(ref n1) = *t1 {
+                if let Term::Num(ref n2) = *t2 {
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Bool(n1 <= n2),
                         pos_op_inh,
@@ -1476,8 +1632,8 @@ fn process_binary_operation(
             }
         }
         BinaryOp::GreaterThan() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
+            if let Term::Num(ref n1) = *t1 {
+                if let Term::Num(ref n2) = *t2 {
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Bool(n1 > n2),
                         pos_op_inh,
@@ -1506,8 +1662,8 @@ fn process_binary_operation(
             }
         }
         BinaryOp::GreaterOrEq() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
+            if let Term::Num(ref n1) = *t1 {
+                if let Term::Num(ref n2) = *t2 {
                     Ok(Closure::atomic_closure(RichTerm::new(
                         Term::Bool(n1 >= n2),
                         pos_op_inh,
@@ -1569,12 +1725,48 @@ fn process_binary_operation(
                 ))
             }
         },
+        BinaryOp::GoVariant() => match_sharedterm! {t1, with {
+                Term::Enum(tag) => match_sharedterm! {t2, with {
+                        Term::Lbl(l) => {
+                            let mut l = l;
+                            l.path.push(ty_path::Elem::Variant(tag));
+                            Ok(Closure::atomic_closure(RichTerm::new(
+                                Term::Lbl(l),
+                                pos_op_inh,
+                            )))
+                        }
+                    } else {
+                        Err(EvalError::TypeError(
+                            String::from("Label"),
+                            String::from("go_variant, 2nd argument"),
+                            snd_pos,
+                            RichTerm {
+                                term: t2,
+                                pos: pos2,
+                            },
+                        ))
+                    }
+                },
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Enum"),
+                    String::from("go_variant, 1st argument"),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                ))
+            }
+        },
         BinaryOp::DynAccess() => match_sharedterm! {t1, with {
                 Term::Str(id) => {
                     if let Term::Record(static_map, _attrs) = &*t2 {
                         match static_map.get(&Ident::from(&id)) {
                             Some(e) => {
-                                call_stack.enter_field(Ident::from(id), pos2, e.pos, pos_op);
+                                let id = Ident::from(id);
+                                warn_deprecated(&id, e, &env2, pos_op, warnings);
+                                call_stack.enter_field(id, pos2, e.pos, pos_op);
                                 Ok(Closure {
                                     body: e.clone(),
                                     env: env2,
@@ -1623,6 +1815,10 @@ fn process_binary_operation(
                 match_sharedterm! {t2, with {
                         Term::Record(static_map, attrs) => {
                             let mut static_map = static_map;
+                            let mut attrs = attrs;
+                            if is_empty_optional(&clos) {
+                                attrs.empty_optional.insert(Ident::from(id));
+                            }
                             let as_var = clos.body.closurize(&mut env2, clos.env);
                             match static_map.insert(Ident::from(id), as_var) {
                                 Some(_) => Err(EvalError::Other(format!("$[ .. ]: tried to extend record with the field {}, but it already exists", id), pos_op)),
@@ -1660,7 +1856,9 @@ fn process_binary_operation(
                 Term::Str(id) => match_sharedterm! {t2, with {
                         Term::Record(static_map, attrs) => {
                             let mut static_map = static_map;
-                            match static_map.remove(&Ident::from(&id)) {
+                            let mut attrs = attrs;
+                            attrs.empty_optional.remove(&Ident::from(&id));
+                            match static_map.shift_remove(&Ident::from(&id)) {
                                 None => Err(EvalError::FieldMissing(
                                     id,
                                     String::from("(-$)"),
@@ -1702,9 +1900,13 @@ fn process_binary_operation(
         },
         BinaryOp::HasField() => match_sharedterm! {t1, with {
                 Term::Str(id) => {
-                    if let Term::Record(static_map, _) = &*t2 {
+                    if let Term::Record(static_map, attrs) = &*t2 {
+                        let id = Ident::from(id);
+                        let has_field =
+                            static_map.contains_key(&id) && !attrs.empty_optional.contains(&id);
+
                         Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Bool(static_map.contains_key(&Ident::from(id))),
+                            Term::Bool(has_field),
                             pos_op_inh,
                         )))
                     } else {
@@ -1777,16 +1979,16 @@ fn process_binary_operation(
         },
         BinaryOp::ArrayElemAt() => match (&*t1, &*t2) {
             (Term::Array(ts), Term::Num(n)) => {
-                let n_int = *n as usize;
-                if n.fract() != 0.0 {
-                    Err(EvalError::Other(format!("elemAt: expected the 2nd agument to be an integer, got the floating-point value {}", n), pos_op))
-                } else if *n < 0.0 || n_int >= ts.len() {
-                    Err(EvalError::Other(format!("elemAt: index out of bounds. Expected a value between 0 and {}, got {}", ts.len(), n), pos_op))
-                } else {
+                let n_int = usize::try_from(n).ok().filter(|n_int| *n_int < ts.len());
+                if !n.is_integer() {
+                    Err(EvalError::Other(format!("elemAt: expected the 2nd agument to be an integer, got the floating-point value {}", number::format(n)), pos_op))
+                } else if let Some(n_int) = n_int {
                     Ok(Closure {
                         body: ts[n_int].clone(),
                         env: env1,
                     })
+                } else {
+                    Err(EvalError::Other(format!("elemAt: index out of bounds. Expected a value between 0 and {}, got {}", ts.len(), n), pos_op))
                 }
             }
             (Term::Array(_), _) => Err(EvalError::TypeError(
@@ -1821,6 +2023,7 @@ fn process_binary_operation(
             env2,
             pos_op,
             MergeMode::Standard,
+            warnings,
         ),
 
         BinaryOp::Hash() => {
@@ -1849,11 +2052,7 @@ fn process_binary_operation(
                             hasher.update(s);
                             format!("{:x}", hasher.finalize())
                         }
-                        "Sha256" => {
-                            let mut hasher = sha2::Sha256::new();
-                            hasher.update(s);
-                            format!("{:x}", hasher.finalize())
-                        }
+                        "Sha256" => sha256(s),
                         "Sha512" => {
                             let mut hasher = sha2::Sha512::new();
                             hasher.update(s);
@@ -1913,6 +2112,9 @@ fn process_binary_operation(
                     _ => return mk_err_fst(t1),
                 };
 
+                // The result is compared and hashed, so it has to be canonical: fields are sorted
+                // instead of being kept in definition order as by `nickel export`.
+                let rt2 = serialize::sort_keys(rt2);
                 serialize::validate(format, &rt2)?;
                 Ok(Closure::atomic_closure(RichTerm::new(
                     Term::Str(serialize::to_string(format, &rt2)?),
@@ -1938,27 +2140,21 @@ fn process_binary_operation(
             if let Term::Enum(id) = &*t1 {
                 if let Term::Str(s) = &*t2 {
                     let rt: RichTerm = match id.as_ref() {
-                        "Json" => serde_json::from_str(s).map_err(|err| {
-                            EvalError::DeserializationError(
-                                String::from("json"),
-                                format!("{}", err),
-                                pos_op,
-                            )
-                        })?,
-                        "Yaml" => serde_yaml::from_str(s).map_err(|err| {
-                            EvalError::DeserializationError(
-                                String::from("yaml"),
-                                format!("{}", err),
-                                pos_op,
-                            )
-                        })?,
-                        "Toml" => toml::from_str(s).map_err(|err| {
-                            EvalError::DeserializationError(
-                                String::from("toml"),
-                                format!("{}", err),
-                                pos_op,
-                            )
-                        })?,
+                        "Json" => {
+                            deserialize::from_string(deserialize::from_json, s).map_err(|msg| {
+                                EvalError::DeserializationError(String::from("json"), msg, pos_op)
+                            })?
+                        }
+                        "Yaml" => {
+                            deserialize::from_string(deserialize::from_yaml, s).map_err(|msg| {
+                                EvalError::DeserializationError(String::from("yaml"), msg, pos_op)
+                            })?
+                        }
+                        "Toml" => {
+                            deserialize::from_string(deserialize::from_toml, s).map_err(|msg| {
+                                EvalError::DeserializationError(String::from("toml"), msg, pos_op)
+                            })?
+                        }
                         _ => return mk_err_fst(t1),
                     };
 
@@ -2081,14 +2277,14 @@ fn process_binary_operation(
 
                         mk_record!(
                             ("match", Term::Str(String::from(first_match.as_str()))),
-                            ("index", Term::Num(first_match.start() as f64)),
+                            ("index", Term::Num(Number::from(first_match.start()))),
                             ("groups", Term::Array(groups))
                         )
                     } else {
                         //FIXME: what should we return when there's no match?
                         mk_record!(
                             ("match", Term::Str(String::new())),
-                            ("index", Term::Num(-1.)),
+                            ("index", Term::Num(Number::from(-1))),
                             ("groups", Term::Array(Vec::new()))
                         )
                     };
@@ -2127,6 +2323,7 @@ fn process_nary_operation(
     args: Vec<(Closure, TermPos)>,
     _stack: &mut Stack,
     pos_op: TermPos,
+    warnings: &mut Warnings,
 ) -> Result<Closure, EvalError> {
     let pos_op_inh = pos_op.into_inherited();
 
@@ -2198,22 +2395,30 @@ fn process_nary_operation(
 
             match (&*fst, &*snd, &*thd) {
                 (Term::Str(s), Term::Num(start), Term::Num(end)) => {
-                    let start_int = *start as usize;
-                    let end_int = *end as usize;
-
-                    if start.fract() != 0.0 {
-                        Err(EvalError::Other(format!("substring: expected the 2nd agument (start) to be an integer, got the floating-point value {}", start), pos_op))
-                    } else if !s.is_char_boundary(start_int) {
-                        Err(EvalError::Other(format!("substring: index out of bounds. Expected the 2nd argument (start) to be between 0 and {}, got {}", s.len(), start), pos_op))
-                    } else if end.fract() != 0.0 {
-                        Err(EvalError::Other(format!("substring: expected the 3nd argument (end) to be an integer, got the floating-point value {}", end), pos_op))
-                    } else if end <= start || !s.is_char_boundary(end_int) {
-                        Err(EvalError::Other(format!("substring: index out of bounds. Expected the 3rd argument (end) to be between {} and {}, got {}", start+1., s.len(), end), pos_op))
-                    } else {
-                        Ok(Closure::atomic_closure(RichTerm::new(
-                            Term::Str(s[start_int..end_int].to_owned()),
-                            pos_op_inh,
-                        )))
+                    let start_int = usize::try_from(start)
+                        .ok()
+                        .filter(|i| s.is_char_boundary(*i));
+                    let end_int = usize::try_from(end).ok().filter(|i| s.is_char_boundary(*i));
+
+                    match (start_int, end_int) {
+                        _ if !start.is_integer() => {
+                            Err(EvalError::Other(format!("substring: expected the 2nd agument (start) to be an integer, got the floating-point value {}", number::format(start)), pos_op))
+                        }
+                        (None, _) => {
+                            Err(EvalError::Other(format!("substring: index out of bounds. Expected the 2nd argument (start) to be between 0 and {}, got {}", s.len(), start), pos_op))
+                        }
+                        _ if !end.is_integer() => {
+                            Err(EvalError::Other(format!("substring: expected the 3nd argument (end) to be an integer, got the floating-point value {}", number::format(end)), pos_op))
+                        }
+                        (Some(start_int), Some(end_int)) if start_int < end_int => {
+                            Ok(Closure::atomic_closure(RichTerm::new(
+                                Term::Str(s[start_int..end_int].to_owned()),
+                                pos_op_inh,
+                            )))
+                        }
+                        _ => {
+                            Err(EvalError::Other(format!("substring: index out of bounds. Expected the 3rd argument (end) to be between {} and {}, got {}", start + Number::from(1), s.len(), end), pos_op))
+                        }
                     }
                 }
                 (Term::Str(_), Term::Num(_), _) => Err(EvalError::TypeError(
@@ -2293,6 +2498,7 @@ fn process_nary_operation(
                             env3,
                             pos_op,
                             MergeMode::Contract(lbl),
+                            warnings,
                         )
                     }
                 } else {
@@ -2366,7 +2572,19 @@ fn eq(env: &mut Environment, c1: Closure, c2: Closure) -> EqResult {
         (Term::Lbl(l1), Term::Lbl(l2)) => EqResult::Bool(l1 == l2),
         (Term::Sym(s1), Term::Sym(s2)) => EqResult::Bool(s1 == s2),
         (Term::Enum(id1), Term::Enum(id2)) => EqResult::Bool(id1 == id2),
-        (Term::Record(m1, _), Term::Record(m2, _)) => {
+        (Term::EnumVariant(id1, t1), Term::EnumVariant(id2, t2)) if id1 == id2 => {
+            gen_eqs(std::iter::once((t1, t2)), env, env1, env2)
+        }
+        (Term::Record(m1, attrs1), Term::Record(m2, attrs2)) => {
+            // Optional fields without a value are absent.
+            let m1 = m1
+                .into_iter()
+                .filter(|(id, _)| !attrs1.empty_optional.contains(id))
+                .collect();
+            let m2 = m2
+                .into_iter()
+                .filter(|(id, _)| !attrs2.empty_optional.contains(id))
+                .collect();
             let (left, center, right) = merge::hashmap::split(m1, m2);
 
             if !left.is_empty() || !right.is_empty() {
@@ -2398,11 +2616,11 @@ mod tests {
         let cont = OperationCont::Op1(UnaryOp::Ite(), TermPos::None);
         let mut stack = Stack::new();
         stack.push_arg(
-            Closure::atomic_closure(Term::Num(5.0).into()),
+            Closure::atomic_closure(Term::Num(Number::from(5)).into()),
             TermPos::None,
         );
         stack.push_arg(
-            Closure::atomic_closure(Term::Num(46.0).into()),
+            Closure::atomic_closure(Term::Num(Number::from(46)).into()),
             TermPos::None,
         );
 
@@ -2414,12 +2632,13 @@ mod tests {
         stack.push_op_cont(cont, 0, TermPos::None);
         let mut call_stack = CallStack::new();
 
-        clos = continuate_operation(clos, &mut stack, &mut call_stack).unwrap();
+        clos =
+            continuate_operation(clos, &mut stack, &mut call_stack, &mut Warnings::new()).unwrap();
 
         assert_eq!(
             clos,
             Closure {
-                body: Term::Num(46.0).into(),
+                body: Term::Num(Number::from(46)).into(),
                 env: Environment::new()
             }
         );
@@ -2431,26 +2650,27 @@ mod tests {
         let cont = OperationCont::Op2First(
             BinaryOp::Plus(),
             Closure {
-                body: Term::Num(6.0).into(),
+                body: Term::Num(Number::from(6)).into(),
                 env: Environment::new(),
             },
             TermPos::None,
         );
 
         let mut clos = Closure {
-            body: Term::Num(7.0).into(),
+            body: Term::Num(Number::from(7)).into(),
             env: Environment::new(),
         };
         let mut stack = Stack::new();
         stack.push_op_cont(cont, 0, TermPos::None);
         let mut call_stack = CallStack::new();
 
-        clos = continuate_operation(clos, &mut stack, &mut call_stack).unwrap();
+        clos =
+            continuate_operation(clos, &mut stack, &mut call_stack, &mut Warnings::new()).unwrap();
 
         assert_eq!(
             clos,
             Closure {
-                body: Term::Num(6.0).into(),
+                body: Term::Num(Number::from(6)).into(),
                 env: Environment::new()
             }
         );
@@ -2461,7 +2681,7 @@ mod tests {
                 OperationCont::Op2Second(
                     BinaryOp::Plus(),
                     Closure {
-                        body: Term::Num(7.0).into(),
+                        body: Term::Num(Number::from(7)).into(),
                         env: Environment::new(),
                     },
                     TermPos::None,
@@ -2479,26 +2699,27 @@ mod tests {
         let cont = OperationCont::Op2Second(
             BinaryOp::Plus(),
             Closure {
-                body: Term::Num(7.0).into(),
+                body: Term::Num(Number::from(7)).into(),
                 env: Environment::new(),
             },
             TermPos::None,
             TermPos::None,
         );
         let mut clos = Closure {
-            body: Term::Num(6.0).into(),
+            body: Term::Num(Number::from(6)).into(),
             env: Environment::new(),
         };
         let mut stack = Stack::new();
         stack.push_op_cont(cont, 0, TermPos::None);
         let mut call_stack = CallStack::new();
 
-        clos = continuate_operation(clos, &mut stack, &mut call_stack).unwrap();
+        clos =
+            continuate_operation(clos, &mut stack, &mut call_stack, &mut Warnings::new()).unwrap();
 
         assert_eq!(
             clos,
             Closure {
-                body: Term::Num(13.0).into(),
+                body: Term::Num(Number::from(13)).into(),
                 env: Environment::new()
             }
         );
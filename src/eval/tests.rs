@@ -1,7 +1,8 @@
 use super::*;
 use crate::cache::resolvers::{DummyResolver, SimpleResolver};
-use crate::error::ImportError;
+use crate::error::{ImportError, Warnings};
 use crate::label::Label;
+use crate::number::{self, Number};
 use crate::parser::{grammar, lexer};
 use crate::term::make as mk_term;
 use crate::term::{BinaryOp, StrChunk, UnaryOp};
@@ -11,7 +12,13 @@ use codespan::Files;
 
 /// Evaluate a term without import support.
 fn eval_no_import(t: RichTerm) -> Result<Term, EvalError> {
-    eval(t, &Environment::new(), &mut DummyResolver {}).map(Term::from)
+    eval(
+        t,
+        &Environment::new(),
+        &mut DummyResolver {},
+        &mut Warnings::new(),
+    )
+    .map(Term::from)
 }
 
 fn parse(s: &str) -> Option<RichTerm> {
@@ -26,7 +33,7 @@ fn parse(s: &str) -> Option<RichTerm> {
 
 #[test]
 fn identity_over_values() {
-    let num = Term::Num(45.3);
+    let num = Term::Num(number::parse("45.3").unwrap());
     assert_eq!(Ok(num.clone()), eval_no_import(num.into()));
 
     let boolean = Term::Bool(true);
@@ -56,36 +63,47 @@ fn lone_var_panics() {
 
 #[test]
 fn only_fun_are_applicable() {
-    eval_no_import(mk_app!(Term::Bool(true), Term::Num(45.))).unwrap_err();
+    eval_no_import(mk_app!(Term::Bool(true), Term::Num(Number::from(45)))).unwrap_err();
 }
 
 #[test]
 fn simple_app() {
-    let t = mk_app!(mk_term::id(), Term::Num(5.0));
-    assert_eq!(Ok(Term::Num(5.0)), eval_no_import(t));
+    let t = mk_app!(mk_term::id(), Term::Num(Number::from(5)));
+    assert_eq!(Ok(Term::Num(Number::from(5))), eval_no_import(t));
 }
 
 #[test]
 fn simple_let() {
-    let t = mk_term::let_in("x", Term::Num(5.0), mk_term::var("x"));
-    assert_eq!(Ok(Term::Num(5.0)), eval_no_import(t));
+    let t = mk_term::let_in("x", Term::Num(Number::from(5)), mk_term::var("x"));
+    assert_eq!(Ok(Term::Num(Number::from(5))), eval_no_import(t));
 }
 
 #[test]
 fn simple_ite() {
-    let t = mk_term::if_then_else(Term::Bool(true), Term::Num(5.0), Term::Bool(false));
-    assert_eq!(Ok(Term::Num(5.0)), eval_no_import(t));
+    let t = mk_term::if_then_else(
+        Term::Bool(true),
+        Term::Num(Number::from(5)),
+        Term::Bool(false),
+    );
+    assert_eq!(Ok(Term::Num(Number::from(5))), eval_no_import(t));
 }
 
 #[test]
 fn simple_plus() {
-    let t = mk_term::op2(BinaryOp::Plus(), Term::Num(5.0), Term::Num(7.5));
-    assert_eq!(Ok(Term::Num(12.5)), eval_no_import(t));
+    let t = mk_term::op2(
+        BinaryOp::Plus(),
+        Term::Num(Number::from(5)),
+        Term::Num(number::parse("7.5").unwrap()),
+    );
+    assert_eq!(
+        Ok(Term::Num(number::parse("12.5").unwrap())),
+        eval_no_import(t)
+    );
 }
 
 #[test]
 fn asking_for_various_types() {
-    let num = mk_term::op1(UnaryOp::IsNum(), Term::Num(45.3));
+    let num = mk_term::op1(UnaryOp::IsNum(), Term::Num(number::parse("45.3").unwrap()));
     assert_eq!(Ok(Term::Bool(true)), eval_no_import(num));
 
     let boolean = mk_term::op1(UnaryOp::IsBool(), Term::Bool(true));
@@ -126,18 +144,18 @@ fn enriched_terms_unwrapping() {
 fn merge_enriched_default() {
     let t = mk_term::op2(
         BinaryOp::Merge(),
-        Term::Num(1.0),
-        mk_default(Term::Num(2.0).into()),
+        Term::Num(Number::from(1)),
+        mk_default(Term::Num(Number::from(2)).into()),
     );
-    assert_eq!(Ok(Term::Num(1.0)), eval_no_import(t));
+    assert_eq!(Ok(Term::Num(Number::from(1))), eval_no_import(t));
 }
 
 #[test]
 fn merge_incompatible_defaults() {
     let t = mk_term::op2(
         BinaryOp::Merge(),
-        mk_default(Term::Num(1.0).into()),
-        mk_default(Term::Num(2.0).into()),
+        mk_default(Term::Num(Number::from(1)).into()),
+        mk_default(Term::Num(Number::from(2)).into()),
     );
 
     eval_no_import(t).unwrap_err();
@@ -195,11 +213,12 @@ fn imports() {
         eval(
             mk_import("x", "two", mk_term::var("x"), &mut resolver).unwrap(),
             &Environment::new(),
-            &mut resolver
+            &mut resolver,
+            &mut Warnings::new()
         )
         .map(Term::from)
         .unwrap(),
-        Term::Num(2.0)
+        Term::Num(Number::from(2))
     );
 
     // let x = import "lib" in x.f
@@ -213,7 +232,8 @@ fn imports() {
             )
             .unwrap(),
             &Environment::new(),
-            &mut resolver
+            &mut resolver,
+            &mut Warnings::new()
         )
         .map(Term::from)
         .unwrap(),
@@ -291,28 +311,28 @@ fn global_env() {
     global_env.insert(
         Ident::from("g"),
         Thunk::new(
-            Closure::atomic_closure(Term::Num(1.0).into()),
+            Closure::atomic_closure(Term::Num(Number::from(1)).into()),
             IdentKind::Let,
         ),
     );
 
-    let t = mk_term::let_in("x", Term::Num(2.0), mk_term::var("x"));
+    let t = mk_term::let_in("x", Term::Num(Number::from(2)), mk_term::var("x"));
     assert_eq!(
-        eval(t, &global_env, &mut resolver).map(Term::from),
-        Ok(Term::Num(2.0))
+        eval(t, &global_env, &mut resolver, &mut Warnings::new()).map(Term::from),
+        Ok(Term::Num(Number::from(2)))
     );
 
-    let t = mk_term::let_in("x", Term::Num(2.0), mk_term::var("g"));
+    let t = mk_term::let_in("x", Term::Num(Number::from(2)), mk_term::var("g"));
     assert_eq!(
-        eval(t, &global_env, &mut resolver).map(Term::from),
-        Ok(Term::Num(1.0))
+        eval(t, &global_env, &mut resolver, &mut Warnings::new()).map(Term::from),
+        Ok(Term::Num(Number::from(1)))
     );
 
     // Shadowing of global environment
-    let t = mk_term::let_in("g", Term::Num(2.0), mk_term::var("g"));
+    let t = mk_term::let_in("g", Term::Num(Number::from(2)), mk_term::var("g"));
     assert_eq!(
-        eval(t, &global_env, &mut resolver).map(Term::from),
-        Ok(Term::Num(2.0))
+        eval(t, &global_env, &mut resolver, &mut Warnings::new()).map(Term::from),
+        Ok(Term::Num(Number::from(2)))
     );
 }
 
@@ -331,7 +351,7 @@ fn mk_env(bindings: Vec<(&str, RichTerm)>) -> Environment {
 #[test]
 fn substitution() {
     let global_env = mk_env(vec![
-        ("glob1", Term::Num(1.0).into()),
+        ("glob1", Term::Num(Number::from(1)).into()),
         ("glob2", parse("\"Glob2\"").unwrap()),
         ("glob3", Term::Bool(false).into()),
     ]);
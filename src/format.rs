@@ -0,0 +1,624 @@
+//! Source code formatting.
+//!
+//! The formatter works at the level of tokens rather than on the AST: the parser desugars a fair
+//! amount of constructs (piecewise record definitions, destructuring, string chunks, etc.) and
+//! throws comments away, so printing back a [`RichTerm`](crate::term::RichTerm) can't reproduce
+//! the original program faithfully. Instead, the source is first parsed to make sure that it is
+//! valid, and then re-lexed. The original text of each token is kept as is, while the whitespace
+//! and the comments in between are rewritten according to the following rules:
+//!
+//! - **Brackets**: a record, an array or an enum row type (`{..}`, `[..]`, `[|..|]`) which spans
+//!   several lines in the source is laid out with one element per line, each element indented by
+//!   one level with respect to the line of the opening bracket, and the closing bracket on its
+//!   own line. Brackets which fit on one line in the source stay on one line.
+//! - **Indentation**: lines which are not determined by the bracket layout (`if`/`then`/`else`
+//!   chains, pipelines, annotations, etc.) keep their indentation relative to the first line of
+//!   the enclosing bracket. Metadata annotations starting a line (`| doc ..`, `| default`,
+//!   contracts) are indented by at least one level.
+//! - **Spacing**: tokens on the same line are separated by exactly one space, excepted around
+//!   delimiters (`(foo)`, `[1, 2]`, `a.b`, `forall a. a`, `` `Tag ``). The spacing around the
+//!   tokens which can be either unary or binary (`-`, `!`, `..`) is preserved.
+//! - **Line breaks**: the line breaks of the source are kept. Consecutive blank lines are
+//!   collapsed, and blank lines right after an opening bracket or right before a closing one are
+//!   removed. Trailing whitespace is removed and the output ends with exactly one newline.
+//! - **Comments** are kept at their place, either at the end of a line or on their own line.
+//!
+//! Strings are reproduced verbatim, including interpolated expressions: changing the layout of a
+//! multiline string may change its content.
+//!
+//! Formatting is idempotent: formatting an already formatted source is a no-op.
+use crate::error::{ParseError, ParseErrors};
+use crate::parser::{
+    grammar,
+    lexer::{Lexer, NormalToken, Token},
+};
+use codespan::FileId;
+
+/// The number of spaces of one indentation level.
+pub const INDENT_WIDTH: usize = 2;
+
+/// The different kinds of brackets.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Bracket {
+    /// `{` and `}`.
+    Brace,
+    /// `[` and `]`.
+    Square,
+    /// `(` and `)`.
+    Paren,
+    /// `[|` and `|]`.
+    EnumRow,
+}
+
+/// The kind of a token, as far as layout is concerned.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Kind {
+    Open(Bracket),
+    Close(Bracket),
+    Comma,
+    Semicolon,
+    /// The dot of a field access `a.b`.
+    Dot,
+    /// The dot ending the variables of a `forall`.
+    ForallDot,
+    Backtick,
+    /// A metadata annotation `|`.
+    Pipe,
+    /// Infix operators, always surrounded by spaces.
+    Infix,
+    /// Tokens which may or may not be followed or preceded by a space depending on their use, such
+    /// as `-`. The spacing of the source is kept.
+    Preserve,
+    /// A whole string, including its delimiters and potential interpolated expressions.
+    Str,
+    /// Any other token (identifiers, keywords, literals, builtin operators, ...).
+    Atom,
+}
+
+impl Kind {
+    fn of(token: &Token) -> Kind {
+        use NormalToken::*;
+
+        match token {
+            Token::Normal(token) => match token {
+                LBrace => Kind::Open(Bracket::Brace),
+                RBrace => Kind::Close(Bracket::Brace),
+                LBracket => Kind::Open(Bracket::Square),
+                RBracket => Kind::Close(Bracket::Square),
+                LParen => Kind::Open(Bracket::Paren),
+                RParen => Kind::Close(Bracket::Paren),
+                EnumOpen => Kind::Open(Bracket::EnumRow),
+                EnumClose => Kind::Close(Bracket::EnumRow),
+                Comma => Kind::Comma,
+                Semicolon => Kind::Semicolon,
+                Dot => Kind::Dot,
+                Backtick => Kind::Backtick,
+                Pipe => Kind::Pipe,
+                QuestionMark | Colon | Equals | NotEquals | Ampersand | Plus | Times | Div
+                | Percent | DoublePlus | DoubleEq | At | DoubleAnd | DoublePipe | RightPipe
+                | SimpleArrow | DoubleArrow | LAngleBracket | LessOrEq | RAngleBracket
+                | GreaterOrEq => Kind::Infix,
+                Minus | Bang | Ellipsis | Dollar => Kind::Preserve,
+                _ => Kind::Atom,
+            },
+            _ => Kind::Str,
+        }
+    }
+}
+
+/// A token of the source, given by its kind and its span.
+#[derive(Clone, Copy, Debug)]
+struct Tok {
+    kind: Kind,
+    start: usize,
+    end: usize,
+}
+
+/// The elements of the source to lay out: tokens, and the comments and line breaks in between.
+#[derive(Clone, Copy, Debug)]
+enum Elem<'a> {
+    Tok(Tok),
+    /// A comment, with the offset of its start in the source.
+    Comment(&'a str, usize),
+    /// A sequence of line breaks in the source.
+    Newlines(usize),
+}
+
+/// An open bracket, together with the indentation information of its content.
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    /// If the content of the brackets must be laid out one element per line.
+    multiline: bool,
+    /// The indentation of the line of the opening bracket.
+    base: usize,
+    /// The indentation of the content.
+    content: usize,
+    /// The first line of the content, given as its indentation in the source and in the output.
+    /// The indentation of the following lines is computed relatively to this anchor.
+    anchor: Option<(usize, usize)>,
+}
+
+impl Frame {
+    fn new(multiline: bool, base: usize) -> Self {
+        Frame {
+            multiline,
+            base,
+            content: base + INDENT_WIDTH,
+            anchor: None,
+        }
+    }
+
+    /// The top-level frame, which is not delimited by actual brackets.
+    fn root() -> Self {
+        Frame {
+            multiline: false,
+            base: 0,
+            content: 0,
+            anchor: None,
+        }
+    }
+}
+
+/// Parse a Nickel source and return it formatted. Fail if the source is not a valid Nickel
+/// program.
+pub fn format(file_id: FileId, source: &str) -> Result<String, ParseErrors> {
+    grammar::TermParser::new().parse_term(file_id, Lexer::new(source))?;
+
+    let tokens = tokenize(file_id, source)?;
+    let elems = elems(source, &tokens);
+    let multiline = multiline_brackets(source, &tokens);
+
+    Ok(Printer::new(source).print(&elems, &multiline))
+}
+
+/// Lex a source. Strings are collapsed to one token.
+fn tokenize(file_id: FileId, source: &str) -> Result<Vec<Tok>, ParseError> {
+    let mut lexer = Lexer::new(source);
+    let mut tokens = Vec::new();
+    let mut str_start = None;
+    let mut in_forall = false;
+
+    while let Some(result) = lexer.next() {
+        let (start, token, end) = result.map_err(|error| {
+            ParseError::from_lalrpop::<Token>(lalrpop_util::ParseError::User { error }, file_id)
+        })?;
+
+        // The lexer is back at the top level, outside of any string: this is the end of the
+        // current string, if any.
+        let outside_str = lexer.stack.is_empty();
+
+        match str_start {
+            Some(str_start_pos) if outside_str => {
+                tokens.push(Tok {
+                    kind: Kind::Str,
+                    start: str_start_pos,
+                    end,
+                });
+                str_start = None;
+            }
+            Some(_) => (),
+            None if outside_str => {
+                let kind = match (Kind::of(&token), &token) {
+                    (Kind::Dot, _) if in_forall => {
+                        in_forall = false;
+                        Kind::ForallDot
+                    }
+                    (kind, Token::Normal(NormalToken::Forall)) => {
+                        in_forall = true;
+                        kind
+                    }
+                    (kind, _) => kind,
+                };
+
+                tokens.push(Tok { kind, start, end });
+            }
+            None => str_start = Some(start),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Extract the comments and the line breaks that lie between the tokens.
+fn elems<'a>(source: &'a str, tokens: &[Tok]) -> Vec<Elem<'a>> {
+    let mut elems = Vec::new();
+    let mut prev_end = 0;
+
+    for tok in tokens.iter() {
+        gap_elems(source, prev_end, tok.start, &mut elems);
+        elems.push(Elem::Tok(*tok));
+        prev_end = tok.end;
+    }
+
+    gap_elems(source, prev_end, source.len(), &mut elems);
+    elems
+}
+
+/// Extract the comments and the line breaks of a gap between two tokens, which is only made of
+/// whitespace and comments.
+fn gap_elems<'a>(source: &'a str, start: usize, end: usize, elems: &mut Vec<Elem<'a>>) {
+    let gap = &source[start..end];
+    let mut newlines = 0;
+    let mut offset = 0;
+
+    while offset < gap.len() {
+        let rest = &gap[offset..];
+
+        if rest.starts_with('#') {
+            if newlines > 0 {
+                elems.push(Elem::Newlines(newlines));
+                newlines = 0;
+            }
+
+            let len = rest.find('\n').unwrap_or(rest.len());
+            elems.push(Elem::Comment(rest[..len].trim_end(), start + offset));
+            offset += len;
+        } else {
+            if rest.starts_with('\n') {
+                newlines += 1;
+            }
+
+            offset += rest.chars().next().map(char::len_utf8).unwrap_or(1);
+        }
+    }
+
+    if newlines > 0 {
+        elems.push(Elem::Newlines(newlines));
+    }
+}
+
+/// Determine which brackets are to be laid out on several lines, that is the opening brackets
+/// (excepted parentheses) whose matching closing bracket is not on the same line. Return a vector
+/// indexed by token.
+fn multiline_brackets(source: &str, tokens: &[Tok]) -> Vec<bool> {
+    let mut result = vec![false; tokens.len()];
+    let mut open: Vec<usize> = Vec::new();
+
+    for (index, tok) in tokens.iter().enumerate() {
+        match tok.kind {
+            Kind::Open(_) => open.push(index),
+            Kind::Close(bracket) => {
+                if let Some(open_index) = open.pop() {
+                    let spans_lines = source[tokens[open_index].end..tok.start].contains('\n');
+                    result[open_index] = bracket != Bracket::Paren && spans_lines;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    result
+}
+
+/// Determine if a space must separate two tokens on the same line.
+///
+/// `had_space` indicates if the two tokens were separated by whitespace in the source.
+fn needs_space(prev: Kind, next: Kind, had_space: bool) -> bool {
+    use Bracket::*;
+
+    match (prev, next) {
+        (_, Kind::Comma) | (_, Kind::Semicolon) => false,
+        (Kind::Comma, _) | (Kind::Semicolon, _) => true,
+        (Kind::Open(Brace), Kind::Close(Brace)) | (Kind::Open(EnumRow), Kind::Close(EnumRow)) => {
+            false
+        }
+        (Kind::Open(Paren), _) | (Kind::Open(Square), _) => false,
+        (_, Kind::Close(Paren)) | (_, Kind::Close(Square)) => false,
+        (Kind::Open(_), _) | (_, Kind::Close(_)) => true,
+        (_, Kind::Dot) | (Kind::Dot, _) | (_, Kind::ForallDot) | (Kind::Backtick, _) => false,
+        (Kind::Preserve, _) | (_, Kind::Preserve) => had_space,
+        _ => true,
+    }
+}
+
+/// The state of the formatter.
+struct Printer<'a> {
+    source: &'a str,
+    out: String,
+    /// The stack of currently open brackets. The bottom element is the root frame.
+    frames: Vec<Frame>,
+    /// The indentation of the current line of the output.
+    line_indent: usize,
+    /// The number of line breaks encountered in the source since the last printed element.
+    pending_newlines: usize,
+    /// Set when the next element must start a new line, whatever the source layout is.
+    force_newline: bool,
+    /// The last printed token.
+    prev: Option<Tok>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(source: &'a str) -> Self {
+        Printer {
+            source,
+            out: String::with_capacity(source.len()),
+            frames: vec![Frame::root()],
+            line_indent: 0,
+            pending_newlines: 0,
+            force_newline: false,
+            prev: None,
+        }
+    }
+
+    fn print(mut self, elems: &[Elem], multiline: &[bool]) -> String {
+        let mut tok_index = 0;
+
+        for elem in elems {
+            match elem {
+                Elem::Newlines(n) => self.pending_newlines += n,
+                Elem::Comment(text, offset) => self.comment(text, *offset),
+                Elem::Tok(tok) => {
+                    self.token(*tok, multiline[tok_index]);
+                    tok_index += 1;
+                }
+            }
+        }
+
+        let len = self.out.trim_end().len();
+        self.out.truncate(len);
+        self.out.push('\n');
+        self.out
+    }
+
+    fn top(&mut self) -> &mut Frame {
+        self.frames
+            .last_mut()
+            .expect("format: the root frame can't be popped")
+    }
+
+    /// The column of an offset in the source.
+    fn column(&self, offset: usize) -> usize {
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        self.source[line_start..offset].chars().count()
+    }
+
+    /// The number of line breaks to print before the next element, which is a closing bracket if
+    /// `closing` is true.
+    fn newlines_before(&self, closing: bool) -> usize {
+        if self.out.is_empty() {
+            return 0;
+        }
+
+        let after_open = matches!(
+            self.prev,
+            Some(Tok {
+                kind: Kind::Open(_),
+                ..
+            })
+        );
+        let max = if after_open || closing { 1 } else { 2 };
+        let min = if self.force_newline { 1 } else { 0 };
+
+        self.pending_newlines.max(min).min(max)
+    }
+
+    /// Compute the indentation of a new line starting at `offset` in the source, using the
+    /// current frame. `from_source` indicates if the element also starts a line in the source.
+    fn indent(&mut self, offset: usize, from_source: bool, is_pipe: bool) -> usize {
+        let column = self.column(offset);
+        let frame = self.top();
+
+        let indent = match frame.anchor {
+            Some((src, out)) if from_source => (out + column).saturating_sub(src),
+            Some(_) => frame.content,
+            None => {
+                let indent = if is_pipe {
+                    frame.content + INDENT_WIDTH
+                } else {
+                    frame.content
+                };
+
+                if from_source {
+                    frame.anchor = Some((column, indent));
+                }
+
+                indent
+            }
+        };
+
+        let indent = indent.max(frame.content);
+
+        if is_pipe {
+            indent.max(frame.content + INDENT_WIDTH)
+        } else {
+            indent
+        }
+    }
+
+    fn newline(&mut self, count: usize, indent: usize) {
+        for _ in 0..count {
+            self.out.push('\n');
+        }
+
+        self.out.push_str(&" ".repeat(indent));
+        self.line_indent = indent;
+    }
+
+    fn comment(&mut self, text: &str, offset: usize) {
+        let from_source = self.pending_newlines > 0;
+
+        if !from_source && self.prev.is_some() {
+            // Trailing comment, kept at the end of the current line.
+            self.out.push(' ');
+        } else {
+            let newlines = self.newlines_before(false);
+            let indent = self.indent(offset, from_source, false);
+            self.newline(newlines, indent);
+        }
+
+        self.out.push_str(text);
+        self.pending_newlines = 0;
+        self.force_newline = true;
+    }
+
+    fn token(&mut self, tok: Tok, multiline: bool) {
+        let from_source = self.pending_newlines > 0;
+        let closing = matches!(tok.kind, Kind::Close(_));
+
+        if closing && self.top().multiline {
+            self.force_newline = true;
+        }
+
+        let newlines = self.newlines_before(closing);
+
+        if newlines > 0 || self.out.is_empty() {
+            let indent = if closing {
+                self.top().base
+            } else {
+                self.indent(tok.start, from_source, tok.kind == Kind::Pipe)
+            };
+
+            self.newline(newlines, indent);
+        } else if let Some(prev) = self.prev {
+            let had_space = prev.end < tok.start;
+
+            if needs_space(prev.kind, tok.kind, had_space) {
+                self.out.push(' ');
+            }
+        }
+
+        self.out.push_str(&self.source[tok.start..tok.end]);
+        self.pending_newlines = 0;
+        self.force_newline = false;
+
+        match tok.kind {
+            Kind::Open(_) => {
+                let frame = Frame::new(multiline, self.line_indent);
+                self.frames.push(frame);
+                self.force_newline = multiline;
+            }
+            // The root frame is never popped, as the source is known to be well-parenthesized.
+            Kind::Close(_) if self.frames.len() > 1 => {
+                self.frames.pop();
+            }
+            Kind::Comma => self.force_newline = self.top().multiline,
+            _ => (),
+        }
+
+        self.prev = Some(tok);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codespan::Files;
+
+    fn format_str(s: &str) -> String {
+        let id = Files::new().add("<test>", String::from(s));
+        format(id, s).unwrap()
+    }
+
+    /// Return the text of the tokens and of the comments of a source.
+    fn lexemes(s: &str) -> Vec<String> {
+        let id = Files::new().add("<test>", String::from(s));
+        let tokens = tokenize(id, s).unwrap();
+
+        elems(s, &tokens)
+            .into_iter()
+            .filter_map(|elem| match elem {
+                Elem::Tok(tok) => Some(String::from(&s[tok.start..tok.end])),
+                Elem::Comment(text, _) => Some(String::from(text)),
+                Elem::Newlines(_) => None,
+            })
+            .collect()
+    }
+
+    /// Check that a source is formatted as expected, and that formatting is idempotent.
+    fn assert_format(source: &str, expected: &str) {
+        let formatted = format_str(source);
+        assert_eq!(formatted, expected);
+        assert_eq!(format_str(&formatted), formatted);
+    }
+
+    #[test]
+    fn spacing() {
+        assert_format("1+2   *3", "1 + 2 * 3\n");
+        assert_format("let x=1 in x", "let x = 1 in x\n");
+        assert_format("{a=1,b= [ 1,2 ]}", "{ a = 1, b = [1, 2] }\n");
+        assert_format("{}  ", "{}\n");
+        assert_format("( fun x=>x ) 1", "(fun x => x) 1\n");
+        assert_format("{ a.b = 1 } . a .b", "{ a.b = 1 }.a.b\n");
+        assert_format("` Foo", "`Foo\n");
+        assert_format(
+            "let f : forall a. a -> a = fun x => x in f",
+            "let f : forall a. a -> a = fun x => x in f\n",
+        );
+        assert_format("-1 - 2", "-1 - 2\n");
+    }
+
+    #[test]
+    fn records_layout() {
+        assert_format(
+            "{ a = 1, b = 2,\n    c = { d = 3 }, e = {\nf = 4 } }",
+            "{\n  a = 1,\n  b = 2,\n  c = { d = 3 },\n  e = {\n    f = 4\n  }\n}\n",
+        );
+        assert_format(
+            "{\n\n\n  a = 1,\n\n\n\n  b = 2,\n\n}",
+            "{\n  a = 1,\n\n  b = 2,\n}\n",
+        );
+        assert_format("[\n1, 2, 3]", "[\n  1,\n  2,\n  3\n]\n");
+    }
+
+    #[test]
+    fn metadata() {
+        assert_format(
+            "{\n  foo\n  | Num\n  | doc \"foo\"\n  | default = 1,\n}",
+            "{\n  foo\n    | Num\n    | doc \"foo\"\n    | default = 1,\n}\n",
+        );
+        assert_format(
+            "{\n foo | Num | default =  1,\n bar : Str = \"a\"\n}",
+            "{\n  foo | Num | default = 1,\n  bar : Str = \"a\"\n}\n",
+        );
+    }
+
+    #[test]
+    fn relative_indentation() {
+        assert_format(
+            "{\n    f = fun x =>\n        if x then\n            1\n        else\n            2,\n}",
+            "{\n  f = fun x =>\n      if x then\n          1\n      else\n          2,\n}\n",
+        );
+    }
+
+    #[test]
+    fn comments() {
+        assert_format(
+            "# head\n{\n   # a field\n  a = 1, # trailing\n\n\n   # last\n   b = 2 } # end",
+            "# head\n{\n  # a field\n  a = 1, # trailing\n\n  # last\n  b = 2\n} # end\n",
+        );
+        assert_format(
+            "let x = 1 in # comment   \nx",
+            "let x = 1 in # comment\nx\n",
+        );
+    }
+
+    #[test]
+    fn strings() {
+        assert_format(
+            "{ a = \"%{ {b=1}.b  }  \", c=m%\"\n     foo\n  \"%m}",
+            "{\n  a = \"%{ {b=1}.b  }  \",\n  c = m%\"\n     foo\n  \"%m\n}\n",
+        );
+        assert_format(r##""#a {" ++ "}""##, "\"#a {\" ++ \"}\"\n");
+    }
+
+    #[test]
+    fn invalid_source() {
+        let s = "{ a = }";
+        let id = Files::new().add("<test>", String::from(s));
+        assert!(format(id, s).is_err());
+    }
+
+    #[test]
+    fn stdlib() {
+        for (name, source) in crate::stdlib::modules() {
+            let formatted = format_str(source);
+            assert_eq!(
+                lexemes(&formatted),
+                lexemes(source),
+                "formatting {} changed its tokens",
+                name
+            );
+            assert_eq!(format_str(&formatted), formatted, "{}", name);
+        }
+    }
+}
@@ -40,7 +40,8 @@ pub mod ty_path {
     //! higher-order functions: the precise cause of an error can correspond to a small subtype of
     //! the original record type. Type path elements can thus also consist of a record field,
     //! indicating that the path leading to the subtype of interest goes through a record via a
-    //! particular field.
+    //! particular field. Likewise, they can consist of a variant, when the subtype of interest is
+    //! the type of the argument of this variant in an enum type.
 
     use super::{AbsType, Types};
     use crate::identifier::Ident;
@@ -52,6 +53,7 @@ pub mod ty_path {
         Codomain,
         Field(Ident),
         Array,
+        Variant(Ident),
     }
 
     pub type Path = Vec<Elem>;
@@ -168,6 +170,39 @@ pub mod ty_path {
                     }
                 }
             }
+            (AbsType::Enum(rows), Some(Elem::Variant(ident))) => {
+                // initial "[|"
+                let mut start_offset = 2;
+                // The space between the tag and the type of its argument
+                let id_offset = 1;
+                // The ", " between two variants
+                let end_offset = 2;
+
+                let mut row = rows.as_ref();
+                loop {
+                    match &row.0 {
+                        AbsType::RowExtend(id, Some(ty), _) if id == ident => {
+                            // The argument is parenthesized if it isn't delimited.
+                            let paren_offset = if ty.fmt_is_delimited() { 0 } else { 1 };
+                            let (sub_start, sub_end) = span(path_it, ty);
+                            let full_offset =
+                                start_offset + format!("{}", id).len() + id_offset + paren_offset;
+                            break (full_offset + sub_start, full_offset + sub_end);
+                        }
+                        AbsType::RowExtend(id, ty, tail) => {
+                            start_offset += format!("{}", id).len() + end_offset;
+
+                            if let Some(ty) = ty {
+                                let paren_offset = if ty.fmt_is_delimited() { 0 } else { 2 };
+                                start_offset += id_offset + paren_offset + format!("{}", ty).len();
+                            }
+
+                            row = tail;
+                        }
+                        _ => panic!(),
+                    }
+                }
+            }
             (AbsType::Array(ty), Some(Elem::Array)) if *ty.as_ref() == Types(AbsType::Dyn()) =>
             // Dyn shouldn't be the target of any blame
             {
@@ -1,14 +1,20 @@
 pub mod cache;
+pub mod deserialize;
 pub mod destruct;
 pub mod environment;
 pub mod error;
 pub mod eval;
+pub mod format;
 pub mod identifier;
 pub mod label;
+pub mod number;
+pub mod package;
 pub mod parser;
 pub mod position;
 pub mod program;
 pub mod repl;
+pub mod report;
+pub mod schema;
 pub mod serialize;
 pub mod stdlib;
 pub mod term;
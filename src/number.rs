@@ -0,0 +1,555 @@
+//! Representation of Nickel numbers.
+//!
+//! Numbers are arbitrary precision rationals. Integers of any size and decimal literals are thus
+//! represented exactly, and the arithmetic operations `+`, `-`, `*`, `/` and `%` are exact as
+//! well. Only a few operations (such as exponentiation to a non-integer power) and the
+//! serialization of non-integers fall back to floating-point approximations.
+//!
+//! As exact numbers can grow without bound, their size is limited: the numerator and the
+//! denominator of a number can't have more than [`MAX_BITS`] bits together, and a literal can't
+//! have an exponent larger than [`MAX_EXPONENT`]. Operations which would exceed these limits
+//! fail with [`NumberError::TooLarge`] instead of running for an unbounded time.
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{Pow, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A number, that is an arbitrary precision rational.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Number(BigRational);
+
+impl Number {
+    /// Determine if the number is an integer.
+    pub fn is_integer(&self) -> bool {
+        self.0.is_integer()
+    }
+
+    /// Determine if the number is zero.
+    pub fn is_zero(&self) -> bool {
+        self.0.is_zero()
+    }
+
+    /// Create a number from an integer.
+    fn from_integer(n: BigInt) -> Self {
+        Number(BigRational::from_integer(n))
+    }
+
+    /// The number of bits of the numerator and of the denominator, taken together.
+    fn significant_bits(&self) -> u64 {
+        self.0.numer().bits() + self.0.denom().bits()
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format(self))
+    }
+}
+
+macro_rules! impl_from_integer {
+    ( $( $int:ty ),* ) => {
+        $(
+            impl From<$int> for Number {
+                fn from(n: $int) -> Self {
+                    Number::from_integer(BigInt::from(n))
+                }
+            }
+        )*
+    };
+}
+
+impl_from_integer!(i32, i64, i128, u32, u64, u128, usize);
+
+macro_rules! impl_try_into_integer {
+    ( $( $int:ty => $to:ident ),* ) => {
+        $(
+            /// Fail with [`NumberError::Invalid`] if the number is not an integer, and with
+            /// [`NumberError::TooLarge`] if it is out of the range of the integer type.
+            impl TryFrom<&Number> for $int {
+                type Error = NumberError;
+
+                fn try_from(n: &Number) -> Result<Self, NumberError> {
+                    if n.is_integer() {
+                        n.0.numer().$to().ok_or(NumberError::TooLarge)
+                    } else {
+                        Err(NumberError::Invalid)
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_try_into_integer!(
+    i64 => to_i64,
+    i128 => to_i128,
+    u32 => to_u32,
+    u64 => to_u64,
+    u128 => to_u128,
+    usize => to_usize
+);
+
+macro_rules! forward_arith_op {
+    ( $( $op:ident, $method:ident );* ) => {
+        $(
+            impl $op for Number {
+                type Output = Number;
+
+                fn $method(self, other: Number) -> Number {
+                    (&self).$method(&other)
+                }
+            }
+
+            impl $op<&Number> for Number {
+                type Output = Number;
+
+                fn $method(self, other: &Number) -> Number {
+                    (&self).$method(other)
+                }
+            }
+
+            impl $op<Number> for &Number {
+                type Output = Number;
+
+                fn $method(self, other: Number) -> Number {
+                    self.$method(&other)
+                }
+            }
+        )*
+    };
+}
+
+forward_arith_op!(Add, add; Sub, sub; Mul, mul; Div, div);
+
+// The operations on rationals reduce their result by computing GCDs, which is slow for large
+// numbers, so integers are handled separately.
+macro_rules! impl_integer_arith_op {
+    ( $( $op:ident, $method:ident );* ) => {
+        $(
+            impl $op<&Number> for &Number {
+                type Output = Number;
+
+                fn $method(self, other: &Number) -> Number {
+                    if self.is_integer() && other.is_integer() {
+                        Number::from_integer(self.0.numer().$method(other.0.numer()))
+                    } else {
+                        Number((&self.0).$method(&other.0))
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_integer_arith_op!(Add, add; Sub, sub; Mul, mul);
+
+impl Div<&Number> for &Number {
+    type Output = Number;
+
+    fn div(self, other: &Number) -> Number {
+        Number(&self.0 / &other.0)
+    }
+}
+
+impl Neg for Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        Number(-self.0)
+    }
+}
+
+impl Neg for &Number {
+    type Output = Number;
+
+    fn neg(self) -> Number {
+        Number(-&self.0)
+    }
+}
+
+/// The maximum number of bits of the numerator and the denominator of a number, taken together.
+pub const MAX_BITS: u64 = 1 << 20;
+
+/// The maximum absolute value of the exponent of a number literal, as in `1e100`.
+pub const MAX_EXPONENT: i64 = 100_000;
+
+/// An error occurring when parsing or computing a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberError {
+    /// The input is not a valid number, or the result of an operation is undefined.
+    Invalid,
+    /// The number exceeds the size limits.
+    TooLarge,
+}
+
+/// Check that a number doesn't exceed [`MAX_BITS`].
+pub fn check_size(n: Number) -> Result<Number, NumberError> {
+    if n.significant_bits() > MAX_BITS {
+        Err(NumberError::TooLarge)
+    } else {
+        Ok(n)
+    }
+}
+
+/// Return `10` raised to a power.
+fn pow10(exp: i64) -> Number {
+    let power = num_traits::pow(BigInt::from(10), exp.unsigned_abs() as usize);
+
+    if exp < 0 {
+        Number(BigRational::new_raw(BigInt::from(1), power))
+    } else {
+        Number::from_integer(power)
+    }
+}
+
+/// Parse a number written in decimal notation, with an optional sign, fractional part and
+/// exponent, as in `-1.5e3`. The result is exact.
+pub fn parse(s: &str) -> Result<Number, NumberError> {
+    // Check the exponent first, as computing a power of ten is as costly as the result is large.
+    if let Some(i) = s.find(['e', 'E']) {
+        let exp = &s[i + 1..];
+        let digits = exp.strip_prefix(['+', '-']).unwrap_or(exp);
+
+        if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            match exp.parse::<i64>() {
+                Ok(exp) if exp.abs() <= MAX_EXPONENT => (),
+                _ => return Err(NumberError::TooLarge),
+            }
+        }
+    }
+
+    let (mantissa, exp) = match s.find(['e', 'E']) {
+        Some(i) => (
+            &s[..i],
+            s[i + 1..]
+                .parse::<i64>()
+                .map_err(|_| NumberError::Invalid)?,
+        ),
+        None => (s, 0),
+    };
+    let (negative, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => (true, mantissa),
+        None => (false, mantissa),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", int, frac);
+
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(NumberError::Invalid);
+    }
+
+    let mut n = BigInt::parse_bytes(digits.as_bytes(), 10).ok_or(NumberError::Invalid)?;
+    if negative {
+        n = -n;
+    }
+
+    let exp = exp - frac.len() as i64;
+    if exp >= 0 || n.is_zero() {
+        return check_size(Number::from_integer(n) * pow10(exp));
+    }
+
+    // The only common factors of the digits and of a power of ten are powers of two and five,
+    // which are removed directly rather than by computing a GCD.
+    let scale = exp.unsigned_abs();
+    let twos = n.trailing_zeros().unwrap_or(0).min(scale);
+    n >>= twos;
+    let five = BigInt::from(5);
+    let mut fives = 0;
+    while fives < scale && n.is_multiple_of(&five) {
+        n /= &five;
+        fives += 1;
+    }
+
+    let denom = (BigInt::from(1) << (scale - twos) as usize)
+        * num_traits::pow(five, (scale - fives) as usize);
+    check_size(Number(BigRational::new_raw(n, denom)))
+}
+
+/// Convert a float to the number with the shortest decimal notation which rounds to it, such that
+/// `0.1` gives back exactly `1/10` and not its binary approximation. Return `None` for infinite
+/// and NaN values.
+pub fn from_f64(f: f64) -> Option<Number> {
+    if f.is_finite() {
+        parse(&format!("{:e}", f)).ok()
+    } else {
+        None
+    }
+}
+
+/// Return the float nearest to a number, saturating to the largest float.
+pub fn to_f64(n: &Number) -> f64 {
+    to_finite_f64(n).unwrap_or(if n.0.is_negative() {
+        f64::MIN
+    } else {
+        f64::MAX
+    })
+}
+
+/// Return the float nearest to a number, or `None` if the number is beyond the range of floats,
+/// instead of saturating to the largest float as [`to_f64`] does.
+pub fn to_finite_f64(n: &Number) -> Option<f64> {
+    n.0.to_f64().filter(|f| f.is_finite())
+}
+
+/// Return the number of digits after the decimal point of a rational with the given denominator,
+/// if its decimal expansion is finite, that is if the denominator only has `2` and `5` as prime
+/// factors.
+fn decimal_scale(denom: &BigInt) -> Option<i64> {
+    let five = BigInt::from(5);
+    let twos = denom.trailing_zeros().unwrap_or(0);
+    let mut rest = denom >> twos;
+    let mut fives = 0;
+
+    while rest.is_multiple_of(&five) {
+        rest /= &five;
+        fives += 1;
+    }
+
+    if rest == BigInt::from(1) {
+        Some(twos.max(fives) as i64)
+    } else {
+        None
+    }
+}
+
+/// Return the exponent of the leading digit of a positive rational, that is the floor of its
+/// logarithm in base `10`.
+fn floor_log10(r: &BigRational) -> i64 {
+    // Compare `r` with `10^log` without building a rational, which would compute a GCD.
+    let ge_pow10 = |log: i64| {
+        let power = num_traits::pow(BigInt::from(10), log.unsigned_abs() as usize);
+        if log >= 0 {
+            *r.numer() >= r.denom() * power
+        } else {
+            r.numer() * power >= *r.denom()
+        }
+    };
+
+    let bits = r.numer().bits() as i64 - r.denom().bits() as i64;
+    let mut log = (bits as f64 * std::f64::consts::LOG10_2).floor() as i64;
+
+    while !ge_pow10(log) {
+        log -= 1;
+    }
+    while ge_pow10(log + 1) {
+        log += 1;
+    }
+
+    log
+}
+
+/// Round the positive quotient of two integers to the nearest integer, rounding ties to the even
+/// one.
+fn round_half_even(numer: &BigInt, denom: &BigInt) -> BigInt {
+    let (quotient, remainder) = numer.div_rem(denom);
+
+    match (remainder * 2u32).cmp(denom) {
+        Ordering::Greater => quotient + 1u32,
+        Ordering::Equal if quotient.is_odd() => quotient + 1u32,
+        _ => quotient,
+    }
+}
+
+/// Format a number. Integers and numbers with a finite decimal expansion are printed exactly,
+/// while other ones (such as `1/3`) are rounded to the same number of digits as a float. Numbers
+/// with a small or, when rounded, a large exponent are printed in scientific notation.
+pub fn format(n: &Number) -> String {
+    if n.is_integer() {
+        return n.0.numer().to_string();
+    }
+
+    let sign = if n.0.is_negative() { "-" } else { "" };
+    let abs = n.0.abs();
+    let mut log = floor_log10(&abs);
+    // The number of digits after the decimal point.
+    let (mut scale, exact) = match decimal_scale(abs.denom()) {
+        Some(scale) => (scale, true),
+        None => (15 - log, false),
+    };
+    let power = num_traits::pow(BigInt::from(10), scale.unsigned_abs() as usize);
+    let mut digits = if scale >= 0 {
+        round_half_even(&(abs.numer() * power), abs.denom())
+    } else {
+        round_half_even(abs.numer(), &(abs.denom() * power))
+    }
+    .to_string();
+
+    // The number was rounded up to a power of ten, which has one more digit.
+    if !exact && digits.len() > 16 {
+        digits.pop();
+        log += 1;
+        scale -= 1;
+    }
+
+    if log <= -6 || scale < 0 {
+        let digits = digits.trim_end_matches('0');
+        let (first, rest) = digits.split_at(1);
+        let point = if rest.is_empty() { "" } else { "." };
+        format!("{}{}{}{}e{}", sign, first, point, rest, log)
+    } else {
+        // Trailing zeros are trimmed from the fractional part only.
+        let int_len = digits.len().saturating_sub(scale as usize);
+        let frac = digits[int_len..].trim_end_matches('0');
+        let int = &digits[..int_len];
+
+        if log < 0 {
+            let zeros = "0".repeat((-log - 1) as usize);
+            format!("{}0.{}{}", sign, zeros, frac)
+        } else if frac.is_empty() {
+            format!("{}{}", sign, int)
+        } else {
+            format!("{}{}.{}", sign, int, frac)
+        }
+    }
+}
+
+/// Return the remainder of the truncated division of `a` by `b`, which has the sign of `a`, as for
+/// floats. Return `None` if `b` is zero.
+pub fn rem(a: &Number, b: &Number) -> Option<Number> {
+    if b.is_zero() {
+        return None;
+    }
+
+    if a.is_integer() && b.is_integer() {
+        return Some(Number::from_integer(a.0.numer() % b.0.numer()));
+    }
+
+    let quotient = Number((&a.0 / &b.0).trunc());
+    Some(a - b * quotient)
+}
+
+/// Raise a number to a power. The result is exact if the exponent is an integer, and is computed
+/// with floats otherwise. Fail with [`NumberError::Invalid`] if the result is undefined or not
+/// representable, as for `0` raised to a negative power or a negative number raised to a
+/// fractional one, and with [`NumberError::TooLarge`] if the exact result would exceed
+/// [`MAX_BITS`].
+pub fn pow(base: &Number, exp: &Number) -> Result<Number, NumberError> {
+    // A lower bound of the size of the result, which is zero for `0`, `1` and `-1`.
+    let base_bits = base.significant_bits().saturating_sub(2);
+
+    match i64::try_from(exp) {
+        Ok(exp) if exp < 0 && base.is_zero() => Err(NumberError::Invalid),
+        Ok(exp) if base_bits.saturating_mul(exp.unsigned_abs()) > MAX_BITS => {
+            Err(NumberError::TooLarge)
+        }
+        Ok(exp) => check_size(Number(Pow::pow(&base.0, exp))),
+        Err(_) if exp.is_integer() && base_bits > 0 => Err(NumberError::TooLarge),
+        Err(_) => from_f64(to_f64(base).powf(to_f64(exp))).ok_or(NumberError::Invalid),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format() {
+        let cases = [
+            ("0", "0"),
+            ("-0.00", "0"),
+            ("0.5e1", "5"),
+            ("0.000012340", "0.00001234"),
+            ("-12", "-12"),
+            ("0.1", "0.1"),
+            ("1.50", "1.5"),
+            ("1e3", "1000"),
+            ("-2.5e-3", "-0.0025"),
+            ("9007199254740993", "9007199254740993"),
+            (
+                "123456789012345678901234567890.25",
+                "123456789012345678901234567890.25",
+            ),
+        ];
+
+        for (input, output) in cases.iter() {
+            assert_eq!(parse(input).map(|n| format(&n)).as_deref(), Ok(*output));
+        }
+
+        assert_eq!(parse("1/3"), Err(NumberError::Invalid));
+        assert_eq!(parse("inf"), Err(NumberError::Invalid));
+        assert_eq!(parse("1e"), Err(NumberError::Invalid));
+        assert_eq!(
+            format(&(Number::from(1) / Number::from(3))),
+            "0.3333333333333333"
+        );
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(from_f64(0.1), parse("0.1").ok());
+        assert_eq!(from_f64(-3.0), Some(Number::from(-3)));
+        assert_eq!(from_f64(f64::NAN), None);
+        assert_eq!(to_f64(&parse("0.1").unwrap()), 0.1);
+        assert_eq!(to_finite_f64(&parse("-1.5e308").unwrap()), Some(-1.5e308));
+        assert_eq!(to_finite_f64(&parse("1e400").unwrap()), None);
+        assert_eq!(to_finite_f64(&parse("-1e400").unwrap()), None);
+        assert_eq!(to_finite_f64(&parse("1e-400").unwrap()), Some(0.0));
+    }
+
+    #[test]
+    fn remainders() {
+        let n = |s| parse(s).unwrap();
+
+        assert_eq!(rem(&n("7"), &n("3")), Some(n("1")));
+        assert_eq!(rem(&n("-7"), &n("3")), Some(n("-1")));
+        assert_eq!(rem(&n("7.5"), &n("2")), Some(n("1.5")));
+        assert_eq!(rem(&n("-0.25"), &n("1")), Some(n("-0.25")));
+        assert_eq!(rem(&n("1"), &n("0")), None);
+    }
+
+    #[test]
+    fn powers() {
+        let two = Number::from(2);
+
+        assert_eq!(pow(&two, &Number::from(64)), parse("18446744073709551616"));
+        assert_eq!(pow(&two, &Number::from(-2)), parse("0.25"));
+        assert_eq!(
+            pow(&Number::from(0), &Number::from(-1)),
+            Err(NumberError::Invalid)
+        );
+        assert_eq!(pow(&Number::from(4), &parse("0.5").unwrap()), Ok(two));
+        assert_eq!(
+            pow(&Number::from(-4), &parse("0.5").unwrap()),
+            Err(NumberError::Invalid)
+        );
+    }
+
+    #[test]
+    fn size_limits() {
+        let ten = Number::from(10);
+
+        assert!(parse("1e100000").is_ok());
+        assert!(parse("1e-100000").is_ok());
+        assert_eq!(parse("1e1000000000"), Err(NumberError::TooLarge));
+        assert_eq!(parse("-2.5E-1000000000"), Err(NumberError::TooLarge));
+        assert_eq!(
+            parse("1e99999999999999999999999"),
+            Err(NumberError::TooLarge)
+        );
+
+        assert!(pow(&ten, &Number::from(1000)).is_ok());
+        assert_eq!(
+            pow(&ten, &Number::from(1_000_000_000)),
+            Err(NumberError::TooLarge)
+        );
+        assert_eq!(
+            pow(&ten, &parse("1e30").unwrap()),
+            Err(NumberError::TooLarge)
+        );
+        assert_eq!(
+            pow(&Number::from(-1), &Number::from(1_000_000_001)),
+            Ok(Number::from(-1))
+        );
+        assert_eq!(
+            pow(&Number::from(1), &parse("1e30").unwrap()),
+            Ok(Number::from(1))
+        );
+
+        let big = pow(&ten, &Number::from(300_000)).unwrap();
+        assert_eq!(check_size(&big * &big), Err(NumberError::TooLarge));
+    }
+}
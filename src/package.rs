@@ -0,0 +1,334 @@
+//! Local packages.
+//!
+//! A package is a directory of Nickel files shared between programs. A program declares the
+//! packages it depends on in a manifest, `nickel.toml`, usually found in the directory of the
+//! program or in one of its ancestors:
+//!
+//! ```toml
+//! [dependencies]
+//! k8s = { path = "../contracts/k8s" }
+//! utils = { path = "vendor/utils", git = "https://github.com/example/utils", rev = "v1.2.0" }
+//! ```
+//!
+//! Dependencies always live in local directories, given relatively to the manifest. A dependency
+//! coming from a git repository is a checkout vendored at `path`: Nickel never fetches anything,
+//! and the repository and the revision are only recorded for reference. Dependencies of packages
+//! are not handled: a package must be listed in the manifest of the program to be imported.
+//!
+//! A file of a dependency is imported by prefixing its path with the name of the dependency, as in
+//! `import "k8s/deployment.ncl"` (see [`crate::cache::Cache`]).
+//!
+//! The lockfile, `nickel.lock`, lives next to the manifest. It records the SHA-256 hash of every
+//! file of each dependency, and is generated by [`Packages::lock`] (`nickel lock` on the command
+//! line). Each time a file belonging to a dependency is imported, its content is checked against
+//! the lockfile, such that a library can't silently change under a program.
+use crate::error::IOError;
+use crate::eval::operation::sha256;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// The name of the manifest file.
+pub const MANIFEST_FILE: &str = "nickel.toml";
+/// The name of the lockfile.
+pub const LOCKFILE: &str = "nickel.lock";
+
+/// The content of a manifest.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub dependencies: IndexMap<String, Dependency>,
+}
+
+/// A dependency of a manifest.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Dependency {
+    /// The directory of the package, relative to the manifest.
+    pub path: PathBuf,
+    /// The git repository the package has been checked out from, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<String>,
+    /// The revision of the git checkout, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+}
+
+/// The content of a lockfile.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Lockfile {
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+/// A dependency recorded in a lockfile.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct LockedPackage {
+    pub name: String,
+    #[serde(flatten)]
+    pub source: Dependency,
+    /// The SHA-256 hash of each file of the package, indexed by its path relative to the root of
+    /// the package, using `/` as a separator.
+    pub files: BTreeMap<String, String>,
+}
+
+/// The dependencies of a program, as declared by a manifest, together with the corresponding
+/// lockfile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Packages {
+    /// The directory of the manifest.
+    root: PathBuf,
+    manifest: Manifest,
+    /// The lockfile, if there is one.
+    lockfile: Option<Lockfile>,
+}
+
+impl Packages {
+    /// Load a manifest, and the lockfile next to it if it exists.
+    pub fn load(manifest_path: impl AsRef<Path>) -> Result<Self, IOError> {
+        let manifest_path = manifest_path.as_ref();
+        let root = manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        let manifest = toml::from_str(&read(manifest_path)?)
+            .map_err(|err| IOError(format!("{}: {}", manifest_path.display(), err)))?;
+
+        let lockfile_path = root.join(LOCKFILE);
+        let lockfile = if lockfile_path.exists() {
+            let lockfile = toml::from_str(&read(&lockfile_path)?)
+                .map_err(|err| IOError(format!("{}: {}", lockfile_path.display(), err)))?;
+            Some(lockfile)
+        } else {
+            None
+        };
+
+        Ok(Packages {
+            root,
+            manifest,
+            lockfile,
+        })
+    }
+
+    /// Look for a manifest in a directory and its ancestors, and load the first one found.
+    pub fn find(dir: impl AsRef<Path>) -> Result<Option<Self>, IOError> {
+        dir.as_ref()
+            .ancestors()
+            .map(|dir| dir.join(MANIFEST_FILE))
+            .find(|path| path.is_file())
+            .map(Packages::load)
+            .transpose()
+    }
+
+    /// The path of the lockfile corresponding to the manifest.
+    pub fn lockfile_path(&self) -> PathBuf {
+        self.root.join(LOCKFILE)
+    }
+
+    /// If the first component of an import path is the name of a dependency, return the path of
+    /// the imported file inside the directory of this dependency.
+    pub fn resolve(&self, path: &Path) -> Option<PathBuf> {
+        let mut components = path.components();
+        let name = match components.next() {
+            Some(Component::Normal(name)) => name.to_str()?,
+            _ => return None,
+        };
+
+        let dependency = self.manifest.dependencies.get(name)?;
+        Some(self.root.join(&dependency.path).join(components.as_path()))
+    }
+
+    /// Check the content of an imported file against the lockfile, if this file belongs to one of
+    /// the dependencies. Other files are always accepted.
+    pub fn verify(&self, path: &Path, content: &[u8]) -> Result<(), String> {
+        let path = match path.canonicalize() {
+            Ok(path) => path,
+            Err(_) => return Ok(()),
+        };
+
+        let owner = self.manifest.dependencies.keys().find_map(|name| {
+            let dir = self.package_dir(name)?.canonicalize().ok()?;
+            path.strip_prefix(dir)
+                .ok()
+                .map(|relative| (name, relative_key(relative)))
+        });
+
+        let (name, key) = match owner {
+            Some(owner) => owner,
+            None => return Ok(()),
+        };
+
+        let lockfile = self.lockfile.as_ref().ok_or_else(|| {
+            format!(
+                "the lockfile {} doesn't exist",
+                self.lockfile_path().display()
+            )
+        })?;
+        let package = lockfile
+            .packages
+            .iter()
+            .find(|package| &package.name == name)
+            .ok_or_else(|| format!("the package `{}` is not recorded in the lockfile", name))?;
+
+        if package.source != self.manifest.dependencies[name] {
+            return Err(format!(
+                "the package `{}` has changed in the manifest since the lockfile was generated",
+                name
+            ));
+        }
+
+        let expected = package.files.get(&key).ok_or_else(|| {
+            format!(
+                "the file `{}` of the package `{}` is not recorded in the lockfile",
+                key, name
+            )
+        })?;
+
+        let actual = sha256(content);
+        if &actual == expected {
+            Ok(())
+        } else {
+            Err(format!(
+                "the content of the file `{}` of the package `{}` doesn't match the lockfile \
+                (expected the hash {}, got {})",
+                key, name, expected, actual
+            ))
+        }
+    }
+
+    /// Compute the lockfile of the dependencies, by hashing all the files of each package. Hidden
+    /// files and directories, such as `.git`, are ignored.
+    pub fn lock(&self) -> Result<Lockfile, IOError> {
+        let packages = self
+            .manifest
+            .dependencies
+            .iter()
+            .map(|(name, dependency)| {
+                let dir = self.root.join(&dependency.path);
+                let mut files = BTreeMap::new();
+                hash_dir(&dir, &dir, &mut files).map_err(|err| {
+                    IOError(format!("package `{}` ({}): {}", name, dir.display(), err))
+                })?;
+
+                Ok(LockedPackage {
+                    name: name.clone(),
+                    source: dependency.clone(),
+                    files,
+                })
+            })
+            .collect::<Result<_, IOError>>()?;
+
+        Ok(Lockfile { packages })
+    }
+
+    /// Compute the lockfile of the dependencies and write it next to the manifest.
+    pub fn write_lockfile(&mut self) -> Result<(), IOError> {
+        let lockfile = self.lock()?;
+        let content = toml::to_string(&lockfile).map_err(|err| IOError(err.to_string()))?;
+        fs::write(self.lockfile_path(), content)?;
+        self.lockfile = Some(lockfile);
+        Ok(())
+    }
+
+    fn package_dir(&self, name: &str) -> Option<PathBuf> {
+        self.manifest
+            .dependencies
+            .get(name)
+            .map(|dependency| self.root.join(&dependency.path))
+    }
+}
+
+fn read(path: &Path) -> Result<String, IOError> {
+    fs::read_to_string(path).map_err(|err| IOError(format!("{}: {}", path.display(), err)))
+}
+
+/// The key of a file in the lockfile: its path relative to the root of the package, using `/` as a
+/// separator whatever the platform is.
+fn relative_key(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Recursively hash the files of a directory.
+fn hash_dir(root: &Path, dir: &Path, files: &mut BTreeMap<String, String>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            hash_dir(root, &path, files)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap();
+            files.insert(relative_key(relative), sha256(fs::read(&path)?));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packages(dependencies: &str) -> Packages {
+        Packages {
+            root: PathBuf::from("project"),
+            manifest: toml::from_str(dependencies).unwrap(),
+            lockfile: None,
+        }
+    }
+
+    #[test]
+    fn resolve() {
+        let packages = packages(
+            "[dependencies]
+            k8s = { path = \"../lib/k8s\" }
+            utils = { path = \"vendor/utils\", git = \"https://example.com/utils\", rev = \"v1\" }",
+        );
+
+        assert_eq!(
+            packages.resolve(Path::new("k8s/deployment.ncl")),
+            Some(PathBuf::from("project/../lib/k8s/deployment.ncl"))
+        );
+        assert_eq!(
+            packages.resolve(Path::new("utils/a/b.ncl")),
+            Some(PathBuf::from("project/vendor/utils/a/b.ncl"))
+        );
+        assert_eq!(packages.resolve(Path::new("other/a.ncl")), None);
+        assert_eq!(packages.resolve(Path::new("./k8s/a.ncl")), None);
+    }
+
+    #[test]
+    fn invalid_manifest() {
+        assert!(toml::from_str::<Manifest>("[dependencies]\nk8s = { paht = \"k8s\" }").is_err());
+    }
+
+    #[test]
+    fn lockfile_roundtrip() {
+        let mut files = BTreeMap::new();
+        files.insert(String::from("a/b.ncl"), sha256("1"));
+        let lockfile = Lockfile {
+            packages: vec![LockedPackage {
+                name: String::from("utils"),
+                source: Dependency {
+                    path: PathBuf::from("vendor/utils"),
+                    git: Some(String::from("https://example.com/utils")),
+                    rev: None,
+                },
+                files,
+            }],
+        };
+
+        let content = toml::to_string(&lockfile).unwrap();
+        assert_eq!(toml::from_str::<Lockfile>(&content).unwrap(), lockfile);
+    }
+}
@@ -33,4 +33,9 @@ pub enum ParseError {
         RawSpan, /* tail position */
         RawSpan, /* whole record position */
     ),
+    /// An unknown or ill-formed strategy in an `array_merge` annotation. The valid strategies are
+    /// `concat`, `union`, `union_by "<field>"` and `replace`.
+    InvalidArrayMerge(RawSpan),
+    /// An unknown format in an import `import "<path>" as <format>`.
+    InvalidImportFormat(RawSpan),
 }
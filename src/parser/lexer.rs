@@ -29,6 +29,7 @@
 //! `0`, this is the end of the current interpolated expressions, and we leave the normal mode and
 //! go back to string mode. In our example, this is the second `}`: at this point, the lexer knows
 //! that the coming characters must be lexed as string tokens, and not as normal tokens.
+use crate::number::{self, Number};
 use crate::parser::error::{LexicalError, ParseError};
 use logos::Logos;
 use std::ops::Range;
@@ -42,8 +43,8 @@ pub enum NormalToken<'input> {
 
     #[regex("_?[a-zA-Z][_a-zA-Z0-9-]*")]
     Identifier(&'input str),
-    #[regex("[0-9]*\\.?[0-9]+", |lex| lex.slice().parse())]
-    NumLiteral(f64),
+    #[regex("[0-9]*\\.?[0-9]+", |lex| number::parse(lex.slice()).ok())]
+    NumLiteral(Number),
 
     #[token("Dyn")]
     Dyn,
@@ -70,6 +71,8 @@ pub enum NormalToken<'input> {
     Let,
     #[token("switch")]
     Switch,
+    #[token("match")]
+    Match,
 
     #[token("null")]
     Null,
@@ -128,6 +131,8 @@ pub enum NormalToken<'input> {
     Fun,
     #[token("import")]
     Import,
+    #[token("as")]
+    As,
     #[token("|")]
     Pipe,
     #[token("|>")]
@@ -174,6 +179,8 @@ pub enum NormalToken<'input> {
     GoField,
     #[token("%go_array%")]
     GoArray,
+    #[token("%go_variant%")]
+    GoVariant,
 
     #[token("%wrap%")]
     Wrap,
@@ -218,6 +225,18 @@ pub enum NormalToken<'input> {
     Default,
     #[token("doc")]
     Doc,
+    #[token("not_exported")]
+    NotExported,
+    #[token("optional")]
+    Optional,
+    #[token("deprecated")]
+    Deprecated,
+    #[token("array_merge")]
+    ArrayMerge,
+    #[token("priority")]
+    Priority,
+    #[token("force")]
+    Force,
 
     #[token("%hash%")]
     OpHash,
@@ -259,6 +278,12 @@ pub enum NormalToken<'input> {
     NumFromStr,
     #[token("%enum_from_str%")]
     EnumFromStr,
+    #[token("%enum_is_variant%")]
+    EnumIsVariant,
+    #[token("%enum_get_tag%")]
+    EnumGetTag,
+    #[token("%enum_get_arg%")]
+    EnumGetArg,
 
     #[token("{")]
     LBrace,
@@ -387,6 +412,15 @@ impl<'input> ModalLexer<'input> {
     }
 }
 
+/// The progress in lexing an import `import "<path>"`, after which `as` is a keyword.
+#[derive(Clone, PartialEq, Eq, Debug, Copy)]
+pub enum ImportState {
+    Outside,
+    Import,
+    Path,
+    AfterPath,
+}
+
 #[derive(Clone, PartialEq, Eq, Debug, Copy)]
 pub enum ModeElt {
     Str,
@@ -420,6 +454,11 @@ pub struct Lexer<'input> {
     /// made necessary by an issue of Logos (<https://github.com/maciejhirsz/logos/issues/200>). See
     /// [`MultiStringToken::QuotesCandidateInterpolation`].
     pub buffer: Option<(Token<'input>, Range<usize>)>,
+    /// The last token returned, which determines if a contextual keyword is in the position of a
+    /// keyword. See [`Lexer::contextual_keyword`].
+    pub prev: Option<Token<'input>>,
+    /// The progress in lexing an import, which determines if `as` is a keyword.
+    pub import: ImportState,
 }
 
 impl<'input> Lexer<'input> {
@@ -429,6 +468,64 @@ impl<'input> Lexer<'input> {
             stack: Vec::new(),
             count: 0,
             buffer: None,
+            prev: None,
+            import: ImportState::Outside,
+        }
+    }
+
+    /// Turn a contextual keyword into an identifier when it isn't in the position of a keyword,
+    /// such that it can still be used as a field name or a variable. The keywords of annotations,
+    /// such as `not_exported`, are only reserved right after `|`, `match` is only reserved right
+    /// before `{`, and `as` is only reserved right after the path of an import.
+    fn contextual_keyword(&self, token: Token<'input>) -> Token<'input> {
+        let after_pipe = self.prev == Some(Token::Normal(NormalToken::Pipe));
+
+        let (keyword, reserved) = match token {
+            Token::Normal(NormalToken::NotExported) => ("not_exported", after_pipe),
+            Token::Normal(NormalToken::Optional) => ("optional", after_pipe),
+            Token::Normal(NormalToken::Deprecated) => ("deprecated", after_pipe),
+            Token::Normal(NormalToken::Priority) => ("priority", after_pipe),
+            Token::Normal(NormalToken::Force) => ("force", after_pipe),
+            Token::Normal(NormalToken::ArrayMerge) => ("array_merge", after_pipe),
+            Token::Normal(NormalToken::Match) => ("match", self.next_is_lbrace()),
+            Token::Normal(NormalToken::As) => ("as", self.import == ImportState::AfterPath),
+            _ => return token,
+        };
+
+        if reserved {
+            token
+        } else {
+            Token::Normal(NormalToken::Identifier(keyword))
+        }
+    }
+
+    /// Update the progress in lexing an import with the token being returned.
+    fn track_import(&mut self, token: &Token<'input>) {
+        use ImportState::*;
+
+        self.import = match (self.import, token) {
+            (_, Token::Normal(NormalToken::Import)) => Import,
+            (Import, Token::Normal(NormalToken::DoubleQuote))
+            | (Import, Token::Normal(NormalToken::MultiStringStart(_))) => Path,
+            (Path, Token::Str(StringToken::Literal(_)))
+            | (Path, Token::Str(StringToken::EscapedChar(_)))
+            | (Path, Token::MultiStr(MultiStringToken::Literal(_))) => Path,
+            (Path, Token::Normal(NormalToken::DoubleQuote))
+            | (Path, Token::MultiStr(MultiStringToken::End)) => AfterPath,
+            _ => Outside,
+        };
+    }
+
+    /// Determine if the next token in normal mode is an opening brace, skipping comments.
+    fn next_is_lbrace(&self) -> bool {
+        match &self.lexer {
+            Some(ModalLexer::Normal(lexer)) => {
+                lexer
+                    .clone()
+                    .find(|token| *token != NormalToken::LineComment)
+                    == Some(NormalToken::LBrace)
+            }
+            _ => false,
         }
     }
 
@@ -681,6 +778,11 @@ impl<'input> Iterator for Lexer<'input> {
             _ => (),
         };
 
+        let token = token.map(|t| self.contextual_keyword(t));
+        if let Some(token) = &token {
+            self.track_import(token);
+        }
+        self.prev = token.clone();
         token.map(|t| Ok((span.start, t, span.end)))
     }
 }
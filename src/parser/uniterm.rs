@@ -210,13 +210,21 @@ impl UniRecord {
                                         doc: None,
                                         types: Some(ctrt),
                                         contracts,
-                                        priority: MergePriority::Normal,
+                                        priority,
+                                        not_exported: false,
+                                        optional: false,
+                                        deprecated: None,
+                                        array_merge: None,
                                         value: None,
-                                    }) if contracts.is_empty() => Ok(Types(AbsType::RowExtend(
-                                        id,
-                                        Some(Box::new(ctrt.types)),
-                                        Box::new(acc),
-                                    ))),
+                                    }) if contracts.is_empty()
+                                        && priority == MergePriority::normal() =>
+                                    {
+                                        Ok(Types(AbsType::RowExtend(
+                                            id,
+                                            Some(Box::new(ctrt.types)),
+                                            Box::new(acc),
+                                        )))
+                                    }
                                     _ => {
                                         // Position of identifiers must always be set at this stage
                                         // (parsing)
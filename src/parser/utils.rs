@@ -1,13 +1,13 @@
 //! Various helpers and companion code for the parser are put here to keep the grammar definition
 //! uncluttered.
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
 use std::fmt::Debug;
 use std::rc::Rc;
 
 use codespan::FileId;
+use indexmap::{map::Entry, IndexMap};
 
 use crate::{
+    destruct::{Destruct, MatchCase},
     identifier::Ident,
     label::Label,
     mk_app, mk_fun,
@@ -24,13 +24,61 @@ pub enum StringKind {
     Multiline,
 }
 
-/// Distinguish between a normal case `id => exp` and a default case `_ => exp`.
+/// Distinguish between a normal case `id => exp`, a case binding the argument of a variant
+/// `id pat => exp` and a default case `_ => exp`.
 #[derive(Clone, Debug)]
 pub enum SwitchCase {
-    Normal(Ident, RichTerm),
+    Normal(Ident, RichTerm, RawSpan),
+    Variant(Ident, (Option<Ident>, Destruct), RichTerm, RawSpan),
     Default(RichTerm),
 }
 
+/// Convert a switch binding the argument of enum variants to the equivalent match expression. The
+/// default case, if any, becomes a final wildcard case.
+pub fn switch_to_match(exp: RichTerm, cases: Vec<SwitchCase>) -> Term {
+    let mut default = None;
+    let mut match_cases: Vec<MatchCase> = cases
+        .into_iter()
+        .filter_map(|case| {
+            let (pattern, body) = match case {
+                SwitchCase::Normal(id, body, span) => (
+                    Destruct::Constant {
+                        value: Term::Enum(id).into(),
+                        span,
+                    },
+                    body,
+                ),
+                SwitchCase::Variant(tag, arg, body, span) => (
+                    Destruct::Variant {
+                        tag,
+                        arg: Box::new(arg),
+                        span,
+                    },
+                    body,
+                ),
+                SwitchCase::Default(body) => {
+                    default.replace(body);
+                    return None;
+                }
+            };
+
+            Some(MatchCase {
+                pattern: (None, pattern),
+                guard: None,
+                body,
+            })
+        })
+        .collect();
+
+    match_cases.extend(default.map(|body| MatchCase {
+        pattern: (None, Destruct::Empty),
+        guard: None,
+        body,
+    }));
+
+    Term::Match(exp, match_cases)
+}
+
 /// Left hand side of a record field declaration.
 #[derive(Clone, Debug)]
 pub enum FieldPathElem {
@@ -141,7 +189,7 @@ pub fn elaborate_field_path(
 
     let content = it.rev().fold(content, |acc, path_elem| match path_elem {
         FieldPathElem::Ident(id) => {
-            let mut map = HashMap::new();
+            let mut map = IndexMap::new();
             map.insert(id, acc);
             Term::Record(map, Default::default()).into()
         }
@@ -167,11 +215,11 @@ pub fn elaborate_field_path(
                     pos: exp.pos,
                 };
 
-                let mut map = HashMap::new();
+                let mut map = IndexMap::new();
                 map.insert(id, acc);
                 Term::Record(map, Default::default()).into()
             } else {
-                let empty = Term::Record(HashMap::new(), Default::default());
+                let empty = Term::Record(IndexMap::new(), Default::default());
                 mk_app!(mk_term::op2(BinaryOp::DynExtend(), exp, empty), acc)
             }
         }
@@ -186,10 +234,10 @@ pub fn build_record<I>(fields: I, attrs: RecordAttrs) -> Term
 where
     I: IntoIterator<Item = (FieldPathElem, RichTerm)> + Debug,
 {
-    let mut static_map = HashMap::new();
+    let mut static_map = IndexMap::new();
     let mut dynamic_fields = Vec::new();
 
-    fn insert_static_field(static_map: &mut HashMap<Ident, RichTerm>, id: Ident, t: RichTerm) {
+    fn insert_static_field(static_map: &mut IndexMap<Ident, RichTerm>, id: Ident, t: RichTerm) {
         match static_map.entry(id) {
             Entry::Occupied(mut occpd) => {
                 // temporary putting null in the entry to take the previous value.
@@ -21,16 +21,28 @@
 //! functions in [`crate::cache`] (see [`crate::cache::Cache::mk_eval_env`]).
 //! Each such value is added to the global environment before the evaluation of the program.
 use crate::cache::*;
-use crate::error::{Error, ToDiagnostic};
+use crate::error::{Error, EvalError, IOError, ToDiagnostic, Warning, Warnings};
+use crate::eval::{merge, Closure};
 use crate::identifier::Ident;
+use crate::label::Label;
+use crate::package::Packages;
 use crate::parser::lexer::Lexer;
-use crate::term::{RichTerm, Term};
-use crate::{eval, parser};
-use codespan::FileId;
+use crate::report::{self as machine_report, ErrorFormat};
+use crate::term::{
+    make as mk_term, BinaryOp, MergePriority, MetaValue, RecordAttrs, RichTerm, Term, UnaryOp,
+};
+use crate::transform::share_normal_form;
+use crate::types::{AbsType, Types};
+use crate::{eval, format, parser, schema, typecheck};
+use codespan::{FileId, Files};
 use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+use indexmap::IndexMap;
 use std::ffi::OsString;
 use std::io::{self, Read};
+use std::path::PathBuf;
+use std::rc::Rc;
 use std::result::Result;
+use std::str::FromStr;
 
 /// A Nickel program.
 ///
@@ -39,8 +51,45 @@ use std::result::Result;
 pub struct Program {
     /// The id of the program source in the file database.
     main_id: FileId,
+    /// The ids of the additional sources, which are merged onto the main one from left to right.
+    inputs: Vec<FileId>,
     /// The cache holding the sources and parsed terms of the main source as well as imports.
     cache: Cache,
+    /// The overrides which are merged onto the program after the additional sources.
+    overrides: Vec<FieldOverride>,
+    /// The warnings emitted during evaluation which have not been reported yet.
+    warnings: Warnings,
+}
+
+/// A value overriding a field of the program, given on the command line as `path=value`, where
+/// the path has the same syntax as for [`query`] and the value is Nickel source code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldOverride {
+    pub path: String,
+    pub value: String,
+}
+
+impl FromStr for FieldOverride {
+    type Err = String;
+
+    /// Split the input at the first `=` which is not part of a quoted field name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut quoted = false;
+        let eq = s.char_indices().find(|&(_, c)| {
+            if c == '"' {
+                quoted = !quoted;
+            }
+            c == '=' && !quoted
+        });
+
+        match eq {
+            Some((i, _)) if !s[..i].trim().is_empty() => Ok(FieldOverride {
+                path: String::from(s[..i].trim()),
+                value: String::from(&s[i + 1..]),
+            }),
+            _ => Err(format!("invalid override `{}`, expected `path=value`", s)),
+        }
+    }
 }
 
 impl Program {
@@ -50,10 +99,33 @@ impl Program {
     }
 
     pub fn new_from_file(path: impl Into<OsString>) -> std::io::Result<Program> {
+        Program::new_from_files(std::iter::once(path))
+    }
+
+    /// Create a program from several files, whose content are merged from left to right, as with
+    /// the `&` operator. Fail if there is no file.
+    pub fn new_from_files<I, P>(paths: I) -> std::io::Result<Program>
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<OsString>,
+    {
         let mut cache = Cache::new();
-        let main_id = cache.add_file(path)?;
+        let mut ids = paths
+            .into_iter()
+            .map(|path| cache.add_file(path))
+            .collect::<std::io::Result<Vec<_>>>()?
+            .into_iter();
+        let main_id = ids.next().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "no input file")
+        })?;
 
-        Ok(Program { main_id, cache })
+        Ok(Program {
+            main_id,
+            inputs: ids.collect(),
+            cache,
+            overrides: Vec::new(),
+            warnings: Warnings::new(),
+        })
     }
 
     /// Create a program by reading it from a generic source.
@@ -65,59 +137,245 @@ impl Program {
         let mut cache = Cache::new();
         let main_id = cache.add_source(source_name, source)?;
 
-        Ok(Program { main_id, cache })
+        Ok(Program {
+            main_id,
+            inputs: Vec::new(),
+            cache,
+            overrides: Vec::new(),
+            warnings: Warnings::new(),
+        })
+    }
+
+    /// Override fields of the program. The values are merged onto the program with the priority
+    /// [`MergePriority::Override`], in order, such that they take precedence over the values
+    /// defined by the program itself, including forced ones.
+    pub fn add_overrides(&mut self, overrides: impl IntoIterator<Item = FieldOverride>) {
+        self.overrides.extend(overrides);
+    }
+
+    /// Add directories to the import search path, consulted in order for the imports which can't
+    /// be found relatively to the importing file. See [`Cache::add_import_paths`].
+    pub fn add_import_paths<P>(&mut self, paths: impl IntoIterator<Item = P>)
+    where
+        P: Into<PathBuf>,
+    {
+        self.cache.add_import_paths(paths);
+    }
+
+    /// Parse the sources of the program in the given format instead of Nickel, as for data piped
+    /// on the standard input or generated files whose extension is meaningless.
+    pub fn set_input_format(&mut self, format: InputFormat) -> Result<(), Error> {
+        for file_id in std::iter::once(self.main_id).chain(self.inputs.iter().copied()) {
+            self.cache.parse_multi(file_id, format)?;
+        }
+        Ok(())
+    }
+
+    /// Set the dependencies of the program, as declared by a package manifest. See
+    /// [`Cache::set_packages`].
+    pub fn set_packages(&mut self, packages: Packages) {
+        self.cache.set_packages(packages);
+    }
+
+    /// Prepare the sources of the program for evaluation, and return the merge of all of them,
+    /// followed by the overrides.
+    fn prepare_main(&mut self, type_env: &typecheck::Environment) -> Result<RichTerm, Error> {
+        self.cache.prepare(self.main_id, type_env)?;
+        let mut t = self.cache.get_owned(self.main_id).unwrap();
+
+        for &file_id in self.inputs.iter() {
+            self.cache.prepare(file_id, type_env)?;
+            t = mk_term::op2(BinaryOp::Merge(), t, self.cache.get_owned(file_id).unwrap());
+        }
+
+        for FieldOverride { path, value } in self.overrides.clone() {
+            // The path is parsed as for `query`.
+            let source = format!("x.{}", path);
+            let path_id = self.cache.add_tmp("<override>", source.clone());
+            let path_term =
+                parser::grammar::TermParser::new().parse_term(path_id, Lexer::new(&source))?;
+            let pos = path_term.pos;
+            let (root, fields) = split_field_path(path_term);
+
+            if fields.is_empty() || !matches!(root.as_ref(), Term::Var(id) if id.label == "x") {
+                return Err(EvalError::Other(
+                    format!("invalid override path `{}`, expected a field path", path),
+                    pos,
+                )
+                .into());
+            }
+
+            let value_id = self.cache.add_string(format!("<override {}>", path), value);
+            self.cache.prepare(value_id, type_env)?;
+            let value = self.cache.get_owned(value_id).unwrap();
+            let pos = value.pos;
+
+            let meta = MetaValue {
+                priority: MergePriority::Override,
+                value: Some(value),
+                ..Default::default()
+            };
+            // The value has already been transformed, so we only put the generated nodes in share
+            // normal form.
+            let record = fields.into_iter().rev().fold(
+                share_normal_form::transform_one(RichTerm::new(Term::MetaValue(meta), pos)),
+                |acc, id| {
+                    let mut map = IndexMap::new();
+                    map.insert(id, acc);
+                    share_normal_form::transform_one(RichTerm::new(
+                        Term::Record(map, RecordAttrs::default()),
+                        pos,
+                    ))
+                },
+            );
+
+            t = mk_term::op2(BinaryOp::Merge(), t, record);
+        }
+
+        Ok(t)
     }
 
     /// Retrieve the parsed term and typecheck it, and generate a fresh global environment. Return
     /// both.
     fn prepare_eval(&mut self) -> Result<(RichTerm, eval::Environment), Error> {
         let GlobalEnv { eval_env, type_env } = self.cache.prepare_stdlib()?;
-        self.cache.prepare(self.main_id, &type_env)?;
-        Ok((self.cache.get(self.main_id).unwrap(), eval_env))
+        let t = self.prepare_main(&type_env)?;
+        Ok((t, eval_env))
     }
 
     /// Parse if necessary, typecheck and then evaluate the program.
     pub fn eval(&mut self) -> Result<RichTerm, Error> {
         let (t, global_env) = self.prepare_eval()?;
-        eval::eval(t, &global_env, &mut self.cache).map_err(|e| e.into())
+        eval::eval(t, &global_env, &mut self.cache, &mut self.warnings).map_err(|e| e.into())
     }
 
     /// Same as `eval`, but proceeds to a full evaluation.
     pub fn eval_full(&mut self) -> Result<RichTerm, Error> {
         let (t, global_env) = self.prepare_eval()?;
-        eval::eval_full(t, &global_env, &mut self.cache).map_err(|e| e.into())
+        eval::eval_full(t, &global_env, &mut self.cache, &mut self.warnings).map_err(|e| e.into())
     }
 
     /// Same as `eval_full`, but does not substitute all variables.
     pub fn eval_deep(&mut self) -> Result<RichTerm, Error> {
         let (t, global_env) = self.prepare_eval()?;
-        eval::eval_deep(t, &global_env, &mut self.cache).map_err(|e| e.into())
+        eval::eval_deep(t, &global_env, &mut self.cache, &mut self.warnings).map_err(|e| e.into())
     }
 
     /// Wrapper for [`query`].
     pub fn query(&mut self, path: Option<String>) -> Result<Term, Error> {
         let global_env = self.cache.prepare_stdlib()?;
-        query(&mut self.cache, self.main_id, &global_env, path)
+        let t = self.prepare_main(&global_env.type_env)?;
+        query_root(&mut self.cache, &mut self.warnings, t, &global_env, path)
+    }
+
+    /// Wrapper for [`explain`].
+    pub fn explain(&mut self, path: String) -> Result<Explanation, Error> {
+        let global_env = self.cache.prepare_stdlib()?;
+        let t = self.prepare_main(&global_env.type_env)?;
+        explain_root(
+            &mut self.cache,
+            &mut self.warnings,
+            t,
+            &global_env,
+            Some(path),
+        )
     }
 
     /// Load, parse, and typecheck the program and the standard library, if not already done.
     pub fn typecheck(&mut self) -> Result<(), Error> {
-        self.cache.parse(self.main_id)?;
         self.cache.load_stdlib()?;
         let global_env = self.cache.mk_types_env().expect("program::typecheck(): stdlib has been loaded but was not found in cache on mk_types_env()");
-        self.cache
-            .resolve_imports(self.main_id)
-            .map_err(|cache_err| {
-                cache_err.unwrap_error("program::typecheck(): expected source to be parsed")
-            })?;
-        self.cache
-            .typecheck(self.main_id, &global_env)
-            .map_err(|cache_err| {
+
+        for file_id in std::iter::once(self.main_id).chain(self.inputs.clone()) {
+            self.cache.parse(file_id)?;
+            self.cache.resolve_imports(file_id).map_err(|cache_err| {
                 cache_err.unwrap_error("program::typecheck(): expected source to be parsed")
             })?;
+            self.cache
+                .typecheck(file_id, &global_env)
+                .map_err(|cache_err| {
+                    cache_err.unwrap_error("program::typecheck(): expected source to be parsed")
+                })?;
+        }
+
         Ok(())
     }
 
+    /// Validate a data file against the contract defined by the program, or against one of its
+    /// fields given as a dot separated path. The data file can be in any of the input formats
+    /// supported by imports, which is determined by its extension, and is fully evaluated.
+    pub fn validate(
+        &mut self,
+        data: impl Into<OsString>,
+        field: Option<String>,
+    ) -> Result<(), Error> {
+        let data = PathBuf::from(data.into());
+        let format = InputFormat::from_path_buf(&data).unwrap_or(InputFormat::Nickel);
+
+        let GlobalEnv {
+            eval_env: global_env,
+            type_env,
+        } = self.cache.prepare_stdlib()?;
+        let contract = self.prepare_main(&type_env)?;
+
+        let data_id = self.cache.add_file(&data).map_err(IOError::from)?;
+        self.cache.parse_multi(data_id, format)?;
+        self.cache.prepare(data_id, &type_env)?;
+
+        // The contract is selected by parsing a path on a variable standing for the program, such
+        // that blame errors point to it. The program is substituted for the variable, as for
+        // `query`, so that it doesn't shadow anything in the global environment.
+        let source = match field {
+            Some(path) => format!("program.{}", path),
+            None => String::from("program"),
+        };
+        let path_id = self.cache.add_tmp("<contract>", source.clone());
+        let path = parser::grammar::TermParser::new().parse_term(path_id, Lexer::new(&source))?;
+        let span = path.pos.unwrap();
+        let mut env = eval::Environment::new();
+        eval::env_add(
+            &mut env,
+            Ident::from("program"),
+            contract,
+            eval::Environment::new(),
+        );
+        let path = eval::subst(path, &eval::Environment::new(), &env);
+
+        let types = Types(AbsType::Flat(path));
+        let label = Label {
+            types: Rc::new(types.clone()),
+            span,
+            ..Default::default()
+        };
+        let t = mk_term::assume(types, label, self.cache.get_owned(data_id).unwrap()).unwrap();
+
+        eval::eval_full(t, &global_env, &mut self.cache, &mut self.warnings)?;
+        Ok(())
+    }
+
+    /// Generate the JSON Schema of the program. See [`crate::schema`].
+    pub fn schema(&mut self) -> Result<serde_json::Value, Error> {
+        let (t, global_env) = self.prepare_eval()?;
+        schema::generate(t, &global_env, &mut self.cache, &mut self.warnings).map_err(|e| e.into())
+    }
+
+    /// Convert the program, which must be a JSON Schema, to the source of a Nickel contract. See
+    /// [`crate::schema::to_contract`].
+    pub fn convert_schema(&self) -> Result<String, Error> {
+        Ok(self.cache.convert_json_schema(self.main_id)?)
+    }
+
+    /// Parse the program and return its source formatted. See [`crate::format`].
+    pub fn format(&self) -> Result<String, Error> {
+        let source = self.cache.files().source(self.main_id);
+        format::format(self.main_id, source).map_err(Error::from)
+    }
+
+    /// Return the source code of the program.
+    pub fn source(&self) -> &str {
+        self.cache.files().source(self.main_id)
+    }
+
     /// Wrapper for [`report`].
     pub fn report<E>(&mut self, error: E)
     where
@@ -126,6 +384,11 @@ impl Program {
         report(&mut self.cache, error)
     }
 
+    /// Wrapper for [`report_as`], which reports the pending warnings of the program.
+    pub fn report_as(&mut self, error: Option<Error>, format: ErrorFormat) {
+        report_as(&mut self.cache, self.warnings.take(), error, format)
+    }
+
     #[cfg(debug_assertions)]
     pub fn set_skip_stdlib(&mut self) {
         self.cache.skip_stdlib = true;
@@ -149,12 +412,31 @@ impl Program {
 //common to both `Program` and `Repl`. Leaving it here as a stand-alone function for now
 pub fn query(
     cache: &mut Cache,
+    warnings: &mut Warnings,
     file_id: FileId,
     global_env: &GlobalEnv,
     path: Option<String>,
 ) -> Result<Term, Error> {
     cache.prepare(file_id, &global_env.type_env)?;
+    let root = cache.get_owned(file_id).unwrap();
+    query_root(cache, warnings, root, global_env, path)
+}
 
+/// Same as [`query`], but for a prepared term instead of a file.
+fn query_root(
+    cache: &mut Cache,
+    warnings: &mut Warnings,
+    root: RichTerm,
+    global_env: &GlobalEnv,
+    path: Option<String>,
+) -> Result<Term, Error> {
+    let t = query_term(cache, root, path)?;
+    Ok(eval::eval_meta(t, &global_env.eval_env, cache, warnings)?.into())
+}
+
+/// Build the term `x.path`, where `x` is the root term, or return the root term itself if there
+/// is no path.
+fn query_term(cache: &mut Cache, root: RichTerm, path: Option<String>) -> Result<RichTerm, Error> {
     let t = if let Some(p) = path {
         // Parsing `y.path`. We `seq` it to force the evaluation of the underlying value,
         // which can be then showed to the user. The newline gives better messages in case of
@@ -166,18 +448,210 @@ pub fn query(
 
         // Substituting `y` for `t`
         let mut env = eval::Environment::new();
-        eval::env_add(
-            &mut env,
-            Ident::from("x"),
-            cache.get_owned(file_id).unwrap(),
-            eval::Environment::new(),
-        );
+        eval::env_add(&mut env, Ident::from("x"), root, eval::Environment::new());
         eval::subst(new_term, &eval::Environment::new(), &env)
     } else {
-        cache.get_owned(file_id).unwrap()
+        root
     };
 
-    Ok(eval::eval_meta(t, &global_env.eval_env, cache)?.into())
+    Ok(t)
+}
+
+/// Decompose a chain of field accesses `root.id1.id2...` into `root` and the list of identifiers.
+fn split_field_path(t: RichTerm) -> (RichTerm, Vec<Ident>) {
+    let mut root = t;
+    let mut fields = Vec::new();
+
+    while let Term::Op1(UnaryOp::StaticAccess(id), inner) = root.as_ref() {
+        fields.push(id.clone());
+        root = inner.clone();
+    }
+
+    fields.reverse();
+    (root, fields)
+}
+
+/// An operand of the merge expression defining a field, as reported by [`explain`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MergeOperand {
+    /// The position of the operand, as `file:line:column`, if available.
+    pub location: Option<String>,
+    /// The merge priority of the operand.
+    pub priority: MergePriority,
+    /// The type annotation of the operand.
+    pub types: Option<String>,
+    /// The contracts attached to the operand.
+    pub contracts: Vec<String>,
+    /// A representation of the value of the operand, if it is already evaluated.
+    pub value: Option<String>,
+}
+
+/// The provenance of a field, as reported by [`explain`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Explanation {
+    /// The operands which have been merged to define the field, from left to right.
+    pub operands: Vec<MergeOperand>,
+    /// The final value of the field, "weakly" evaluated as for [`query`].
+    pub value: Term,
+}
+
+/// Explain where the value of a field of a term in the cache comes from.
+///
+/// The path is given as for [`query`]. If there is no path, the term of the file must itself be a
+/// field access, as in `config.server.port`. The prefix of the path is evaluated to reach the
+/// field, and the merge expression defining the field is then taken apart before the field is
+/// evaluated, so that all the operands which contributed to its value are reported.
+pub fn explain(
+    cache: &mut Cache,
+    warnings: &mut Warnings,
+    file_id: FileId,
+    global_env: &GlobalEnv,
+    path: Option<String>,
+) -> Result<Explanation, Error> {
+    cache.prepare(file_id, &global_env.type_env)?;
+    let root = cache.get_owned(file_id).unwrap();
+    explain_root(cache, warnings, root, global_env, path)
+}
+
+/// Same as [`explain`], but for a prepared term instead of a file.
+fn explain_root(
+    cache: &mut Cache,
+    warnings: &mut Warnings,
+    root: RichTerm,
+    global_env: &GlobalEnv,
+    path: Option<String>,
+) -> Result<Explanation, Error> {
+    let t = query_term(cache, root, path)?;
+    let pos = t.pos;
+
+    let (root, fields) = split_field_path(t);
+
+    if fields.is_empty() {
+        return Err(EvalError::Other(
+            String::from("explain: expected a field path, as in `config.field`"),
+            pos,
+        )
+        .into());
+    }
+
+    let mut field = Closure::atomic_closure(root);
+    for id in fields {
+        let pos = field.body.pos;
+        let (rt, env) = eval::eval_closure(field, &global_env.eval_env, cache, warnings, true)?;
+
+        field = match rt.as_ref() {
+            Term::Record(map, _) => match map.get(&id) {
+                Some(body) => Closure {
+                    body: body.clone(),
+                    env,
+                },
+                None => {
+                    return Err(EvalError::FieldMissing(
+                        id.label,
+                        String::from("explain"),
+                        rt.clone(),
+                        pos,
+                    )
+                    .into())
+                }
+            },
+            _ => {
+                return Err(EvalError::TypeError(
+                    String::from("Record"),
+                    String::from("explain"),
+                    pos,
+                    rt.clone(),
+                )
+                .into())
+            }
+        };
+    }
+
+    // The operands must be collected before the evaluation of the field, which would replace the
+    // merge expression by its result.
+    let operands = merge::operands(field.clone(), &global_env.eval_env)
+        .iter()
+        .map(|clos| describe_operand(cache.files(), clos, &global_env.eval_env))
+        .collect();
+    let value = eval::eval_meta_closure(field, &global_env.eval_env, cache, warnings)?.into();
+
+    Ok(Explanation { operands, value })
+}
+
+/// Gather the position, the priority, the contracts and the value of a merge operand.
+fn describe_operand(
+    files: &Files<String>,
+    clos: &Closure,
+    global_env: &eval::Environment,
+) -> MergeOperand {
+    // The share normal form transformation may have bound the content of a metavalue in a `let`.
+    let mut clos = clos.clone();
+    while let Term::Let(id, bound, body, _) = clos.body.as_ref() {
+        let (id, bound, body) = (id.clone(), bound.clone(), body.clone());
+        let local_env = clos.env.clone();
+        eval::env_add(&mut clos.env, id, bound, local_env);
+        clos.body = body;
+    }
+
+    let location = clos.body.pos.as_opt_ref().and_then(|span| {
+        let loc = files.location(span.src_id, span.start).ok()?;
+        Some(format!(
+            "{}:{}:{}",
+            files.name(span.src_id).to_string_lossy(),
+            loc.line.to_usize() + 1,
+            loc.column.to_usize() + 1
+        ))
+    });
+
+    // Follow variables and skip the contract checks generated from the annotations, and only show
+    // values which are already evaluated.
+    let repr = |t: &RichTerm| {
+        let mut clos = Closure {
+            body: t.clone(),
+            env: clos.env.clone(),
+        };
+
+        loop {
+            match clos.body.as_ref() {
+                Term::Var(id) => match clos.env.get(id).or_else(|| global_env.get(id)) {
+                    Some(thunk) => clos = thunk.get_owned(),
+                    None => return None,
+                },
+                Term::App(f, t) if matches!(f.as_ref(), Term::Op2(BinaryOp::Assume(), ..)) => {
+                    clos.body = t.clone()
+                }
+                _ => break,
+            }
+        }
+
+        match clos.body.as_ref() {
+            t if t.is_whnf() => Some(t.shallow_repr()),
+            t @ Term::RecRecord(..) => Some(t.shallow_repr()),
+            _ => None,
+        }
+    };
+
+    // We use the original user-written types stored in the labels, as in the `query` command.
+    match clos.body.as_ref() {
+        Term::MetaValue(meta) => MergeOperand {
+            location,
+            priority: meta.priority.clone(),
+            types: meta.types.as_ref().map(|ctr| ctr.label.types.to_string()),
+            contracts: meta
+                .contracts
+                .iter()
+                .map(|ctr| ctr.label.types.to_string())
+                .collect(),
+            value: meta.value.as_ref().and_then(repr),
+        },
+        _ => MergeOperand {
+            location,
+            priority: MergePriority::normal(),
+            types: None,
+            contracts: Vec::new(),
+            value: repr(&clos.body),
+        },
+    }
 }
 
 /// Pretty-print an error.
@@ -207,13 +681,67 @@ where
     };
 }
 
+/// Print warnings, followed by an error if any, on the standard error in the given format. The
+/// text format is the one of [`report`], while the JSON and SARIF formats are meant to be consumed
+/// by other tools (see [`crate::report`]).
+pub fn report_as(
+    cache: &mut Cache,
+    warnings: Vec<Warning>,
+    error: Option<Error>,
+    format: ErrorFormat,
+) {
+    if format == ErrorFormat::Text {
+        warnings
+            .into_iter()
+            .for_each(|warning| report(cache, warning));
+        if let Some(error) = error {
+            report(cache, error);
+        }
+        return;
+    }
+
+    let contracts_id = cache.id_of("<stdlib/contract.ncl>");
+    let mut diagnostics = Vec::new();
+    for warning in warnings {
+        let kind = warning.kind();
+        diagnostics.extend(
+            warning
+                .to_diagnostic(cache.files_mut(), contracts_id)
+                .into_iter()
+                .map(|d| (kind, d)),
+        );
+    }
+    if let Some(error) = error {
+        let kind = error.kind();
+        diagnostics.extend(
+            error
+                .to_diagnostic(cache.files_mut(), contracts_id)
+                .into_iter()
+                .map(|d| (kind, d)),
+        );
+    }
+    let files = cache.files();
+
+    match format {
+        ErrorFormat::Json => diagnostics
+            .iter()
+            .for_each(|(kind, d)| eprintln!("{}", machine_report::to_json(files, kind, d))),
+        ErrorFormat::Sarif if !diagnostics.is_empty() => {
+            eprintln!("{:#}", machine_report::to_sarif(files, &diagnostics))
+        }
+        ErrorFormat::Sarif | ErrorFormat::Text => (),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::error::EvalError;
+    use crate::number::Number;
     use crate::parser::{grammar, lexer};
     use crate::position::TermPos;
     use crate::term::SharedTerm;
+    use assert_matches::assert_matches;
     use codespan::Files;
     use std::io::Cursor;
 
@@ -239,6 +767,22 @@ mod tests {
         p.eval_full()
     }
 
+    #[test]
+    fn input_format() {
+        let mut p = Program::new_from_source(Cursor::new("{\"a\": [1, 2]}"), "<test>").unwrap();
+        p.set_input_format(InputFormat::Json).unwrap();
+        assert_eq!(
+            p.eval_full().map(|t| Term::from(t).deep_repr()),
+            Ok(String::from("{ a = [ 1, 2 ]}"))
+        );
+
+        let mut p = Program::new_from_source(Cursor::new("a: [1"), "<test>").unwrap();
+        assert_matches!(
+            p.set_input_format(InputFormat::Yaml),
+            Err(Error::ParseErrors(..))
+        );
+    }
+
     #[test]
     fn evaluation_full() {
         use crate::mk_record;
@@ -260,7 +804,7 @@ mod tests {
         // Records are parsed as RecRecords, so we need to build one by hand
         let expd = mk_record!((
             "foo",
-            mk_record!(("bar", mk_record!(("baz", Term::Num(2.0)))))
+            mk_record!(("bar", mk_record!(("baz", Term::Num(Number::from(2))))))
         ));
         assert_eq!(t.without_pos(), expd);
 
@@ -270,4 +814,114 @@ mod tests {
         // that this test fails.
         eval_full("{y = fun x => x, x = fun y => y}").unwrap();
     }
+
+    fn eval_full_overrides(s: &str, overrides: &[&str]) -> Result<RichTerm, Error> {
+        let mut p = Program::new_from_source(Cursor::new(s), "<test>").unwrap();
+        p.add_overrides(overrides.iter().map(|o| o.parse().unwrap()));
+        p.eval_full()
+    }
+
+    #[test]
+    fn parse_overrides() {
+        let parse = |s: &str| s.parse::<FieldOverride>();
+        let ovd = |path: &str, value: &str| FieldOverride {
+            path: String::from(path),
+            value: String::from(value),
+        };
+
+        assert_eq!(parse("a.b=1"), Ok(ovd("a.b", "1")));
+        assert_eq!(parse("a = \"x=y\""), Ok(ovd("a", " \"x=y\"")));
+        assert_eq!(parse("\"a=b\".c=true"), Ok(ovd("\"a=b\".c", "true")));
+        assert!(parse("a").is_err());
+        assert!(parse("=1").is_err());
+    }
+
+    #[test]
+    fn overrides() {
+        let source = "{
+            server = {
+                host = \"localhost\",
+                port | Num | default = 80,
+                url = \"%{host}:%{string.from_num port}\",
+            },
+            tags = [\"base\"],
+        }";
+
+        let t = eval_full_overrides(source, &["server.port=8080", "tags=[\"a\"]", "new=null"]);
+        let expd = eval_full(
+            "{
+                server = {host = \"localhost\", port = 8080, url = \"localhost:8080\"},
+                tags = [\"a\"],
+                new = null,
+            }",
+        );
+        assert_eq!(
+            serde_json::to_value(t.unwrap()).unwrap(),
+            serde_json::to_value(expd.unwrap()).unwrap()
+        );
+
+        // Overrides take precedence over forced values.
+        let t = eval_full_overrides("{a | force = 1, b = a}", &["a=2"]);
+        assert_eq!(
+            serde_json::to_value(t.unwrap()).unwrap(),
+            serde_json::to_value(eval_full("{a = 2, b = 2}").unwrap()).unwrap()
+        );
+        assert_matches!(
+            eval_full_overrides("{a = 1}", &["a=2", "a=3"]),
+            Err(Error::EvalError(EvalError::MergeIncompatibleArgs(..)))
+        );
+
+        // Overrides are still subject to the contracts of the program.
+        assert_matches!(
+            eval_full_overrides(source, &["server.port=\"80\""]),
+            Err(Error::EvalError(EvalError::BlameError(..)))
+        );
+        assert_matches!(
+            eval_full_overrides(source, &["tags @ [] = 1"]),
+            Err(Error::EvalError(EvalError::Other(..)))
+        );
+        assert_matches!(
+            eval_full_overrides(source, &["(server).port=1"]),
+            Err(Error::ParseErrors(..))
+        );
+    }
+
+    #[test]
+    fn deprecated_warnings() {
+        use crate::error::Warning;
+
+        let warnings = |s: &str| {
+            let mut p = Program::new_from_source(Cursor::new(s), "<test>").unwrap();
+            p.eval_full().unwrap();
+            p.warnings.take()
+        };
+
+        let record = "{a | deprecated \"use b\" | default = 1, b = 2}";
+
+        assert_matches!(
+            warnings(&format!("let r = {} in r.a", record)).as_slice(),
+            [Warning::DeprecatedField(Some(id), msg, pos, _)]
+                if id.label == "a" && msg == "use b" && pos.into_opt().is_some()
+        );
+        assert_matches!(
+            warnings(&format!(
+                "let r = {} in let k = \"a\" in r.\"%{{k}}\"",
+                record
+            ))
+            .as_slice(),
+            [Warning::DeprecatedField(Some(_), ..)]
+        );
+        // Defining a deprecated field by merging, even if the field is not used.
+        assert_matches!(
+            warnings(&format!("({} & {{a = 3}}).b", record)).as_slice(),
+            [Warning::DeprecatedField(Some(id), _, pos, _)]
+                if id.label == "a" && pos.into_opt().is_some()
+        );
+        // Exporting a deprecated field.
+        assert_matches!(
+            warnings(record).as_slice(),
+            [Warning::DeprecatedField(Some(id), ..)] if id.label == "a"
+        );
+        assert!(warnings(&format!("let r = {} in r.b", record)).is_empty());
+    }
 }
@@ -8,6 +8,7 @@ pub enum CommandType {
     Load,
     Typecheck,
     Query,
+    Explain,
     Print,
     Help,
     Exit,
@@ -20,6 +21,7 @@ pub enum Command {
     Load(OsString),
     Typecheck(String),
     Query(String),
+    Explain(String),
     Print(String),
     Help(Option<String>),
     Exit,
@@ -49,6 +51,7 @@ impl FromStr for CommandType {
             "load" | "l" => Ok(Load),
             "typecheck" | "tc" => Ok(Typecheck),
             "query" | "q" => Ok(Query),
+            "explain" | "ex" => Ok(Explain),
             "print" | "p" => Ok(Print),
             "help" | "?" | "h" => Ok(Help),
             "exit" | "e" => Ok(Exit),
@@ -66,6 +69,7 @@ impl CommandType {
             Load => vec![String::from("l")],
             Typecheck => vec![String::from("tc")],
             Query => vec![String::from("q")],
+            Explain => vec![String::from("ex")],
             Print => vec![String::from("p")],
             Help => vec![String::from("h"), String::from("?")],
             Exit => vec![String::from("e")],
@@ -81,6 +85,7 @@ impl std::fmt::Display for CommandType {
             Load => write!(f, "load"),
             Typecheck => write!(f, "typecheck"),
             Query => write!(f, "query"),
+            Explain => write!(f, "explain"),
             Print => write!(f, "print"),
             Help => write!(f, "help"),
             Exit => write!(f, "exit"),
@@ -123,6 +128,14 @@ impl FromStr for Command {
                 require_arg(cmd, &arg, None)?;
                 Ok(Command::Query(arg))
             }
+            CommandType::Explain => {
+                require_arg(
+                    cmd,
+                    &arg,
+                    Some("Please provide a field path, as in `config.field`"),
+                )?;
+                Ok(Command::Explain(arg))
+            }
             CommandType::Print => {
                 require_arg(cmd, &arg, None)?;
                 Ok(Command::Print(arg))
@@ -149,6 +162,7 @@ impl Command {
             Load(..) => CommandType::Load,
             Typecheck(..) => CommandType::Typecheck,
             Query(..) => CommandType::Query,
+            Explain(..) => CommandType::Explain,
             Print(..) => CommandType::Print,
             Help(..) => CommandType::Help,
             Exit => CommandType::Exit,
@@ -7,9 +7,12 @@
 //! jupyter-kernel (which is not exactly user-facing, but still manages input/output and
 //! formatting), etc.
 use crate::cache::{Cache, GlobalEnv};
-use crate::error::{Error, EvalError, IOError, ParseError, ParseErrors, ReplError};
+use crate::error::{
+    Error, EvalError, IOError, ParseError, ParseErrors, ReplError, Warning, Warnings,
+};
 use crate::identifier::Ident;
 use crate::parser::{grammar, lexer, ExtendedTerm};
+use crate::program::Explanation;
 use crate::term::{RichTerm, Term};
 use crate::transform::import_resolution;
 use crate::types::Types;
@@ -61,8 +64,13 @@ pub trait Repl {
     fn typecheck(&mut self, exp: &str) -> Result<Types, Error>;
     /// Query the metadata of an expression.
     fn query(&mut self, exp: &str) -> Result<Term, Error>;
+    /// Explain where the value of a field, given as an expression `e.path`, comes from.
+    fn explain(&mut self, exp: &str) -> Result<Explanation, Error>;
     /// Required for error reporting on the frontend.
     fn cache_mut(&mut self) -> &mut Cache;
+    /// Take the warnings emitted during evaluation since the last call, to be reported by the
+    /// frontend.
+    fn take_warnings(&mut self) -> Vec<Warning>;
 }
 
 /// Standard implementation of the REPL backend.
@@ -77,6 +85,8 @@ pub struct ReplImpl {
     /// The initial type environment, without the toplevel declarations made inside the REPL. Used
     /// to typecheck imports in a fresh environment.
     init_type_env: typecheck::Environment,
+    /// The warnings emitted during evaluation which have not been reported yet.
+    warnings: Warnings,
 }
 
 impl ReplImpl {
@@ -87,6 +97,7 @@ impl ReplImpl {
             parser: grammar::ExtendedTermParser::new(),
             env: GlobalEnv::new(),
             init_type_env: typecheck::Environment::new(),
+            warnings: Warnings::new(),
         }
     }
 
@@ -168,7 +179,10 @@ impl ReplImpl {
         match term {
             ExtendedTerm::RichTerm(t) => {
                 let t = prepare(self, None, t)?;
-                Ok(eval_function(t, &self.env.eval_env, &mut self.cache)?.into())
+                Ok(
+                    eval_function(t, &self.env.eval_env, &mut self.cache, &mut self.warnings)?
+                        .into(),
+                )
             }
             ExtendedTerm::ToplevelLet(id, t) => {
                 let t = prepare(self, Some(id.clone()), t)?;
@@ -244,12 +258,35 @@ impl Repl for ReplImpl {
         use crate::program;
 
         let file_id = self.cache.add_tmp("<repl-query>", String::from(exp));
-        program::query(&mut self.cache, file_id, &self.env, None)
+        program::query(
+            &mut self.cache,
+            &mut self.warnings,
+            file_id,
+            &self.env,
+            None,
+        )
+    }
+
+    fn explain(&mut self, exp: &str) -> Result<Explanation, Error> {
+        use crate::program;
+
+        let file_id = self.cache.add_tmp("<repl-explain>", String::from(exp));
+        program::explain(
+            &mut self.cache,
+            &mut self.warnings,
+            file_id,
+            &self.env,
+            None,
+        )
     }
 
     fn cache_mut(&mut self) -> &mut Cache {
         &mut self.cache
     }
+
+    fn take_warnings(&mut self) -> Vec<Warning> {
+        self.warnings.take()
+    }
 }
 
 /// Error occurring when initializing the REPL.
@@ -367,6 +404,15 @@ pub fn print_help(out: &mut impl Write, arg: Option<&str>) -> std::io::Result<()
                 print_aliases(out, c)?;
                 writeln!(out, "Print the metadata attached to an attribute")?;
             }
+            Ok(c @ CommandType::Explain) => {
+                writeln!(out, ":{} <expression>.<path>", c)?;
+                print_aliases(out, c)?;
+                write!(
+                    out,
+                    "Show every merge operand which contributed to the value of a field,"
+                )?;
+                writeln!(out, " with its position, priority and contracts")?;
+            }
             Ok(c @ CommandType::Load) => {
                 writeln!(out, ":{} <file>", c)?;
                 print_aliases(out, c)?;
@@ -396,12 +442,18 @@ pub fn print_help(out: &mut impl Write, arg: Option<&str>) -> std::io::Result<()
             }
             Err(UnknownCommandError {}) => {
                 writeln!(out, "Unknown command `{}`.", arg)?;
-                writeln!(out, "Available commands: ? help query load typecheck")?;
+                writeln!(
+                    out,
+                    "Available commands: ? help query explain load typecheck"
+                )?;
             }
         };
 
         Ok(())
     } else {
-        writeln!(out, "Available commands: help query load typecheck exit")
+        writeln!(
+            out,
+            "Available commands: help query explain load typecheck exit"
+        )
     }
 }
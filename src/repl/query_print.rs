@@ -1,5 +1,6 @@
 //! Rendering of the results of a metadata query.
 use crate::identifier::Ident;
+use crate::program::Explanation;
 use crate::term::{MergePriority, MetaValue, Term};
 use std::{io, io::Write};
 
@@ -218,6 +219,18 @@ fn write_query_result_<R: QueryPrinter>(
                 found = true;
             }
 
+            if selected_attrs.contract && meta.optional {
+                renderer.write_metadata(out, "optional", "true")?;
+                found = true;
+            }
+
+            if let Some(ref strategy) = meta.array_merge {
+                if selected_attrs.contract {
+                    renderer.write_metadata(out, "array_merge", &strategy.to_string())?;
+                    found = true;
+                }
+            }
+
             if selected_attrs.types && meta.types.is_some() {
                 renderer.write_metadata(
                     out,
@@ -237,7 +250,17 @@ fn write_query_result_<R: QueryPrinter>(
                     found = true;
                 }
                 MetaValue {
-                    priority: MergePriority::Normal,
+                    priority: MergePriority::Numeral(_),
+                    value: Some(t),
+                    ..
+                }
+                | MetaValue {
+                    priority: MergePriority::Top,
+                    value: Some(t),
+                    ..
+                }
+                | MetaValue {
+                    priority: MergePriority::Override,
                     value: Some(t),
                     ..
                 } if selected_attrs.value => {
@@ -247,6 +270,22 @@ fn write_query_result_<R: QueryPrinter>(
                 _ => (),
             }
 
+            if selected_attrs.value
+                && meta.value.is_some()
+                && meta.priority != MergePriority::Default
+                && meta.priority != MergePriority::normal()
+            {
+                renderer.write_metadata(out, "priority", &meta.priority.to_string())?;
+            }
+
+            match meta.deprecated {
+                Some(ref msg) if selected_attrs.doc => {
+                    renderer.write_metadata(out, "deprecated", msg)?;
+                    found = true;
+                }
+                _ => (),
+            }
+
             match meta.doc {
                 Some(ref s) if selected_attrs.doc => {
                     renderer.write_doc(out, s)?;
@@ -280,3 +319,52 @@ fn write_query_result_<R: QueryPrinter>(
 
     Ok(())
 }
+
+/// Print the result of an `explain` command (see [`crate::program::explain`]): the operands of the
+/// merge defining a field, followed by the final value of the field.
+///
+/// Wrapper around `write_explanation_` that selects an adapated query printer at compile time.
+pub fn write_explanation(out: &mut impl Write, explanation: &Explanation) -> io::Result<()> {
+    #[cfg(feature = "markdown")]
+    let renderer = MarkdownRenderer::new();
+
+    #[cfg(not(feature = "markdown"))]
+    let renderer = SimpleRenderer {};
+
+    write_explanation_(out, explanation, &renderer)
+}
+
+/// Print the result of an `explain` command.
+fn write_explanation_<R: QueryPrinter>(
+    out: &mut impl Write,
+    explanation: &Explanation,
+    renderer: &R,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "Merge operands, from left to right: {}",
+        explanation.operands.len()
+    )?;
+
+    for (i, operand) in explanation.operands.iter().enumerate() {
+        let location = operand.location.as_deref().unwrap_or("<unknown position>");
+        writeln!(out, "\n{}. {}", i + 1, location)?;
+
+        renderer.write_metadata(out, "priority", &operand.priority.to_string())?;
+
+        if let Some(ref types) = operand.types {
+            renderer.write_metadata(out, "type", types)?;
+        }
+
+        if !operand.contracts.is_empty() {
+            renderer.write_metadata(out, "contract", &operand.contracts.join(","))?;
+        }
+
+        if let Some(ref value) = operand.value {
+            renderer.write_metadata(out, "value", value)?;
+        }
+    }
+
+    writeln!(out, "\nResulting value:")?;
+    write_query_result_(out, &explanation.value, Attributes::default(), renderer)
+}
@@ -5,6 +5,7 @@ use super::command::Command;
 use super::*;
 
 use crate::program;
+use crate::report::ErrorFormat;
 use ansi_term::{Colour, Style};
 use rustyline::config::OutputStreamType;
 use rustyline::error::ReadlineError;
@@ -76,6 +77,9 @@ pub fn repl(histfile: PathBuf) -> Result<(), InitError> {
                         )
                         .unwrap();
                     }),
+                    Ok(Command::Explain(exp)) => repl.explain(&exp).map(|explanation| {
+                        query_print::write_explanation(&mut stdout, &explanation).unwrap();
+                    }),
                     Ok(Command::Print(exp)) => {
                         match repl.eval_full(&exp) {
                             Ok(EvalResult::Evaluated(rt)) => println!("{}\n", rt.as_ref().deep_repr()),
@@ -121,6 +125,10 @@ pub fn repl(histfile: PathBuf) -> Result<(), InitError> {
                 );
             }
         }
+
+        // Report the warnings emitted while evaluating the input, if any.
+        let warnings = repl.take_warnings();
+        program::report_as(repl.cache_mut(), warnings, None, ErrorFormat::Text);
     };
     let _ = editor.save_history(&histfile);
     result
@@ -62,6 +62,14 @@ pub fn input<R: Repl>(repl: &mut R, line: &str) -> Result<InputResult, InputErro
                     InputResult::Success(String::from_utf8(buffer.into_inner()).unwrap())
                 })
                 .map_err(InputError::from),
+            Ok(Command::Explain(exp)) => repl
+                .explain(&exp)
+                .map(|explanation| {
+                    let mut buffer = Cursor::new(Vec::<u8>::new());
+                    query_print::write_explanation(&mut buffer, &explanation).unwrap();
+                    InputResult::Success(String::from_utf8(buffer.into_inner()).unwrap())
+                })
+                .map_err(InputError::from),
             Ok(Command::Print(exp)) => repl
                 .eval_full(&exp)
                 .map(|res| match res {
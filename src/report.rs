@@ -0,0 +1,287 @@
+//! Machine-readable rendering of error and warning diagnostics.
+//!
+//! By default, errors are rendered for humans on the terminal by codespan (see
+//! [`crate::program::report`]). This module renders the same diagnostics as JSON, for tools which
+//! need to react to errors, or as a [SARIF](https://sarifweb.azurewebsites.net/) log, the format
+//! understood by code scanning services to annotate pull requests.
+use codespan::{FileId, Files};
+use codespan_reporting::diagnostic::{Diagnostic, Label, LabelStyle, Severity};
+use serde_json::{json, Value};
+use std::ffi::OsStr;
+use std::path::Path;
+use std::str::FromStr;
+use std::{env, fmt};
+
+/// The available formats to report errors.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ErrorFormat {
+    /// Human-readable text, with colors and source snippets.
+    #[default]
+    Text,
+    /// One JSON object per diagnostic and per line.
+    Json,
+    /// A SARIF 2.1.0 log.
+    Sarif,
+}
+
+impl fmt::Display for ErrorFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorFormat::Text => write!(f, "text"),
+            ErrorFormat::Json => write!(f, "json"),
+            ErrorFormat::Sarif => write!(f, "sarif"),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseErrorFormatError(String);
+
+impl fmt::Display for ParseErrorFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unsupported error format {}", self.0)
+    }
+}
+
+impl FromStr for ErrorFormat {
+    type Err = ParseErrorFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "text" => Ok(ErrorFormat::Text),
+            "json" => Ok(ErrorFormat::Json),
+            "sarif" => Ok(ErrorFormat::Sarif),
+            _ => Err(ParseErrorFormatError(String::from(s))),
+        }
+    }
+}
+
+fn severity(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Bug => "bug",
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+        Severity::Help => "help",
+    }
+}
+
+/// The position of a label: the file path, the byte range and the line and column range, which
+/// start at 1.
+fn location(files: &Files<String>, label: &Label<FileId>) -> Value {
+    let line_col = |byte: usize| {
+        files
+            .location(label.file_id, byte as u32)
+            .map(
+                |loc| json!({"line": loc.line.to_usize() + 1, "column": loc.column.to_usize() + 1}),
+            )
+            .unwrap_or(Value::Null)
+    };
+
+    json!({
+        "file": files.name(label.file_id).to_string_lossy(),
+        "range": {"start": label.range.start, "end": label.range.end},
+        "start": line_col(label.range.start),
+        "end": line_col(label.range.end),
+    })
+}
+
+/// The `file://` URI of a source file, as required by SARIF. Relative paths are resolved from the
+/// current directory.
+fn file_uri(name: &OsStr) -> String {
+    let path = Path::new(name);
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        env::current_dir()
+            .map(|dir| dir.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+    let path = path.to_string_lossy();
+
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'\\' if cfg!(windows) => uri.push('/'),
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    uri
+}
+
+/// Render a diagnostic as a JSON object. The kind is the category of the error (see
+/// [`crate::error::Error::kind`]).
+pub fn to_json(files: &Files<String>, kind: &str, diagnostic: &Diagnostic<FileId>) -> Value {
+    let labels: Vec<Value> = diagnostic
+        .labels
+        .iter()
+        .map(|label| {
+            let mut value = location(files, label);
+            value["style"] = json!(match label.style {
+                LabelStyle::Primary => "primary",
+                LabelStyle::Secondary => "secondary",
+            });
+            value["message"] = json!(label.message);
+            value
+        })
+        .collect();
+
+    json!({
+        "kind": kind,
+        "severity": severity(diagnostic.severity),
+        "code": diagnostic.code,
+        "message": diagnostic.message,
+        "labels": labels,
+        "notes": diagnostic.notes,
+    })
+}
+
+/// Render diagnostics as a SARIF log, with one result per diagnostic. Each diagnostic comes with
+/// the kind of the error or of the warning, which is used as the rule identifier.
+pub fn to_sarif(files: &Files<String>, diagnostics: &[(&str, Diagnostic<FileId>)]) -> Value {
+    let sarif_location = |label: &Label<FileId>| {
+        let loc = location(files, label);
+        let mut region = json!({
+            "byteOffset": label.range.start,
+            "byteLength": label.range.end - label.range.start,
+        });
+
+        if !loc["start"].is_null() && !loc["end"].is_null() {
+            region["startLine"] = loc["start"]["line"].clone();
+            region["startColumn"] = loc["start"]["column"].clone();
+            region["endLine"] = loc["end"]["line"].clone();
+            region["endColumn"] = loc["end"]["column"].clone();
+        }
+
+        json!({
+            "physicalLocation": {
+                "artifactLocation": {"uri": file_uri(files.name(label.file_id))},
+                "region": region,
+            },
+            "message": {"text": label.message},
+        })
+    };
+
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|(kind, diagnostic)| {
+            let level = match diagnostic.severity {
+                Severity::Bug | Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Note | Severity::Help => "note",
+            };
+            let (primary, secondary): (Vec<_>, Vec<_>) = diagnostic
+                .labels
+                .iter()
+                .partition(|label| label.style == LabelStyle::Primary);
+            let text = std::iter::once(diagnostic.message.clone())
+                .chain(diagnostic.notes.iter().cloned())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            json!({
+                "ruleId": kind,
+                "level": level,
+                "message": {"text": text},
+                "locations": primary.into_iter().map(sarif_location).collect::<Vec<_>>(),
+                "relatedLocations": secondary.into_iter().map(sarif_location).collect::<Vec<_>>(),
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "nickel",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://nickel-lang.org",
+                }
+            },
+            "results": results,
+        }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Cache;
+    use crate::error::{Error, ToDiagnostic};
+
+    fn parse_error(source: &str) -> (Cache, &'static str, Vec<Diagnostic<FileId>>) {
+        let mut cache = Cache::new();
+        let file_id = cache.add_string("test.ncl", String::from(source));
+        let error = match cache.parse_nocache(file_id) {
+            Err(err) => Error::from(err),
+            Ok((_, errs)) => Error::from(errs),
+        };
+        let kind = error.kind();
+        let diagnostics = error.to_diagnostic(cache.files_mut(), None);
+        (cache, kind, diagnostics)
+    }
+
+    #[test]
+    fn json() {
+        let (cache, kind, diagnostics) = parse_error("{\n  a = 1,\n  b = }");
+        assert_eq!(kind, "parse_error");
+
+        let json = to_json(cache.files(), kind, &diagnostics[0]);
+        assert_eq!(json["kind"], "parse_error");
+        assert_eq!(json["severity"], "error");
+
+        let label = &json["labels"][0];
+        assert_eq!(label["style"], "primary");
+        assert_eq!(label["file"], "test.ncl");
+        assert_eq!(label["range"]["start"], 17);
+        assert_eq!(label["start"], json!({"line": 3, "column": 7}));
+    }
+
+    #[test]
+    fn sarif() {
+        let (cache, kind, diagnostics) = parse_error("{\n  a = 1,\n  b = }");
+        let diagnostics: Vec<_> = diagnostics.into_iter().map(|d| (kind, d)).collect();
+        let sarif = to_sarif(cache.files(), &diagnostics);
+        assert_eq!(sarif["version"], "2.1.0");
+
+        let result = &sarif["runs"][0]["results"][0];
+        assert_eq!(result["ruleId"], "parse_error");
+        assert_eq!(result["level"], "error");
+
+        let location = &result["locations"][0]["physicalLocation"];
+        let uri = location["artifactLocation"]["uri"].as_str().unwrap();
+        assert!(uri.starts_with("file:///") && uri.ends_with("/test.ncl"));
+        assert_eq!(location["region"]["startLine"], 3);
+        assert_eq!(location["region"]["startColumn"], 7);
+        assert_eq!(location["region"]["byteOffset"], 17);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_uris() {
+        assert_eq!(file_uri(OsStr::new("/tmp/a.ncl")), "file:///tmp/a.ncl");
+        assert_eq!(
+            file_uri(OsStr::new("/tmp/my config/%a.ncl")),
+            "file:///tmp/my%20config/%25a.ncl"
+        );
+
+        let uri = file_uri(OsStr::new("test.ncl"));
+        assert!(uri.starts_with("file:///") && uri.ends_with("/test.ncl"));
+    }
+
+    #[test]
+    fn error_format_from_str() {
+        assert_eq!("json".parse(), Ok(ErrorFormat::Json));
+        assert_eq!("SARIF".parse(), Ok(ErrorFormat::Sarif));
+        assert!("xml".parse::<ErrorFormat>().is_err());
+    }
+}
@@ -0,0 +1,1367 @@
+//! Conversion between [JSON Schema](https://json-schema.org/) documents and Nickel contracts.
+//!
+//! # Generation of JSON Schemas
+//!
+//! The schema of a term is computed from its types and contracts, and from the metadata of record
+//! fields:
+//!
+//! - Types are translated structurally: `Num`, `Bool` and `Str` become the corresponding JSON
+//!   types, `Array T` becomes an array whose items are described by the schema of `T`, `{_: T}`
+//!   becomes an object whose properties are described by the schema of `T`, record types become
+//!   closed objects whose fields are all required, and enum types become an enumeration of
+//!   strings. Enum variants carrying data become objects with a single property, following their
+//!   serialization.
+//! - Record contracts, that is records whose fields carry metadata, become objects. A field
+//!   without a value is required unless it is `optional`, the documentation of a field becomes
+//!   its `description`, and default values which are constants become `default`s. Record
+//!   contracts are closed unless they end with an ellipsis `..`.
+//! - A few contracts of the standard library, such as `num.Nat` or `string.NonEmpty`, have a
+//!   dedicated translation.
+//! - Other contracts (custom contracts written as functions, function types, type variables,
+//!   etc.) can't be translated and are degraded to the schema `{}`, which accepts anything. When
+//!   the corresponding field has documentation, it is still included as the `description`.
+//!
+//! Generating a schema requires evaluating contracts and record fields, albeit not fully: the
+//! term is only evaluated until metadata are found. The fields whose value can't be evaluated on
+//! their own, such as a field defined from another field that doesn't have a value yet, are
+//! degraded to `{}` as well.
+//!
+//! # Conversion of JSON Schemas
+//!
+//! A JSON Schema can conversely be converted to the source of a Nickel contract (see
+//! [`to_contract`]), which is what happens when importing a file ending with `.schema.json`. The
+//! following keywords are supported:
+//!
+//! - `type`: the JSON types translate to `Str`, `Num`, `num.Int`, `Bool`, `Array` and record
+//!   contracts. A list of types is checked by a custom contract which only looks at the type of
+//!   the value, save for a single type together with `null`, which is fully checked.
+//! - `properties`, `required` and `additionalProperties`: an object becomes a record contract.
+//!   Required properties become fields without a value, properties with a `default` become
+//!   fields with a default value, and other properties become `optional` fields. The record is
+//!   closed if `additionalProperties` is `false`, and becomes a dictionary type `{_: T}` if
+//!   `additionalProperties` is a schema and there are no properties.
+//! - `items`: an array becomes `Array T`.
+//! - `enum`: the value is checked to be one of the listed values.
+//! - `$ref`: references to another part of the same document, such as `#/definitions/Foo`, are
+//!   bound to a name in a recursive record of definitions.
+//! - `description` becomes the documentation of the corresponding field.
+//!
+//! Other keywords are ignored.
+use crate::cache::ImportResolver;
+use crate::error::{EvalError, Warnings};
+use crate::eval::lazy::Thunk;
+use crate::eval::{self, Closure, Environment};
+use crate::identifier::Ident;
+use crate::number;
+use crate::parser::lexer::{Lexer, NormalToken, Token};
+use crate::term::{MergePriority, MetaValue, RecordAttrs, RichTerm, Term, UnaryOp};
+use crate::types::{AbsType, Types};
+use indexmap::IndexMap;
+use serde_json::value::RawValue;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+
+/// The JSON Schema dialect of the generated documents.
+pub const SCHEMA_DIALECT: &str = "http://json-schema.org/draft-07/schema#";
+
+/// Generate the JSON Schema of a term.
+pub fn generate<R>(
+    t: RichTerm,
+    global_env: &Environment,
+    resolver: &mut R,
+    warnings: &mut Warnings,
+) -> Result<Value, EvalError>
+where
+    R: ImportResolver,
+{
+    let mut generator = Generator {
+        global_env,
+        resolver,
+        warnings,
+    };
+
+    let FieldSchema { mut schema, .. } = generator.field(Closure::atomic_closure(t))?;
+
+    if let Value::Object(ref mut map) = schema {
+        map.insert(String::from("$schema"), Value::from(SCHEMA_DIALECT));
+    }
+
+    Ok(schema)
+}
+
+/// The schema of a record field, together with the information of whether it is required.
+struct FieldSchema {
+    schema: Value,
+    required: bool,
+}
+
+impl FieldSchema {
+    fn optional(schema: Value) -> Self {
+        FieldSchema {
+            schema,
+            required: false,
+        }
+    }
+}
+
+/// The schema which accepts any value.
+fn any() -> Value {
+    json!({})
+}
+
+fn is_any(schema: &Value) -> bool {
+    matches!(schema, Value::Object(map) if map.is_empty())
+}
+
+/// Combine the schemas of several contracts applied to the same value.
+fn all_of(schemas: Vec<Value>) -> Value {
+    let mut schemas: Vec<Value> = schemas.into_iter().filter(|s| !is_any(s)).collect();
+
+    match schemas.len() {
+        0 => any(),
+        1 => schemas.pop().unwrap(),
+        _ => json!({ "allOf": schemas }),
+    }
+}
+
+/// Convert a term to a JSON value if it is a constant.
+fn constant(t: &Term) -> Option<Value> {
+    match t {
+        Term::Null | Term::Bool(_) | Term::Num(_) | Term::Str(_) => serde_json::to_value(t).ok(),
+        Term::Enum(id) => Some(Value::from(id.to_string())),
+        _ => None,
+    }
+}
+
+/// The modules of the standard library which define contracts translated by [`stdlib_contract`].
+const STDLIB_MODULES: &[&str] = &["array", "num", "string"];
+
+/// The translation of the contracts of the standard library which have a direct counterpart in
+/// JSON Schema. The module is recognized by the value it is bound to in `env`, and not by its
+/// name, such that a local variable shadowing a module isn't mistaken for it.
+fn stdlib_contract(t: &RichTerm, env: &Environment, global_env: &Environment) -> Option<Value> {
+    let (module, contract) = match t.as_ref() {
+        Term::Op1(UnaryOp::StaticAccess(contract), module) => match module.as_ref() {
+            Term::Var(id) => {
+                let thunk = env.get(id).or_else(|| global_env.get(id))?;
+                let module = STDLIB_MODULES.iter().find(|module| {
+                    match global_env.get(&Ident::from(**module)) {
+                        Some(stdlib) => Thunk::ptr_eq(&thunk, &stdlib),
+                        None => false,
+                    }
+                })?;
+                (*module, contract.label.as_str())
+            }
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    let schema = match (module, contract) {
+        ("num", "Int") => json!({ "type": "integer" }),
+        ("num", "Nat") => json!({ "type": "integer", "minimum": 0 }),
+        ("num", "PosNat") => json!({ "type": "integer", "minimum": 1 }),
+        ("num", "NonZero") => json!({ "type": "number", "not": { "const": 0 } }),
+        ("string", "BoolLiteral") => json!({ "type": "string", "enum": ["true", "false"] }),
+        ("string", "NumLiteral") => json!({
+            "type": "string",
+            "pattern": r"^[+-]?(\d+(\.\d*)?(e[+-]?\d+)?|\.\d+(e[+-]?\d+)?)$",
+        }),
+        ("string", "CharLiteral") => json!({ "type": "string", "minLength": 1, "maxLength": 1 }),
+        ("string", "Ident") => json!({ "type": "string", "pattern": "_?[a-zA-Z][_a-zA-Z0-9]*" }),
+        ("string", "NonEmpty") => json!({ "type": "string", "minLength": 1 }),
+        ("array", "NonEmpty") => json!({ "type": "array", "minItems": 1 }),
+        _ => return None,
+    };
+
+    Some(schema)
+}
+
+/// Insert an attribute in a schema. Does nothing if the schema is not an object.
+fn insert(schema: &mut Value, key: &str, value: Value) {
+    if let Value::Object(ref mut map) = schema {
+        map.insert(String::from(key), value);
+    }
+}
+
+/// The state of the schema generation, which needs to evaluate terms.
+struct Generator<'a, R> {
+    global_env: &'a Environment,
+    resolver: &'a mut R,
+    warnings: &'a mut Warnings,
+}
+
+impl<'a, R: ImportResolver> Generator<'a, R> {
+    /// Evaluate a closure to a weak head normal form, stopping at metavalues.
+    fn eval_weak(&mut self, clos: Closure) -> Result<(RichTerm, Environment), EvalError> {
+        eval::eval_closure(clos, self.global_env, self.resolver, self.warnings, false)
+    }
+
+    /// Compute the schema of a term, seen as the value of a record field.
+    fn field(&mut self, clos: Closure) -> Result<FieldSchema, EvalError> {
+        let (rt, env) = self.eval_weak(clos)?;
+
+        match rt.as_ref() {
+            Term::MetaValue(meta) => self.meta_value(meta, &env),
+            Term::Record(fields, attrs) => self.record(fields, attrs, &env),
+            t => Ok(FieldSchema::optional(
+                constant(t)
+                    .map(|value| json!({ "const": value }))
+                    .unwrap_or_else(any),
+            )),
+        }
+    }
+
+    /// Same as [`Generator::field`], but degrade to `{}` if the evaluation fails.
+    fn field_or_any(&mut self, clos: Closure) -> FieldSchema {
+        self.field(clos)
+            .unwrap_or_else(|_| FieldSchema::optional(any()))
+    }
+
+    fn meta_value(
+        &mut self,
+        meta: &MetaValue,
+        env: &Environment,
+    ) -> Result<FieldSchema, EvalError> {
+        let schemas = meta
+            .types
+            .iter()
+            .chain(meta.contracts.iter())
+            .map(|ctr| self.types(&ctr.types, env))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut schema = all_of(schemas);
+
+        let required = match (&meta.value, &meta.priority) {
+            (None, _) => !meta.optional,
+            (Some(t), MergePriority::Default) => {
+                let clos = Closure {
+                    body: t.clone(),
+                    env: env.clone(),
+                };
+
+                if let Ok((value, _)) =
+                    eval::eval_closure(clos, self.global_env, self.resolver, self.warnings, true)
+                {
+                    if let Some(value) = constant(value.as_ref()) {
+                        insert(&mut schema, "default", value);
+                    }
+                }
+
+                false
+            }
+            (Some(t), MergePriority::Numeral(_))
+            | (Some(t), MergePriority::Top)
+            | (Some(t), MergePriority::Override) => {
+                let field = self.field_or_any(Closure {
+                    body: t.clone(),
+                    env: env.clone(),
+                });
+
+                if is_any(&schema) {
+                    schema = field.schema;
+                }
+
+                field.required
+            }
+        };
+
+        if let Some(doc) = &meta.doc {
+            insert(&mut schema, "description", Value::from(doc.clone()));
+        }
+
+        Ok(FieldSchema { schema, required })
+    }
+
+    fn record(
+        &mut self,
+        fields: &IndexMap<Ident, RichTerm>,
+        attrs: &RecordAttrs,
+        env: &Environment,
+    ) -> Result<FieldSchema, EvalError> {
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+
+        for (id, t) in fields {
+            if attrs.not_exported.contains(id) {
+                continue;
+            }
+
+            let field = self.field_or_any(Closure {
+                body: t.clone(),
+                env: env.clone(),
+            });
+
+            if field.required {
+                required.push(Value::from(id.to_string()));
+            }
+
+            properties.insert(id.to_string(), field.schema);
+        }
+
+        let mut schema = json!({ "type": "object", "properties": properties });
+
+        if !attrs.open {
+            insert(&mut schema, "additionalProperties", Value::from(false));
+        }
+
+        let has_required = !required.is_empty();
+
+        if has_required {
+            insert(&mut schema, "required", Value::from(required));
+        }
+
+        Ok(FieldSchema {
+            schema,
+            required: has_required,
+        })
+    }
+
+    /// Compute the schema of a type. `env` is the environment of the user-defined contracts
+    /// occurring in the type.
+    fn types(&mut self, ty: &Types, env: &Environment) -> Result<Value, EvalError> {
+        let schema = match &ty.0 {
+            AbsType::Num() => json!({ "type": "number" }),
+            AbsType::Bool() => json!({ "type": "boolean" }),
+            AbsType::Str() => json!({ "type": "string" }),
+            AbsType::Forall(_, ty) => self.types(ty, env)?,
+            AbsType::Flat(t) => self.flat(t, env)?,
+            AbsType::Array(ty) => {
+                let items = self.types(ty, env)?;
+
+                if is_any(&items) {
+                    json!({ "type": "array" })
+                } else {
+                    json!({ "type": "array", "items": items })
+                }
+            }
+            AbsType::DynRecord(ty) => {
+                let properties = self.types(ty, env)?;
+
+                if is_any(&properties) {
+                    json!({ "type": "object" })
+                } else {
+                    json!({ "type": "object", "additionalProperties": properties })
+                }
+            }
+            AbsType::StaticRecord(row) => {
+                let mut properties = Map::new();
+                let mut row = row.as_ref();
+
+                while let AbsType::RowExtend(id, ty, tail) = &row.0 {
+                    let field = match ty {
+                        Some(ty) => self.types(ty, env)?,
+                        None => any(),
+                    };
+                    properties.insert(id.to_string(), field);
+                    row = tail;
+                }
+
+                let required: Vec<_> = properties.keys().cloned().map(Value::from).collect();
+                let mut schema = json!({ "type": "object", "properties": properties });
+
+                if !required.is_empty() {
+                    insert(&mut schema, "required", Value::from(required));
+                }
+
+                // An open row (ending with a type variable) allows additional fields.
+                if let AbsType::RowEmpty() = row.0 {
+                    insert(&mut schema, "additionalProperties", Value::from(false));
+                }
+
+                schema
+            }
+            AbsType::Enum(row) => {
+                let mut tags = Vec::new();
+                let mut variants = Vec::new();
+                let mut row = row.as_ref();
+
+                while let AbsType::RowExtend(id, ty, tail) = &row.0 {
+                    match ty {
+                        // A variant carrying data is serialized as `{"Tag": data}`.
+                        Some(ty) => variants.push(json!({
+                            "type": "object",
+                            "properties": { id.to_string(): self.types(ty, env)? },
+                            "required": [id.to_string()],
+                            "additionalProperties": false,
+                        })),
+                        None => tags.push(id.to_string()),
+                    }
+                    row = tail;
+                }
+
+                tags.sort();
+
+                match row.0 {
+                    AbsType::RowEmpty() if variants.is_empty() => {
+                        json!({ "type": "string", "enum": tags })
+                    }
+                    AbsType::RowEmpty() => {
+                        if !tags.is_empty() {
+                            variants.insert(0, json!({ "type": "string", "enum": tags }));
+                        }
+
+                        if variants.len() == 1 {
+                            variants.pop().unwrap()
+                        } else {
+                            json!({ "oneOf": variants })
+                        }
+                    }
+                    _ if variants.is_empty() => json!({ "type": "string" }),
+                    _ => any(),
+                }
+            }
+            AbsType::Dyn()
+            | AbsType::Sym()
+            | AbsType::Var(_)
+            | AbsType::Arrow(..)
+            | AbsType::RowEmpty()
+            | AbsType::RowExtend(..) => any(),
+        };
+
+        Ok(schema)
+    }
+
+    /// Compute the schema of a user-defined contract.
+    fn flat(&mut self, t: &RichTerm, env: &Environment) -> Result<Value, EvalError> {
+        if let Some(schema) = stdlib_contract(t, env, self.global_env) {
+            return Ok(schema);
+        }
+
+        // Follow aliases such as `let Port = num.Nat in`, which are otherwise evaluated to a
+        // function before we get a chance to recognize a contract of the standard library.
+        if let Term::Var(id) = t.as_ref() {
+            if let Some(thunk) = env.get(id).or_else(|| self.global_env.get(id)) {
+                let Closure { body, env } = thunk.get_owned();
+
+                if let Term::Var(_) | Term::Op1(UnaryOp::StaticAccess(_), _) = body.as_ref() {
+                    return self.flat(&body, &env);
+                }
+            }
+        }
+
+        let clos = Closure {
+            body: t.clone(),
+            env: env.clone(),
+        };
+        let (rt, env) =
+            eval::eval_closure(clos, self.global_env, self.resolver, self.warnings, true)?;
+
+        match rt.as_ref() {
+            Term::Record(fields, attrs) => Ok(self.record(fields, attrs, &env)?.schema),
+            _ => Ok(any()),
+        }
+    }
+}
+
+/// The name of the contract corresponding to the root of a converted JSON Schema, when it needs to
+/// be bound because the schema contains references.
+const ROOT_NAME: &str = "Schema";
+
+/// The names used by the generated contracts, which can't be used to name definitions: the
+/// modules of the standard library and the parameters of custom contracts.
+const RESERVED: &[&str] = &["array", "builtin", "contract", "label", "num", "value"];
+
+/// Convert a JSON Schema to the source of a Nickel contract. See the module documentation for the
+/// supported keywords. The source is the text the schema was parsed from, which is used to print
+/// the numbers as they are written, instead of their approximation as floats.
+pub fn to_contract(schema: &Value, source: &str) -> Result<String, String> {
+    let mut numbers = HashMap::new();
+    let raw: &RawValue = serde_json::from_str(source).map_err(|err| err.to_string())?;
+    number_texts(schema, raw, &mut numbers)?;
+
+    let mut converter = Converter {
+        root: schema,
+        names: HashMap::new(),
+        taken: HashSet::new(),
+        pending: Vec::new(),
+        root_referenced: false,
+        numbers,
+    };
+
+    converter.taken.insert(String::from(ROOT_NAME));
+    let contract = converter.contract(schema, 2)?;
+
+    if converter.names.is_empty() && !converter.root_referenced {
+        // The contract was generated to be a field of the record of definitions.
+        return Ok(format!("{}\n", contract.replace("\n  ", "\n")));
+    }
+
+    let mut definitions = vec![(String::from(ROOT_NAME), contract)];
+    while !converter.pending.is_empty() {
+        let pointer = converter.pending.remove(0);
+        let subschema = converter.root.pointer(&pointer).unwrap();
+        let contract = converter.contract(subschema, 2)?;
+        definitions.push((converter.names[&pointer].clone(), contract));
+    }
+
+    let mut result = String::from("let definitions = {\n");
+    for (name, contract) in definitions {
+        result.push_str(&format!("  {} = {},\n", name, contract));
+    }
+    result.push_str(&format!("}} in\ndefinitions.{}\n", ROOT_NAME));
+
+    Ok(result)
+}
+
+/// Record the text of the numbers of a JSON document, indexed by the address of their node in the
+/// parsed document.
+fn number_texts(
+    value: &Value,
+    raw: &RawValue,
+    numbers: &mut HashMap<*const Value, String>,
+) -> Result<(), String> {
+    let parse_err = |err: serde_json::Error| err.to_string();
+
+    match value {
+        Value::Number(_) => {
+            numbers.insert(value, String::from(raw.get()));
+        }
+        Value::Array(values) => {
+            let raws: Vec<&RawValue> = serde_json::from_str(raw.get()).map_err(parse_err)?;
+            for (value, raw) in values.iter().zip(raws) {
+                number_texts(value, raw, numbers)?;
+            }
+        }
+        Value::Object(map) => {
+            let raws: HashMap<String, &RawValue> =
+                serde_json::from_str(raw.get()).map_err(parse_err)?;
+            for (name, value) in map.iter() {
+                if let Some(raw) = raws.get(name) {
+                    number_texts(value, raw, numbers)?;
+                }
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// Determine if a string is lexed to a single identifier when it follows the given prefix. The
+/// prefix matters because some keywords, such as `priority`, are only reserved after `|`.
+fn is_ident_after(prefix: &str, s: &str) -> bool {
+    let source = format!("{}{}", prefix, s);
+    let mut tokens = Lexer::new(&source)
+        .skip_while(|token| matches!(token, Ok((_, _, end)) if *end <= prefix.len()));
+
+    matches!(
+        (tokens.next(), tokens.next()),
+        (Some(Ok((start, Token::Normal(NormalToken::Identifier(_)), end))), None)
+            if start == prefix.len() && end == source.len()
+    )
+}
+
+/// Determine if a string is a valid identifier, by checking that the lexer turns it into a single
+/// identifier.
+fn is_ident(s: &str) -> bool {
+    is_ident_after("", s)
+}
+
+/// Print a string as a Nickel string literal.
+pub(crate) fn string_literal(s: &str) -> String {
+    let mut result = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '%' => result.push_str("\\%"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if c.is_ascii_control() => result.push_str(&format!("\\x{:02x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+/// Print a field name, which is quoted if it isn't a valid identifier.
+pub(crate) fn field_name(name: &str) -> String {
+    if is_ident(name) {
+        String::from(name)
+    } else {
+        string_literal(name)
+    }
+}
+
+/// Print the text of a JSON number as a Nickel number literal. Nickel number literals don't
+/// support the exponent notation, so the decimal point is moved instead, which keeps the number
+/// exact.
+fn number_literal(text: &str) -> Result<String, String> {
+    // Check the size of the number first, as the exponent gives the number of digits to print.
+    number::parse(text).map_err(|_| format!("unsupported number `{}`", text))?;
+
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(text) => ("-", text),
+        None => ("", text),
+    };
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(i) => (&text[..i], text[i + 1..].parse::<i64>().unwrap_or_default()),
+        None => (text, 0),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+    let digits = format!("{}{}", int, frac);
+    let point = int.len() as i64 + exponent;
+    let (int, frac) = if point <= 0 {
+        (
+            String::new(),
+            format!("{}{}", "0".repeat((-point) as usize), digits),
+        )
+    } else if point as usize >= digits.len() {
+        let zeros = "0".repeat(point as usize - digits.len());
+        (format!("{}{}", digits, zeros), String::new())
+    } else {
+        let (int, frac) = digits.split_at(point as usize);
+        (String::from(int), String::from(frac))
+    };
+
+    let int = match int.trim_start_matches('0') {
+        "" => "0",
+        int => int,
+    };
+    match frac.trim_end_matches('0') {
+        "" => Ok(format!("{}{}", sign, int)),
+        frac => Ok(format!("{}{}.{}", sign, int, frac)),
+    }
+}
+
+/// Parenthesize a contract if it can't be used as the argument of a type constructor.
+fn atom(contract: String) -> String {
+    if contract.starts_with('{') || !contract.contains(' ') {
+        contract
+    } else {
+        format!("({})", contract)
+    }
+}
+
+/// Parenthesize a contract if it can't be used as an annotation, which is the case of functions
+/// whose body would otherwise extend over the following annotations.
+fn annotation(contract: String) -> String {
+    if contract.starts_with("fun ") {
+        format!("({})", contract)
+    } else {
+        contract
+    }
+}
+
+/// A custom contract checking a predicate on the value, written as a condition on `value`.
+fn predicate(condition: &str, message: &str) -> String {
+    format!(
+        "fun label value => if {} then value else contract.blame_with {} label",
+        condition,
+        string_literal(message)
+    )
+}
+
+/// The condition, as used by [`predicate`], checking that a value has a given JSON type.
+fn type_condition(ty: &str) -> Result<&'static str, String> {
+    match ty {
+        "string" => Ok("builtin.is_str value"),
+        "number" => Ok("builtin.is_num value"),
+        "integer" => Ok("num.is_int value"),
+        "boolean" => Ok("builtin.is_bool value"),
+        "null" => Ok("value == null"),
+        "array" => Ok("builtin.is_array value"),
+        "object" => Ok("builtin.is_record value"),
+        _ => Err(format!("unknown type `{}`", ty)),
+    }
+}
+
+/// The state of the conversion of a JSON Schema, which names the referenced subschemas.
+struct Converter<'a> {
+    root: &'a Value,
+    /// The names of the referenced subschemas, indexed by JSON pointer.
+    names: HashMap<String, String>,
+    /// The names already given.
+    taken: HashSet<String>,
+    /// The pointers of the referenced subschemas which have not been converted yet.
+    pending: Vec<String>,
+    /// If the root schema is referenced, in which case it needs to be bound to a name as well.
+    root_referenced: bool,
+    /// The text of the numbers of the document, as written in the source. See [`number_texts`].
+    numbers: HashMap<*const Value, String>,
+}
+
+impl<'a> Converter<'a> {
+    /// Print a JSON value of the document as a Nickel literal.
+    fn literal(&self, value: &Value) -> Result<String, String> {
+        match value {
+            Value::Null => Ok(String::from("null")),
+            Value::Bool(b) => Ok(b.to_string()),
+            Value::Number(n) => match self.numbers.get(&(value as *const Value)) {
+                Some(text) => number_literal(text),
+                None => number_literal(&n.to_string()),
+            },
+            Value::String(s) => Ok(string_literal(s)),
+            Value::Array(values) => Ok(format!("[{}]", self.literals(values.iter())?)),
+            Value::Object(map) if map.is_empty() => Ok(String::from("{}")),
+            Value::Object(map) => {
+                let fields = map
+                    .iter()
+                    .map(|(name, value)| {
+                        Ok(format!("{} = {}", field_name(name), self.literal(value)?))
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(format!("{{ {} }}", fields.join(", ")))
+            }
+        }
+    }
+
+    /// Print JSON values of the document as a comma separated list of Nickel literals.
+    fn literals<'b>(&self, values: impl Iterator<Item = &'b Value>) -> Result<String, String> {
+        Ok(values
+            .map(|value| self.literal(value))
+            .collect::<Result<Vec<_>, _>>()?
+            .join(", "))
+    }
+
+    /// Return the name of the subschema designated by a reference, and schedule its conversion if
+    /// it wasn't referenced before.
+    fn reference(&mut self, reference: &str) -> Result<String, String> {
+        let pointer = reference.strip_prefix('#').ok_or_else(|| {
+            format!(
+                "unsupported reference `{}`: only references within the same document are supported",
+                reference
+            )
+        })?;
+
+        if pointer.is_empty() {
+            self.root_referenced = true;
+            return Ok(String::from(ROOT_NAME));
+        }
+
+        if let Some(name) = self.names.get(pointer) {
+            return Ok(name.clone());
+        }
+
+        if self.root.pointer(pointer).is_none() {
+            return Err(format!("unresolved reference `{}`", reference));
+        }
+
+        let last = pointer.rsplit('/').next().unwrap_or_default();
+        let name = self.fresh_name(&last.replace("~1", "/").replace("~0", "~"));
+        self.names.insert(String::from(pointer), name.clone());
+        self.pending.push(String::from(pointer));
+
+        Ok(name)
+    }
+
+    /// Turn the name of a definition into a valid identifier which doesn't clash with the names
+    /// already given or with the names used by the generated code.
+    fn fresh_name(&mut self, name: &str) -> String {
+        let mut base: String = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '_' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+
+        if !is_ident(&base) {
+            base = format!("{}_{}", ROOT_NAME, base);
+        }
+
+        let mut name = base.clone();
+        let mut counter = 1;
+        // Definitions are referenced in contract annotations, right after `|`.
+        while !is_ident_after("| ", &name)
+            || RESERVED.contains(&name.as_str())
+            || self.taken.contains(&name)
+        {
+            counter += 1;
+            name = format!("{}_{}", base, counter);
+        }
+
+        self.taken.insert(name.clone());
+        name
+    }
+
+    /// Convert a schema to a contract. The indentation is the level of the fields of the record
+    /// contracts.
+    fn contract(&mut self, schema: &Value, indent: usize) -> Result<String, String> {
+        let map = match schema {
+            Value::Bool(true) => return Ok(String::from("Dyn")),
+            Value::Bool(false) => return Ok(predicate("false", "no value is allowed")),
+            Value::Object(map) => map,
+            _ => return Err(format!("invalid schema `{}`", schema)),
+        };
+
+        if let Some(reference) = map.get("$ref") {
+            let reference = reference
+                .as_str()
+                .ok_or_else(|| format!("invalid reference `{}`", reference))?;
+            return self.reference(reference);
+        }
+
+        if let Some(values) = map.get("enum") {
+            let values = values
+                .as_array()
+                .ok_or_else(|| format!("invalid enum `{}`", values))?;
+            let list = self.literals(values.iter())?;
+            // The numbers are printed as in the contract, as their JSON serialization is a float.
+            let message = format!(
+                "must be one of {}",
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Number(_) => self.literal(value),
+                        _ => Ok(value.to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(", ")
+            );
+            return Ok(predicate(&format!("array.elem value [{}]", list), &message));
+        }
+
+        let types: Vec<&str> = match map.get("type") {
+            Some(Value::String(ty)) => vec![ty.as_str()],
+            Some(Value::Array(types)) => types
+                .iter()
+                .map(|ty| ty.as_str().ok_or_else(|| format!("invalid type `{}`", ty)))
+                .collect::<Result<_, _>>()?,
+            Some(ty) => return Err(format!("invalid type `{}`", ty)),
+            None if map.contains_key("properties")
+                || map.contains_key("required")
+                || map.contains_key("additionalProperties") =>
+            {
+                vec!["object"]
+            }
+            None if map.contains_key("items") => vec!["array"],
+            None => return Ok(String::from("Dyn")),
+        };
+
+        match types.as_slice() {
+            [ty] => self.typed(ty, map, indent),
+            [ty, "null"] | ["null", ty] => Ok(format!(
+                "fun label value => if value == null then value else contract.apply {} label value",
+                atom(self.typed(ty, map, indent)?)
+            )),
+            _ => {
+                let condition = types
+                    .iter()
+                    .map(|ty| type_condition(ty))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(" || ");
+                let message = format!("must be of type {}", types.join(" or "));
+                Ok(predicate(&condition, &message))
+            }
+        }
+    }
+
+    /// Convert a schema of a given JSON type.
+    fn typed(
+        &mut self,
+        ty: &str,
+        map: &Map<String, Value>,
+        indent: usize,
+    ) -> Result<String, String> {
+        match ty {
+            "string" => Ok(String::from("Str")),
+            "number" => Ok(String::from("Num")),
+            "integer" => Ok(String::from("num.Int")),
+            "boolean" => Ok(String::from("Bool")),
+            "null" => Ok(predicate("value == null", "must be null")),
+            // Tuples, that is `items` being a list of schemas, aren't supported.
+            "array" => match map.get("items") {
+                Some(items @ Value::Object(_)) | Some(items @ Value::Bool(_)) => {
+                    Ok(format!("Array {}", atom(self.contract(items, indent)?)))
+                }
+                _ => Ok(String::from("Array Dyn")),
+            },
+            "object" => self.object(map, indent),
+            _ => Err(format!("unknown type `{}`", ty)),
+        }
+    }
+
+    /// Convert the schema of an object to a record contract.
+    fn object(&mut self, map: &Map<String, Value>, indent: usize) -> Result<String, String> {
+        let empty = Map::new();
+        let properties = match map.get("properties") {
+            Some(Value::Object(properties)) => properties,
+            Some(properties) => return Err(format!("invalid properties `{}`", properties)),
+            None => &empty,
+        };
+        let required: Vec<&str> = match map.get("required") {
+            Some(Value::Array(required)) => required.iter().filter_map(Value::as_str).collect(),
+            Some(required) => return Err(format!("invalid required properties `{}`", required)),
+            None => Vec::new(),
+        };
+        let additional = map.get("additionalProperties");
+
+        if properties.is_empty() && required.is_empty() {
+            return match additional {
+                Some(Value::Bool(false)) => Ok(String::from("{}")),
+                Some(schema) if schema != &Value::Bool(true) => {
+                    Ok(format!("{{_: {}}}", self.contract(schema, indent)?))
+                }
+                _ => Ok(String::from("{_: Dyn}")),
+            };
+        }
+
+        let padding = "  ".repeat(indent);
+        let open = additional != Some(&Value::Bool(false));
+        let mut fields = Vec::new();
+
+        for (name, property) in properties.iter() {
+            let default = property.get("default");
+            let is_required = required.contains(&name.as_str());
+
+            let mut field = field_name(name);
+            let doc = property.get("description").and_then(Value::as_str);
+            if let Some(doc) = doc {
+                field.push_str(&format!(" | doc {}", string_literal(doc)));
+            }
+
+            if !is_required && default.is_none() {
+                field.push_str(" | optional");
+            }
+
+            let contract = self.contract(property, indent + 1)?;
+            if contract != "Dyn" || (doc.is_none() && default.is_none()) {
+                field.push_str(&format!(" | {}", annotation(contract)));
+            }
+
+            if let Some(default) = default {
+                field.push_str(&format!(" | default = {}", self.literal(default)?));
+            }
+
+            fields.push(field);
+        }
+
+        for name in required
+            .iter()
+            .filter(|name| !properties.contains_key(**name))
+        {
+            fields.push(format!("{} | Dyn", field_name(name)));
+        }
+
+        let mut result = String::from("{\n");
+        for field in fields {
+            result.push_str(&format!("{}{},\n", padding, field));
+        }
+
+        // The ellipsis can't be followed by a comma.
+        if open {
+            result.push_str(&format!("{}..\n", padding));
+        }
+        result.push_str(&"  ".repeat(indent - 1));
+        result.push('}');
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::to_contract;
+    use crate::program::Program;
+    use serde_json::{json, Value};
+    use std::io::Cursor;
+
+    fn schema(s: &str) -> Value {
+        let mut p = Program::new_from_source(Cursor::new(s), "<test>").unwrap();
+        let mut schema = p.schema().unwrap();
+        schema.as_object_mut().unwrap().remove("$schema");
+        schema
+    }
+
+    /// Convert a schema whose source is its serialization.
+    fn convert(schema: &Value) -> Result<String, String> {
+        to_contract(schema, &schema.to_string())
+    }
+
+    #[test]
+    fn types() {
+        assert_eq!(
+            schema("{ a | Num, b | Str, c | Bool, d | Array Str, e | {_: Num}, f | Dyn }"),
+            json!({
+                "type": "object",
+                "properties": {
+                    "a": { "type": "number" },
+                    "b": { "type": "string" },
+                    "c": { "type": "boolean" },
+                    "d": { "type": "array", "items": { "type": "string" } },
+                    "e": { "type": "object", "additionalProperties": { "type": "number" } },
+                    "f": {},
+                },
+                "required": ["a", "b", "c", "d", "e", "f"],
+                "additionalProperties": false,
+            })
+        );
+
+        assert_eq!(
+            schema("{ a : {foo : Num, bar : [| x, y |]} | default = {foo = 1, bar = `x} }"),
+            json!({
+                "type": "object",
+                "properties": {
+                    "a": {
+                        "type": "object",
+                        "properties": {
+                            "foo": { "type": "number" },
+                            "bar": { "type": "string", "enum": ["x", "y"] },
+                        },
+                        "required": ["foo", "bar"],
+                        "additionalProperties": false,
+                    },
+                },
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn enum_variants() {
+        assert_eq!(
+            schema("{ a | [| Port Num, Plain, Insecure |] }"),
+            json!({
+                "type": "object",
+                "properties": {
+                    "a": {
+                        "oneOf": [
+                            { "type": "string", "enum": ["Insecure", "Plain"] },
+                            {
+                                "type": "object",
+                                "properties": { "Port": { "type": "number" } },
+                                "required": ["Port"],
+                                "additionalProperties": false,
+                            },
+                        ]
+                    },
+                },
+                "required": ["a"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn record_contracts() {
+        let s = r#"
+            let Port = num.Nat in
+            let Custom = fun label value => value in
+            let Server = { host | Str | doc "The host", port | Port | default = 80, .. } in
+            {
+                server | Server,
+                name | doc "The name" | default = "app",
+                version = "1",
+                check | Custom,
+            }
+        "#;
+
+        assert_eq!(
+            schema(s),
+            json!({
+                "type": "object",
+                "properties": {
+                    "server": {
+                        "type": "object",
+                        "properties": {
+                            "host": { "type": "string", "description": "The host" },
+                            "port": { "type": "integer", "minimum": 0, "default": 80 },
+                        },
+                        "required": ["host"],
+                    },
+                    "name": { "description": "The name", "default": "app" },
+                    "version": { "const": "1" },
+                    "check": {},
+                },
+                "required": ["server", "check"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn shadowed_stdlib_modules() {
+        let s = r#"
+            let num = { Nat = fun label value => value } in
+            { a | num.Nat, b | array.NonEmpty }
+        "#;
+
+        assert_eq!(
+            schema(s),
+            json!({
+                "type": "object",
+                "properties": {
+                    "a": {},
+                    "b": { "type": "array", "minItems": 1 },
+                },
+                "required": ["a", "b"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn nested_records() {
+        assert_eq!(
+            schema("{ a = { b | Num, c = { d | Num | default = 1 } } }"),
+            json!({
+                "type": "object",
+                "properties": {
+                    "a": {
+                        "type": "object",
+                        "properties": {
+                            "b": { "type": "number" },
+                            "c": {
+                                "type": "object",
+                                "properties": {
+                                    "d": { "type": "number", "default": 1 },
+                                },
+                                "additionalProperties": false,
+                            },
+                        },
+                        "required": ["b"],
+                        "additionalProperties": false,
+                    },
+                },
+                "required": ["a"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn optional_fields() {
+        assert_eq!(
+            schema("{ a | optional | Num, b | Num }"),
+            json!({
+                "type": "object",
+                "properties": {
+                    "a": { "type": "number" },
+                    "b": { "type": "number" },
+                },
+                "required": ["b"],
+                "additionalProperties": false,
+            })
+        );
+    }
+
+    #[test]
+    fn convert_types() {
+        assert_eq!(convert(&json!({ "type": "string" })).unwrap(), "Str\n");
+        assert_eq!(
+            convert(&json!({ "type": "array", "items": { "type": "integer" } })).unwrap(),
+            "Array num.Int\n"
+        );
+        assert_eq!(
+            convert(&json!({ "type": "array", "items": { "type": "array" } })).unwrap(),
+            "Array (Array Dyn)\n"
+        );
+        assert_eq!(
+            convert(&json!({ "additionalProperties": { "type": "number" } })).unwrap(),
+            "{_: Num}\n"
+        );
+        assert_eq!(
+            convert(&json!({ "type": ["string", "number"] })).unwrap(),
+            "fun label value => if builtin.is_str value || builtin.is_num value then value \
+            else contract.blame_with \"must be of type string or number\" label\n"
+        );
+        assert_eq!(
+            convert(&json!({ "enum": ["a%{", 1] })).unwrap(),
+            "fun label value => if array.elem value [\"a\\%{\", 1] then value \
+            else contract.blame_with \"must be one of \\\"a\\%{\\\", 1\" label\n"
+        );
+    }
+
+    #[test]
+    fn convert_objects() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "description": "The name" },
+                "port": { "type": "integer", "default": 80 },
+                "tags": { "type": "array", "items": { "type": "string" } },
+                "the host": {
+                    "type": ["object", "null"],
+                    "properties": { "ip": { "type": "string" } },
+                    "required": ["ip"],
+                    "additionalProperties": false,
+                },
+            },
+            "required": ["name", "the host"],
+        });
+
+        assert_eq!(
+            convert(&schema).unwrap(),
+            r#"{
+  name | doc "The name" | Str,
+  port | num.Int | default = 80,
+  tags | optional | Array Str,
+  "the host" | (fun label value => if value == null then value else contract.apply {
+    ip | Str,
+  } label value),
+  ..
+}
+"#
+        );
+    }
+
+    #[test]
+    fn convert_references() {
+        let schema = json!({
+            "properties": {
+                "tree": { "$ref": "#/definitions/Tree" },
+                "contract": { "$ref": "#/$defs/contract" },
+            },
+            "required": ["tree", "contract"],
+            "additionalProperties": false,
+            "definitions": {
+                "Tree": {
+                    "properties": {
+                        "children": {
+                            "type": "array",
+                            "items": { "$ref": "#/definitions/Tree" },
+                            "default": [],
+                        },
+                        "parent": { "$ref": "#" },
+                    },
+                },
+            },
+            "$defs": { "contract": { "type": "boolean" } },
+        });
+
+        assert_eq!(
+            convert(&schema).unwrap(),
+            r#"let definitions = {
+  Schema = {
+    tree | Tree,
+    contract | contract_2,
+  },
+  Tree = {
+    children | Array Tree | default = [],
+    parent | optional | Schema,
+    ..
+  },
+  contract_2 = Bool,
+} in
+definitions.Schema
+"#
+        );
+
+        assert!(convert(&json!({ "$ref": "#/definitions/Missing" })).is_err());
+        assert!(convert(&json!({ "$ref": "other.json#/definitions/Foo" })).is_err());
+    }
+
+    #[test]
+    fn convert_numbers() {
+        let source = r#"{
+            "enum": [0.1, 1e3, -2.5E-2, 12345678901234567890123, 0.30000000000000000004, 1e-3]
+        }"#;
+        let schema: Value = serde_json::from_str(source).unwrap();
+        assert_eq!(
+            to_contract(&schema, source).unwrap(),
+            "fun label value => if array.elem value [0.1, 1000, -0.025, \
+            12345678901234567890123, 0.30000000000000000004, 0.001] then value \
+            else contract.blame_with \"must be one of 0.1, 1000, -0.025, \
+            12345678901234567890123, 0.30000000000000000004, 0.001\" label\n"
+        );
+
+        let source = r#"{ "properties": { "ratio": { "default": 0.30000000000000000004 } } }"#;
+        let schema: Value = serde_json::from_str(source).unwrap();
+        assert_eq!(
+            to_contract(&schema, source).unwrap(),
+            "{\n  ratio | default = 0.30000000000000000004,\n  ..\n}\n"
+        );
+    }
+
+    #[test]
+    fn keyword_fields() {
+        let check = |name: &str, field: &str| {
+            let schema = json!({
+                "properties": { name: { "type": "integer" } },
+                "required": [name],
+            });
+            let contract = convert(&schema).unwrap();
+            assert_eq!(contract, format!("{{\n  {} | num.Int,\n  ..\n}}\n", field));
+
+            let source = format!(
+                "builtin.deep_seq ({{ \"{}\" = 1 }} | {}) true",
+                name, contract
+            );
+            assert!(Program::new_from_source(Cursor::new(source), "<test>")
+                .unwrap()
+                .eval_full()
+                .is_ok());
+        };
+
+        check("if", "\"if\"");
+        check("let", "\"let\"");
+        check("merge", "\"merge\"");
+        check("default", "\"default\"");
+        check("_", "\"_\"");
+        check("not_exported", "not_exported");
+        check("optional", "optional");
+        check("deprecated", "deprecated");
+        check("priority", "priority");
+        check("force", "force");
+        check("array_merge", "array_merge");
+        check("match", "match");
+        check("as", "as");
+    }
+
+    #[test]
+    fn keyword_definitions() {
+        let schema = json!({
+            "properties": { "a": { "$ref": "#/definitions/optional" } },
+            "required": ["a"],
+            "definitions": { "optional": { "type": "boolean" } },
+        });
+
+        assert_eq!(
+            convert(&schema).unwrap(),
+            r#"let definitions = {
+  Schema = {
+    a | optional_2,
+    ..
+  },
+  optional_2 = Bool,
+} in
+definitions.Schema
+"#
+        );
+
+        let source = format!("({{ a = true }} | ({})).a", convert(&schema).unwrap());
+        assert!(Program::new_from_source(Cursor::new(source), "<test>")
+            .unwrap()
+            .eval_full()
+            .is_ok());
+    }
+
+    #[test]
+    fn converted_contracts() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "kind": { "enum": ["a", "b"], "default": "a" },
+                "size": { "type": "integer" },
+                "tag": { "type": "string" },
+            },
+            "required": ["name", "size"],
+            "additionalProperties": false,
+        });
+        let contract = convert(&schema).unwrap();
+        let eval = |value: &str| {
+            let source = format!("builtin.deep_seq ({} | {}) true", value, contract);
+            Program::new_from_source(Cursor::new(source), "<test>")
+                .unwrap()
+                .eval_full()
+        };
+
+        assert!(eval("{ name = \"x\", size = 1 }").is_ok());
+        assert!(eval("{ name = \"x\", size = 1, kind = \"b\" }").is_ok());
+        assert!(eval("{ name = \"x\", size = 1.5 }").is_err());
+        assert!(eval("{ name = \"x\", size = 1, kind = \"c\" }").is_err());
+        assert!(eval("{ name = 1, size = 1 }").is_err());
+        assert!(eval("{ name = \"x\", size = 1, tag = \"t\" }").is_ok());
+        assert!(eval("{ name = \"x\", size = 1, tag = 1 }").is_err());
+    }
+}
@@ -1,12 +1,15 @@
 //! Serialization of an evaluated program to various data format.
 use crate::error::SerializationError;
 use crate::identifier::Ident;
-use crate::term::{MetaValue, RecordAttrs, RichTerm, Term};
-use serde::de::{Deserialize, Deserializer};
+use crate::number::{self, Number};
+use crate::term::{MetaValue, RecordAttrs, RichTerm, Term, TraverseOrder};
+use indexmap::IndexMap;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
 use serde::ser::{Error, Serialize, SerializeMap, Serializer};
-use std::collections::HashMap;
+use std::convert::{Infallible, TryFrom};
 use std::fmt;
 use std::io;
+use std::path::{Component, PathBuf};
 use std::str::FromStr;
 
 /// Available export formats.
@@ -59,23 +62,79 @@ impl FromStr for ExportFormat {
     }
 }
 
-/// Implicitly convert float to integers when possible to avoid trailing zeros. Note this this
-/// only work if the float is in range of either `i64` or `f64`. It seems there's no easy general
-/// solution (working for both YAML, TOML, and JSON) to choose the way floating point values are
-/// formatted.
-pub fn serialize_num<S>(n: &f64, serializer: S) -> Result<S::Ok, S::Error>
+/// Serializer for numbers. Integers are serialized exactly as long as they fit in a 128 bits
+/// integer (most formats don't support larger ones, and TOML is restricted to 64 bits). Other
+/// numbers are serialized as the nearest floating-point value. Numbers which can't be serialized
+/// this way, such as larger integers, are rejected instead of being approximated.
+pub fn serialize_num<S>(n: &Number, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    if n.fract() == 0.0 {
-        if *n < 0.0 && *n >= (i64::MIN as f64) && *n <= (i64::MAX as f64) {
-            return (*n as i64).serialize(serializer);
-        } else if *n >= 0.0 && *n <= (u64::MAX as f64) {
-            return (*n as u64).serialize(serializer);
+    if let Ok(n) = i64::try_from(n) {
+        n.serialize(serializer)
+    } else if let Ok(n) = u64::try_from(n) {
+        n.serialize(serializer)
+    } else if let Ok(n) = i128::try_from(n) {
+        n.serialize(serializer)
+    } else if let Ok(n) = u128::try_from(n) {
+        n.serialize(serializer)
+    } else if n.is_integer() {
+        Err(Error::custom("integer too large to be serialized exactly"))
+    } else {
+        number::to_finite_f64(n)
+            .ok_or_else(|| Error::custom("number out of the range of floats"))?
+            .serialize(serializer)
+    }
+}
+
+/// Determine if a number can be serialized to a given format by [`serialize_num`].
+fn is_serializable_num(format: ExportFormat, n: &Number) -> bool {
+    if format == ExportFormat::Toml && n.is_integer() {
+        i64::try_from(n).is_ok()
+    } else if n.is_integer() {
+        i128::try_from(n).is_ok() || u128::try_from(n).is_ok()
+    } else {
+        number::to_finite_f64(n).is_some()
+    }
+}
+
+/// Deserializer for numbers. Floats are converted to the simplest rational rounding to them, such
+/// that `0.1` is deserialized as exactly `1/10`.
+pub fn deserialize_num<'de, D>(deserializer: D) -> Result<Number, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct NumVisitor;
+
+    impl<'de> Visitor<'de> for NumVisitor {
+        type Value = Number;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a number")
+        }
+
+        fn visit_i64<E: de::Error>(self, n: i64) -> Result<Number, E> {
+            Ok(Number::from(n))
+        }
+
+        fn visit_u64<E: de::Error>(self, n: u64) -> Result<Number, E> {
+            Ok(Number::from(n))
+        }
+
+        fn visit_i128<E: de::Error>(self, n: i128) -> Result<Number, E> {
+            Ok(Number::from(n))
+        }
+
+        fn visit_u128<E: de::Error>(self, n: u128) -> Result<Number, E> {
+            Ok(Number::from(n))
+        }
+
+        fn visit_f64<E: de::Error>(self, n: f64) -> Result<Number, E> {
+            number::from_f64(n).ok_or_else(|| E::custom(format!("unsupported number {}", n)))
         }
     }
 
-    n.serialize(serializer)
+    deserializer.deserialize_any(NumVisitor)
 }
 
 /// Serializer for metavalues.
@@ -91,35 +150,59 @@ where
     }
 }
 
-/// Serializer for a record. Serialize fields in alphabetical order to get a deterministic output
-/// (by default, `HashMap`'s randomness implies a randomized order of fields in the output).
+/// Determine if a field of an evaluated record is serialized. Fields annotated with
+/// `not_exported` and optional fields without a value are skipped.
+fn is_exported(id: &Ident, attrs: &RecordAttrs) -> bool {
+    !attrs.not_exported.contains(id) && !attrs.empty_optional.contains(id)
+}
+
+/// Serializer for a record. Fields are serialized in the order of their definition. Use
+/// [`sort_keys`] beforehand to get them in alphabetical order instead. Fields which are not
+/// exported are skipped.
 pub fn serialize_record<S>(
-    map: &HashMap<Ident, RichTerm>,
-    _attrs: &RecordAttrs,
+    map: &IndexMap<Ident, RichTerm>,
+    attrs: &RecordAttrs,
     serializer: S,
 ) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
 {
-    let mut entries: Vec<(_, _)> = map.iter().collect();
-    entries.sort_by(|(k1, _), (k2, _)| k1.cmp(k2));
+    let exported: Vec<_> = map
+        .iter()
+        .filter(|(id, _)| is_exported(id, attrs))
+        .collect();
 
-    let mut map_ser = serializer.serialize_map(Some(entries.len()))?;
-    for (id, t) in entries.iter() {
+    let mut map_ser = serializer.serialize_map(Some(exported.len()))?;
+    for (id, t) in exported {
         map_ser.serialize_entry(&id.to_string(), &t)?
     }
 
     map_ser.end()
 }
 
+/// Serializer for an enum variant carrying data. The variant `` `Tag data `` is serialized as an
+/// object with a single field `Tag` whose value is `data`, that is `{"Tag": data}` in JSON.
+pub fn serialize_enum_variant<S>(
+    tag: &Ident,
+    arg: &RichTerm,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut map_ser = serializer.serialize_map(Some(1))?;
+    map_ser.serialize_entry(&tag.to_string(), arg)?;
+    map_ser.end()
+}
+
 /// Deserialize for a record. Required to set the record attributes to default.
 pub fn deserialize_record<'de, D>(
     deserializer: D,
-) -> Result<(HashMap<Ident, RichTerm>, RecordAttrs), D::Error>
+) -> Result<(IndexMap<Ident, RichTerm>, RecordAttrs), D::Error>
 where
     D: Deserializer<'de>,
 {
-    let map: HashMap<Ident, RichTerm> = HashMap::deserialize(deserializer)?;
+    let map: IndexMap<Ident, RichTerm> = IndexMap::deserialize(deserializer)?;
     Ok((map, Default::default()))
 }
 
@@ -144,7 +227,8 @@ impl<'de> Deserialize<'de> for RichTerm {
 }
 
 /// Check that a term is serializable. Serializable terms are booleans, numbers, strings, enum,
-/// arrays of serializable terms or records of serializable terms.
+/// enum variants carrying serializable terms, arrays of serializable terms or records of
+/// serializable terms.
 pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), SerializationError> {
     use crate::term;
     use Term::*;
@@ -160,9 +244,15 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), SerializationE
             // TOML doesn't support null values
             Null if format == ExportFormat::Json || format == ExportFormat::Yaml => Ok(()),
             Null => Err(SerializationError::UnsupportedNull(format, t.clone())),
+            Num(n) if !is_serializable_num(format, n) => {
+                Err(SerializationError::UnsupportedNumber(format, t.clone()))
+            }
             Bool(_) | Num(_) | Str(_) | Enum(_) => Ok(()),
-            Record(map, _) => {
-                map.iter().try_for_each(|(_, t)| validate(format, t))?;
+            EnumVariant(_, t) => validate(format, t),
+            Record(map, attrs) => {
+                map.iter()
+                    .filter(|(id, _)| is_exported(id, attrs))
+                    .try_for_each(|(_, t)| validate(format, t))?;
                 Ok(())
             }
             Array(vec) => {
@@ -178,6 +268,34 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), SerializationE
     }
 }
 
+/// Sort the fields of all the records of a term in alphabetical order. By default, fields are
+/// serialized in the order of their definition.
+pub fn sort_keys(rt: RichTerm) -> RichTerm {
+    let result: Result<RichTerm, Infallible> = rt.traverse(
+        &mut |rt: RichTerm, _: &mut ()| {
+            if let Term::Record(..) = rt.as_ref() {
+                let RichTerm { term, pos } = rt;
+                match term.into_owned() {
+                    Term::Record(mut map, attrs) => {
+                        map.sort_keys();
+                        Ok(RichTerm::new(Term::Record(map, attrs), pos))
+                    }
+                    _ => unreachable!(),
+                }
+            } else {
+                Ok(rt)
+            }
+        },
+        &mut (),
+        TraverseOrder::BottomUp,
+    );
+
+    match result {
+        Ok(rt) => rt,
+        Err(never) => match never {},
+    }
+}
+
 pub fn to_writer<W>(
     mut writer: W,
     format: ExportFormat,
@@ -208,6 +326,101 @@ where
     }
 }
 
+/// A file of a multi-file export.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportFile {
+    /// The path of the file, relative to the output directory.
+    pub path: PathBuf,
+    pub format: ExportFormat,
+    pub content: RichTerm,
+}
+
+/// Extract the files of a multi-file export from a fully evaluated term.
+///
+/// The term must be a record mapping relative paths to file descriptions. A description is either
+/// a string, which is exported as is, or a record `{format = `Yaml, content = ..}`, where the
+/// format is one of the export formats and the content is serialized in this format. Paths must
+/// stay inside the output directory.
+pub fn export_files(rt: &RichTerm) -> Result<Vec<ExportFile>, SerializationError> {
+    // Look through the metavalues left by the evaluation, such as fields with a default value.
+    fn value(rt: &RichTerm) -> &RichTerm {
+        match rt.as_ref() {
+            Term::MetaValue(MetaValue { value: Some(t), .. }) => value(t),
+            _ => rt,
+        }
+    }
+
+    let map = match value(rt).as_ref() {
+        Term::Record(map, _) => map,
+        _ => {
+            return Err(SerializationError::InvalidExportFile(
+                String::new(),
+                String::from("a multi-file export must be a record mapping paths to files"),
+                rt.clone(),
+            ))
+        }
+    };
+
+    map.iter()
+        .map(|(id, entry)| {
+            let invalid = |reason: &str| {
+                SerializationError::InvalidExportFile(
+                    id.label.clone(),
+                    String::from(reason),
+                    entry.clone(),
+                )
+            };
+
+            let path = PathBuf::from(&id.label);
+            let is_inside = path
+                .components()
+                .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+            if path.as_os_str().is_empty() || !is_inside {
+                return Err(invalid(
+                    "the path must be relative, and must not contain `..`",
+                ));
+            }
+
+            match value(entry).as_ref() {
+                Term::Str(_) => Ok(ExportFile {
+                    path,
+                    format: ExportFormat::Raw,
+                    content: value(entry).clone(),
+                }),
+                Term::Record(fields, _) => {
+                    let field = |name: &str| fields.get(&Ident::from(name)).map(value);
+                    let format = match field("format").map(RichTerm::as_ref) {
+                        Some(Term::Enum(tag)) => tag.label.parse().map_err(|_| {
+                            invalid("unknown format, expected one of `Raw, `Json, `Yaml or `Toml")
+                        })?,
+                        _ => {
+                            return Err(invalid(
+                                "expected a field `format` with an enum tag, such as `Yaml",
+                            ))
+                        }
+                    };
+                    let content = field("content")
+                        .ok_or_else(|| invalid("missing field `content`"))?
+                        .clone();
+
+                    if fields.len() > 2 {
+                        return Err(invalid("expected only the fields `format` and `content`"));
+                    }
+
+                    Ok(ExportFile {
+                        path,
+                        format,
+                        content,
+                    })
+                }
+                _ => Err(invalid(
+                    "expected a string or a record `{format = .., content = ..}`",
+                )),
+            }
+        })
+        .collect()
+}
+
 pub fn to_string(format: ExportFormat, rt: &RichTerm) -> Result<String, SerializationError> {
     match format {
         ExportFormat::Json => serde_json::to_string_pretty(&rt)
@@ -253,9 +466,9 @@ mod tests {
     macro_rules! assert_json_eq {
         ( $term:expr, $result:expr ) => {
             assert_eq!(
-                serde_json::to_string(&mk_program($term).and_then(|mut p| p.eval_full()).unwrap())
+                serde_json::to_value(&mk_program($term).and_then(|mut p| p.eval_full()).unwrap())
                     .unwrap(),
-                serde_json::to_string(&$result).unwrap()
+                serde_json::to_value(&$result).unwrap()
             )
         };
     }
@@ -298,7 +511,8 @@ mod tests {
                 $crate::eval::eval(
                     mk_term::op2(BinaryOp::Eq(), from_json, evaluated.clone()),
                     &Environment::new(),
-                    &mut $crate::cache::resolvers::DummyResolver {}
+                    &mut $crate::cache::resolvers::DummyResolver {},
+                    &mut $crate::error::Warnings::new()
                 )
                 .map(Term::from),
                 Ok(Term::Bool(true))
@@ -307,7 +521,8 @@ mod tests {
                 $crate::eval::eval(
                     mk_term::op2(BinaryOp::Eq(), from_yaml, evaluated.clone()),
                     &Environment::new(),
-                    &mut $crate::cache::resolvers::DummyResolver {}
+                    &mut $crate::cache::resolvers::DummyResolver {},
+                    &mut $crate::error::Warnings::new()
                 )
                 .map(Term::from),
                 Ok(Term::Bool(true))
@@ -316,7 +531,8 @@ mod tests {
                 $crate::eval::eval(
                     mk_term::op2(BinaryOp::Eq(), from_toml, evaluated),
                     &Environment::new(),
-                    &mut $crate::cache::resolvers::DummyResolver {}
+                    &mut $crate::cache::resolvers::DummyResolver {},
+                    &mut $crate::error::Warnings::new()
                 )
                 .map(Term::from),
                 Ok(Term::Bool(true))
@@ -369,6 +585,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn enum_variants() {
+        assert_json_eq!("`Port 8080", json!({"Port": 8080}));
+        assert_json_eq!(
+            "[`Plain, `Tls {cert = \"a\" ++ \"b\"}, `Some (`Some 1)]",
+            json!(["Plain", {"Tls": {"cert": "ab"}}, {"Some": {"Some": 1}}])
+        );
+    }
+
     #[test]
     fn meta_values() {
         assert_json_eq!(
@@ -387,6 +612,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn not_exported() {
+        assert_json_eq!(
+            "{helper | not_exported = fun x => x + 1, base | not_exported = 1, port = helper base}",
+            json!({"port": 2})
+        );
+
+        // A field referring to a hidden field is still exported
+        assert_json_eq!("{a | not_exported = 1, b = a}", json!({"b": 1}));
+
+        assert_json_eq!(
+            "{a = {b | default = 1} & {b | not_exported}, c = {d.e | not_exported = 1, d.f = 2}}",
+            json!({"a": {}, "c": {"d": {"f": 2}}})
+        );
+
+        assert_pass_validation!(
+            ExportFormat::Json,
+            "{a | not_exported = fun x => x, b = 1}",
+            true
+        );
+    }
+
+    #[test]
+    fn optional_fields() {
+        assert_json_eq!("{a | optional | Num, b = 1}", json!({"b": 1}));
+        assert_json_eq!("{a | optional | Num = 1, b = a}", json!({"a": 1, "b": 1}));
+        assert_json_eq!(
+            "{a | optional | Num} & {a | optional} & {b = 1}",
+            json!({"b": 1})
+        );
+        assert_json_eq!("{a | optional | Num} & {a = 2}", json!({"a": 2}));
+    }
+
     #[test]
     fn prevalidation() {
         assert_pass_validation!(ExportFormat::Json, "{a = 1, b = {c = fun x => x}}", false);
@@ -406,4 +664,117 @@ mod tests {
         assert_involutory!("{val = [\"a\", 3, []]}");
         assert_involutory!("{a.foo.bar = \"2\", b = false, c = [{d = \"e\"}, {d = \"f\"}]}");
     }
+
+    #[test]
+    fn field_order() {
+        let source = "{zeta = 1, alpha = {c = 1, b = 2} & {a = 3}, mid = [{y = 1, x = 2}]}";
+        let evaluated = mk_program(source).and_then(|mut p| p.eval_full()).unwrap();
+
+        assert_eq!(
+            to_string(ExportFormat::Json, &evaluated)
+                .unwrap()
+                .split_whitespace()
+                .collect::<String>(),
+            r#"{"zeta":1,"alpha":{"c":1,"b":2,"a":3},"mid":[{"y":1,"x":2}]}"#
+        );
+        assert_eq!(
+            to_string(ExportFormat::Yaml, &evaluated).unwrap(),
+            "---\nzeta: 1\nalpha:\n  c: 1\n  b: 2\n  a: 3\nmid:\n  - y: 1\n    x: 2\n"
+        );
+        // TOML requires plain values to come first, and the serializer puts arrays of tables
+        // before tables. The order is preserved otherwise.
+        assert_eq!(
+            to_string(ExportFormat::Toml, &evaluated).unwrap(),
+            "zeta = 1\n\n[[mid]]\ny = 1\nx = 2\n\n[alpha]\nc = 1\nb = 2\na = 3\n"
+        );
+
+        let sorted = sort_keys(evaluated);
+        assert_eq!(
+            to_string(ExportFormat::Json, &sorted)
+                .unwrap()
+                .split_whitespace()
+                .collect::<String>(),
+            r#"{"alpha":{"a":3,"b":2,"c":1},"mid":[{"x":2,"y":1}],"zeta":1}"#
+        );
+    }
+
+    #[test]
+    fn exact_numbers() {
+        let evaluated = mk_program(
+            "{big = 12345678901234567890123, id = 9007199254740993, neg = -5, dec = 0.1 + 0.2}",
+        )
+        .and_then(|mut p| p.eval_full())
+        .unwrap();
+
+        assert_eq!(
+            to_string(ExportFormat::Json, &evaluated)
+                .unwrap()
+                .split_whitespace()
+                .collect::<String>(),
+            r#"{"big":12345678901234567890123,"id":9007199254740993,"neg":-5,"dec":0.3}"#
+        );
+
+        // TOML integers are limited to 64 bits.
+        let evaluated = mk_program("{id = 9007199254740993, dec = 0.5}")
+            .and_then(|mut p| p.eval_full())
+            .unwrap();
+        assert_eq!(
+            to_string(ExportFormat::Toml, &evaluated).unwrap(),
+            "id = 9007199254740993\ndec = 0.5\n"
+        );
+    }
+
+    #[test]
+    fn unsupported_numbers() {
+        let two_128 = "%pow% 2 128";
+        assert_pass_validation!(ExportFormat::Json, "%pow% 2 127", true);
+        assert_pass_validation!(ExportFormat::Json, two_128, false);
+        assert_pass_validation!(ExportFormat::Yaml, "-(%pow% 10 400)", false);
+        assert_pass_validation!(ExportFormat::Json, "(%pow% 10 400) + 0.5", false);
+        assert_pass_validation!(ExportFormat::Json, "1 / (%pow% 10 400)", true);
+        assert_pass_validation!(ExportFormat::Toml, "{a = %pow% 2 63}", false);
+        assert_pass_validation!(ExportFormat::Toml, "{a = (%pow% 2 63) - 1}", true);
+
+        let evaluated = mk_program(two_128).and_then(|mut p| p.eval_full()).unwrap();
+        assert!(to_string(ExportFormat::Json, &evaluated).is_err());
+    }
+
+    #[test]
+    fn multi_file_export() {
+        let export = |s: &str| {
+            mk_program(s)
+                .and_then(|mut p| p.eval_full())
+                .map(|rt| export_files(&rt))
+                .unwrap()
+        };
+
+        let files = export(
+            "{
+                \"a/b.yaml\" = {format = `Yaml, content = {x = 1}},
+                \"notes.txt\" | default = \"hello\",
+            }",
+        )
+        .unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, PathBuf::from("a/b.yaml"));
+        assert_eq!(files[0].format, ExportFormat::Yaml);
+        assert_eq!(
+            to_string(files[0].format, &files[0].content).unwrap(),
+            "---\nx: 1\n"
+        );
+        assert_eq!(files[1].path, PathBuf::from("notes.txt"));
+        assert_eq!(files[1].format, ExportFormat::Raw);
+        assert_eq!(
+            to_string(files[1].format, &files[1].content).unwrap(),
+            "hello"
+        );
+
+        assert!(export("[1]").is_err());
+        assert!(export("{\"../a\" = \"x\"}").is_err());
+        assert!(export("{\"/a\" = \"x\"}").is_err());
+        assert!(export("{a = 1}").is_err());
+        assert!(export("{a = {format = `Xml, content = 1}}").is_err());
+        assert!(export("{a = {format = `Json}}").is_err());
+        assert!(export("{a = {format = `Json, content = 1, extra = 2}}").is_err());
+    }
 }
@@ -47,6 +47,7 @@ pub mod contract {
     generate_accessor!(forall_var);
     generate_accessor!(fail);
     generate_accessor!(row_extend);
+    generate_accessor!(row_extend_variant);
     generate_accessor!(record);
     generate_accessor!(dyn_record);
     generate_accessor!(record_extend);
@@ -54,3 +55,17 @@ pub mod contract {
     generate_accessor!(dyn_tail);
     generate_accessor!(empty_tail);
 }
+
+/// Accessors to the internal functions implementing the strategies of the `array_merge`
+/// annotation (see [`crate::term::ArrayMerge`]).
+pub mod array_merge {
+    use super::*;
+
+    pub fn union() -> RichTerm {
+        mk_term::var("$array_union")
+    }
+
+    pub fn union_by() -> RichTerm {
+        mk_term::var("$array_union_by")
+    }
+}
@@ -16,10 +16,12 @@
 //! contracts, default values, documentation, etc. They bring such usually external object down to
 //! the term level, and together with [crate::eval::merge], they allow for flexible and modular
 //! definitions of contracts, record and metadata all together.
-use crate::destruct::Destruct;
+use crate::cache::InputFormat;
+use crate::destruct::{Destruct, MatchCase};
 use crate::identifier::Ident;
 use crate::label::Label;
 use crate::match_sharedterm;
+use crate::number::{self, Number};
 use crate::position::TermPos;
 use crate::types::{AbsType, Types, UnboundTypeVariableError};
 use codespan::FileId;
@@ -30,6 +32,10 @@ use std::fmt;
 use std::ops::Deref;
 use std::rc::Rc;
 
+/// The map of record fields, re-exported for the users of [`Term::Record`] and of the
+/// [`mk_record`](crate::mk_record) and [`mk_switch`](crate::mk_switch) macros.
+pub use indexmap::IndexMap;
+
 /// The AST of a Nickel expression.
 ///
 /// Parsed terms also need to store their position in the source for error reporting.  This is why
@@ -42,9 +48,10 @@ pub enum Term {
     Null,
     /// A boolean value.
     Bool(bool),
-    /// A floating-point value.
+    /// A number, represented exactly as an arbitrary precision rational.
     #[serde(serialize_with = "crate::serialize::serialize_num")]
-    Num(f64),
+    #[serde(deserialize_with = "crate::serialize::deserialize_num")]
+    Num(Number),
     /// A literal string.
     Str(String),
     /// A string containing interpolated expressions, represented as a list of either literals or
@@ -82,15 +89,19 @@ pub enum Term {
 
     /// An enum variant.
     Enum(Ident),
+    /// An enum variant carrying data, such as `` `Port 8080 ``.
+    #[serde(serialize_with = "crate::serialize::serialize_enum_variant")]
+    #[serde(skip_deserializing)]
+    EnumVariant(Ident, RichTerm),
 
-    /// A record, mapping identifiers to terms.
+    /// A record, mapping identifiers to terms. Fields are kept in the order of their definition.
     #[serde(serialize_with = "crate::serialize::serialize_record")]
     #[serde(deserialize_with = "crate::serialize::deserialize_record")]
-    Record(HashMap<Ident, RichTerm>, RecordAttrs),
+    Record(IndexMap<Ident, RichTerm>, RecordAttrs),
     /// A recursive record, where the fields can reference each others.
     #[serde(skip)]
     RecRecord(
-        HashMap<Ident, RichTerm>,
+        IndexMap<Ident, RichTerm>,
         Vec<(RichTerm, RichTerm)>, /* field whose name is defined by interpolation */
         RecordAttrs,
         Option<RecordDeps>, /* dependency tracking between fields. None before the free var pass */
@@ -98,10 +109,14 @@ pub enum Term {
     /// A switch construct. The evaluation is done by the corresponding unary operator, but we
     /// still need this one for typechecking.
     Switch(
-        RichTerm,                 /* tested expression */
-        HashMap<Ident, RichTerm>, /* cases */
-        Option<RichTerm>,         /* default */
+        RichTerm,                  /* tested expression */
+        IndexMap<Ident, RichTerm>, /* cases */
+        Option<RichTerm>,          /* default */
     ),
+    /// A match expression, matching a value against a list of patterns. It is desugared to
+    /// conditionals and destructuring let-bindings before evaluation.
+    #[serde(skip)]
+    Match(RichTerm /* tested expression */, Vec<MatchCase>),
 
     /// An array.
     Array(Vec<RichTerm>),
@@ -147,9 +162,10 @@ pub enum Term {
     #[serde(skip_deserializing)]
     MetaValue(MetaValue),
 
-    /// An unresolved import.
+    /// An unresolved import, with the format of the imported file if it is given explicitly, as in
+    /// `import "config" as yaml`. Otherwise, the format is determined by the extension of the file.
     #[serde(skip)]
-    Import(OsString),
+    Import(OsString, Option<InputFormat>),
     /// A resolved import (which has already been loaded and parsed).
     #[serde(skip)]
     ResolvedImport(FileId),
@@ -180,15 +196,44 @@ impl Default for BindingType {
     }
 }
 
-#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
 pub struct RecordAttrs {
     pub open: bool,
+    /// The fields annotated with `not_exported`, which are skipped by serialization. This is
+    /// filled when a recursive record is evaluated, because the metavalue of a field may be
+    /// shared with other fields referring to it, which must still be exported.
+    pub not_exported: HashSet<Ident>,
+    /// The optional fields without a value, which are considered as absent from the record (see
+    /// [`Term::is_empty_optional`]). As for `not_exported`, this is filled when a record is
+    /// evaluated, and then maintained by merging.
+    pub empty_optional: HashSet<Ident>,
 }
 
 impl RecordAttrs {
-    pub fn merge(attrs1: RecordAttrs, attrs2: RecordAttrs) -> RecordAttrs {
+    /// Merge the attributes of two records. `in_left` and `in_right` tell if a field is defined
+    /// by the left and by the right record respectively: an optional field without a value stays
+    /// absent only if it is absent from the other record as well, or has no value there either.
+    pub fn merge(
+        attrs1: RecordAttrs,
+        attrs2: RecordAttrs,
+        in_left: impl Fn(&Ident) -> bool,
+        in_right: impl Fn(&Ident) -> bool,
+    ) -> RecordAttrs {
+        let empty_optional = attrs1
+            .empty_optional
+            .iter()
+            .filter(|id| !in_right(id) || attrs2.empty_optional.contains(id))
+            .chain(attrs2.empty_optional.iter().filter(|id| !in_left(id)))
+            .cloned()
+            .collect();
+
+        let mut not_exported = attrs1.not_exported;
+        not_exported.extend(attrs2.not_exported);
+
         RecordAttrs {
             open: attrs1.open || attrs2.open,
+            not_exported,
+            empty_optional,
         }
     }
 }
@@ -206,15 +251,44 @@ pub struct RecordDeps {
 /// Potential dependencies of a single field over the sibling fields in a recursive record.
 pub type FieldDeps = Option<Rc<HashSet<Ident>>>;
 
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Copy, Clone)]
+/// The priority of the value of a metavalue. When merging two values, the one with the highest
+/// priority wins, while values of the same priority are merged together.
+#[derive(Debug, Eq, PartialEq, Ord, PartialOrd, Clone)]
 pub enum MergePriority {
+    /// The priority of values annotated with `default`, which is lower than any numeral priority.
     Default,
-    Normal,
+    /// A numeral priority, set by the `priority` annotation. Values without annotation have the
+    /// priority `0`.
+    Numeral(Number),
+    /// The priority of values annotated with `force`, which take precedence over any other value
+    /// of the program.
+    Top,
+    /// The priority of values set from outside of the program, such as the command line
+    /// overrides, which take precedence even over forced values.
+    Override,
+}
+
+impl MergePriority {
+    /// The priority of values without annotation.
+    pub fn normal() -> Self {
+        MergePriority::Numeral(Number::from(0))
+    }
 }
 
 impl Default for MergePriority {
     fn default() -> Self {
-        Self::Normal
+        Self::normal()
+    }
+}
+
+impl fmt::Display for MergePriority {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergePriority::Default => write!(f, "default"),
+            MergePriority::Numeral(n) => write!(f, "{}", number::format(n)),
+            MergePriority::Top => write!(f, "force"),
+            MergePriority::Override => write!(f, "override"),
+        }
     }
 }
 
@@ -224,12 +298,50 @@ pub struct Contract {
     pub label: Label,
 }
 
+/// A strategy to merge two arrays, set by the `array_merge` annotation of a record field.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum ArrayMerge {
+    /// Concatenate the two arrays.
+    Concat,
+    /// Concatenate the two arrays, but skip the elements already present.
+    Union,
+    /// Concatenate the two arrays, but merge together the records with the same value for the
+    /// given field.
+    UnionBy(Ident),
+    /// Keep the right array.
+    Replace,
+}
+
+impl fmt::Display for ArrayMerge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArrayMerge::Concat => write!(f, "concat"),
+            ArrayMerge::Union => write!(f, "union"),
+            ArrayMerge::UnionBy(id) => write!(f, "union_by \"{}\"", id),
+            ArrayMerge::Replace => write!(f, "replace"),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct MetaValue {
     pub doc: Option<String>,
     pub types: Option<Contract>,
     pub contracts: Vec<Contract>,
     pub priority: MergePriority,
+    /// If the field is omitted by serialization, set by the `not_exported` annotation. Such a field
+    /// is otherwise a normal field, which can be referred to, recursively or not, and merged.
+    pub not_exported: bool,
+    /// If the field may be absent, set by the `optional` annotation. An optional field without a
+    /// value is considered as absent from the record, but its contracts are still applied when
+    /// it is defined by merging.
+    pub optional: bool,
+    /// A deprecation message, set by the `deprecated` annotation. A warning is emitted when the
+    /// field is accessed, or defined by merging.
+    pub deprecated: Option<String>,
+    /// How to merge the value with another array of the same priority, set by the `array_merge`
+    /// annotation. Without strategy, only empty arrays can be merged.
+    pub array_merge: Option<ArrayMerge>,
     pub value: Option<RichTerm>,
 }
 
@@ -240,6 +352,10 @@ impl From<RichTerm> for MetaValue {
             types: None,
             contracts: Vec::new(),
             priority: Default::default(),
+            not_exported: false,
+            optional: false,
+            deprecated: None,
+            array_merge: None,
             value: Some(rt),
         }
     }
@@ -258,6 +374,10 @@ impl MetaValue {
             types: None,
             contracts: Vec::new(),
             priority: Default::default(),
+            not_exported: false,
+            optional: false,
+            deprecated: None,
+            array_merge: None,
             value: None,
         }
     }
@@ -284,6 +404,10 @@ impl MetaValue {
             types,
             mut contracts,
             priority,
+            not_exported,
+            optional,
+            deprecated,
+            array_merge,
             value: _,
         } = outer;
 
@@ -303,12 +427,30 @@ impl MetaValue {
             doc: doc.or(inner.doc),
             types: types.or(inner.types),
             contracts,
-            priority: std::cmp::min(priority, inner.priority),
+            priority: flatten_priority(priority, inner.priority),
+            not_exported: not_exported || inner.not_exported,
+            optional: optional || inner.optional,
+            deprecated: deprecated.or(inner.deprecated),
+            array_merge: array_merge.or(inner.array_merge),
             value: inner.value,
         }
     }
 }
 
+/// Combine the priorities of two nested metavalues. An explicit priority takes precedence over the
+/// normal one, and if both are explicit, the lowest one is kept.
+fn flatten_priority(outer: MergePriority, inner: MergePriority) -> MergePriority {
+    let normal = MergePriority::normal();
+
+    if outer == normal {
+        inner
+    } else if inner == normal {
+        outer
+    } else {
+        std::cmp::min(outer, inner)
+    }
+}
+
 /// A chunk of a string with interpolated expressions inside. Same as `Either<String,
 /// RichTerm>` but with explicit constructor names.
 #[derive(Debug, PartialEq, Clone)]
@@ -349,6 +491,13 @@ impl Term {
                     func(def)
                 }
             }
+            Match(ref mut t, ref mut cases) => {
+                func(t);
+                cases.iter_mut().for_each(|c| {
+                    c.guard.iter_mut().for_each(&func);
+                    func(&mut c.body);
+                });
+            }
             Record(ref mut static_map, _) => {
                 static_map.iter_mut().for_each(|(_, t)| func(t));
             }
@@ -359,10 +508,11 @@ impl Term {
                     func(t2);
                 });
             }
-            Bool(_) | Num(_) | Str(_) | Lbl(_) | Var(_) | Sym(_) | Enum(_) | Import(_)
+            Bool(_) | Num(_) | Str(_) | Lbl(_) | Var(_) | Sym(_) | Enum(_) | Import(..)
             | ResolvedImport(_) => {}
             Fun(_, ref mut t)
             | FunPattern(_, _, ref mut t)
+            | EnumVariant(_, ref mut t)
             | Op1(_, ref mut t)
             | Wrapped(_, ref mut t) => {
                 func(t);
@@ -407,7 +557,7 @@ impl Term {
             Term::Str(_) => Some("Str"),
             Term::Fun(_, _) | Term::FunPattern(_, _, _) => Some("Fun"),
             Term::Lbl(_) => Some("Label"),
-            Term::Enum(_) => Some("Enum"),
+            Term::Enum(_) | Term::EnumVariant(..) => Some("Enum"),
             Term::Record(..) | Term::RecRecord(..) => Some("Record"),
             Term::Array(_) => Some("Array"),
             Term::Sym(_) => Some("Sym"),
@@ -418,10 +568,11 @@ impl Term {
             | Term::App(_, _)
             | Term::Var(_)
             | Term::Switch(..)
+            | Term::Match(..)
             | Term::Op1(_, _)
             | Term::Op2(_, _, _)
             | Term::OpN(..)
-            | Term::Import(_)
+            | Term::Import(..)
             | Term::ResolvedImport(_)
             | Term::StrChunks(_)
             | Term::ParseError => None,
@@ -435,7 +586,7 @@ impl Term {
             Term::Null => String::from("null"),
             Term::Bool(true) => String::from("true"),
             Term::Bool(false) => String::from("false"),
-            Term::Num(n) => format!("{}", n),
+            Term::Num(n) => number::format(n),
             Term::Str(s) => format!("\"{}\"", s),
             Term::StrChunks(chunks) => {
                 let chunks_str: Vec<String> = chunks
@@ -460,6 +611,11 @@ impl Term {
                     format!("`\"{}\"", s)
                 }
             }
+            Term::EnumVariant(id, t) => format!(
+                "{} {}",
+                Term::Enum(id.clone()).shallow_repr(),
+                t.as_ref().shallow_repr()
+            ),
             Term::Record(..) | Term::RecRecord(..) => String::from("{ ... }"),
             Term::Array(_) => String::from("[ ... ]"),
             Term::Sym(_) => String::from("<sym>"),
@@ -493,10 +649,11 @@ impl Term {
             | Term::LetPattern(..)
             | Term::App(_, _)
             | Term::Switch(..)
+            | Term::Match(..)
             | Term::Op1(_, _)
             | Term::Op2(_, _, _)
             | Term::OpN(..)
-            | Term::Import(_)
+            | Term::Import(..)
             | Term::ResolvedImport(_) => String::from("<unevaluated>"),
         }
     }
@@ -524,6 +681,11 @@ impl Term {
                     .collect();
                 format!("[ {} ]", elements_str.join(", "))
             }
+            Term::EnumVariant(id, t) => format!(
+                "{} {}",
+                Term::Enum(id.clone()).shallow_repr(),
+                t.as_ref().deep_repr()
+            ),
             _ => self.shallow_repr(),
         }
     }
@@ -538,6 +700,7 @@ impl Term {
             | Term::Fun(_, _)
             | Term::Lbl(_)
             | Term::Enum(_)
+            | Term::EnumVariant(..)
             | Term::Record(..)
             | Term::Array(_)
             | Term::Sym(_) => true,
@@ -547,12 +710,13 @@ impl Term {
             | Term::App(_, _)
             | Term::Var(_)
             | Term::Switch(..)
+            | Term::Match(..)
             | Term::Op1(_, _)
             | Term::Op2(_, _, _)
             | Term::OpN(..)
             | Term::Wrapped(_, _)
             | Term::MetaValue(_)
-            | Term::Import(_)
+            | Term::Import(..)
             | Term::ResolvedImport(_)
             | Term::StrChunks(_)
             | Term::RecRecord(..)
@@ -565,6 +729,26 @@ impl Term {
         matches!(self, Term::MetaValue(..))
     }
 
+    /// Determine if a record field is an optional field without a value, which is then considered
+    /// as absent from the record. The field must be given as it was defined, before evaluation:
+    /// it is absent if it is itself a metavalue annotated with `optional` and without value, or a
+    /// merge of such metavalues. A field referring to an absent field is not absent.
+    pub fn is_empty_optional(&self) -> bool {
+        match self {
+            Term::MetaValue(MetaValue {
+                optional: true,
+                value: None,
+                ..
+            }) => true,
+            // Look through the bindings introduced by the share normal form transformation.
+            Term::Let(_, _, body, _) => body.as_ref().is_empty_optional(),
+            Term::Op2(BinaryOp::Merge(), t1, t2) => {
+                t1.as_ref().is_empty_optional() && t2.as_ref().is_empty_optional()
+            }
+            _ => false,
+        }
+    }
+
     /// Determine if a term is a constant.
     ///
     /// In this context, a constant is an atomic literal of the language: null, a boolean, a number, a
@@ -582,17 +766,19 @@ impl Term {
             | Term::LetPattern(..)
             | Term::Record(..)
             | Term::Array(_)
+            | Term::EnumVariant(..)
             | Term::Fun(_, _)
             | Term::FunPattern(_, _, _)
             | Term::App(_, _)
             | Term::Switch(..)
+            | Term::Match(..)
             | Term::Var(_)
             | Term::Op1(_, _)
             | Term::Op2(_, _, _)
             | Term::OpN(..)
             | Term::Wrapped(_, _)
             | Term::MetaValue(_)
-            | Term::Import(_)
+            | Term::Import(..)
             | Term::ResolvedImport(_)
             | Term::StrChunks(_)
             | Term::RecRecord(..)
@@ -688,6 +874,9 @@ pub enum UnaryOp {
     Embed(Ident),
     /// A switch block. Used to match on a enumeration.
     Switch(bool /* presence of a default case */), //HashMap<Ident, CapturedTerm>, Option<CapturedTerm>),
+    /// Raise a non-exhaustive match error for the argument. Generated by the desugaring of a
+    /// `match` expression, when no case matches the tested value.
+    NonExhaustiveMatch(),
 
     /// Static access to a record field.
     ///
@@ -791,6 +980,12 @@ pub enum UnaryOp {
     NumFromStr(),
     /// Transform a string to an enum.
     EnumFromStr(),
+    /// Test if a term is an enum variant carrying data.
+    EnumIsVariant(),
+    /// Return the tag of an enum, with or without data, as a bare enum tag.
+    EnumGetTag(),
+    /// Return the data carried by an enum variant.
+    EnumGetArg(),
 }
 
 /// Primitive binary operators
@@ -836,6 +1031,10 @@ pub enum BinaryOp {
     ///
     /// See `GoDom`.
     GoField(),
+    /// Go to the argument of a specific variant in the type path of a label.
+    ///
+    /// See `GoDom`.
+    GoVariant(),
     /// Set the tag text of a blame label.
     Tag(),
     /// Extend a record with a dynamic field.
@@ -1029,7 +1228,7 @@ impl RichTerm {
             Term::Switch(t, cases, default) => {
                 // The annotation on `map_res` use Result's corresponding trait to convert from
                 // Iterator<Result> to a Result<Iterator>
-                let cases_res: Result<HashMap<Ident, RichTerm>, E> = cases
+                let cases_res: Result<IndexMap<Ident, RichTerm>, E> = cases
                     .into_iter()
                     // For the conversion to work, note that we need a Result<(Ident,RichTerm), E>
                     .map(|(id, t)| t.traverse(f, state, method).map(|t_ok| (id.clone(), t_ok)))
@@ -1044,6 +1243,22 @@ impl RichTerm {
                     pos,
                 )
             },
+            Term::Match(t, cases) => {
+                let t = t.traverse(f, state, method)?;
+                let cases = cases
+                    .into_iter()
+                    .map(|MatchCase { pattern, guard, body }| {
+                        let guard = guard.map(|g| g.traverse(f, state, method)).transpose()?;
+                        let body = body.traverse(f, state, method)?;
+                        Ok(MatchCase { pattern, guard, body })
+                    })
+                    .collect::<Result<Vec<_>, E>>()?;
+
+                RichTerm::new(
+                    Term::Match(t, cases),
+                    pos,
+                )
+            },
             Term::Op1(op, t) => {
                 let t = t.traverse(f, state, method)?;
                 RichTerm::new(
@@ -1075,10 +1290,17 @@ impl RichTerm {
                     pos,
                 )
             },
+            Term::EnumVariant(tag, t) => {
+                let t = t.traverse(f, state, method)?;
+                RichTerm::new(
+                    Term::EnumVariant(tag, t),
+                    pos,
+                )
+            },
             Term::Record(map, attrs) => {
                 // The annotation on `map_res` uses Result's corresponding trait to convert from
                 // Iterator<Result> to a Result<Iterator>
-                let map_res: Result<HashMap<Ident, RichTerm>, E> = map
+                let map_res: Result<IndexMap<Ident, RichTerm>, E> = map
                     .into_iter()
                     // For the conversion to work, note that we need a Result<(Ident,RichTerm), E>
                     .map(|(id, t)| t.traverse(f, state, method).map(|t_ok| (id.clone(), t_ok)))
@@ -1091,7 +1313,7 @@ impl RichTerm {
             Term::RecRecord(map, dyn_fields, attrs, deps) => {
                 // The annotation on `map_res` uses Result's corresponding trait to convert from
                 // Iterator<Result> to a Result<Iterator>
-                let map_res: Result<HashMap<Ident, RichTerm>, E> = map
+                let map_res: Result<IndexMap<Ident, RichTerm>, E> = map
                     .into_iter()
                     // For the conversion to work, note that we need a Result<(Ident,RichTerm), E>
                     .map(|(id, t)| Ok((id, t.traverse(f, state, method)?)))
@@ -1175,6 +1397,10 @@ impl RichTerm {
                         types,
                         contracts,
                         priority: meta.priority,
+                        not_exported: meta.not_exported,
+                        optional: meta.optional,
+                        deprecated: meta.deprecated,
+                        array_merge: meta.array_merge,
                         value,
                     };
                 RichTerm::new(
@@ -1218,10 +1444,10 @@ impl From<Term> for RichTerm {
 /// It is used somehow as a match statement, going from
 /// ```
 /// # use nickel_lang::term::{RichTerm, Term};
-/// let rt = RichTerm::from(Term::Num(5.0));
+/// let rt = RichTerm::from(Term::Bool(true));
 ///
 /// match rt.term.into_owned() {
-///     Term::Num(x) => x as usize,
+///     Term::Bool(b) => b as usize,
 ///     Term::Str(s) => s.len(),
 ///     _ => 42,
 /// };
@@ -1230,10 +1456,10 @@ impl From<Term> for RichTerm {
 /// ```
 /// # use nickel_lang::term::{RichTerm, Term};
 /// # use nickel_lang::match_sharedterm;
-/// let rt = RichTerm::from(Term::Num(5.0));
+/// let rt = RichTerm::from(Term::Bool(true));
 ///
 /// match_sharedterm!{rt.term, with {
-///         Term::Num(x) => x as usize,
+///         Term::Bool(b) => b as usize,
 ///         Term::Str(s) => s.len(),
 ///     } else 42
 /// };
@@ -1308,7 +1534,7 @@ pub mod make {
     macro_rules! mk_record {
         ( $( ($id:expr, $body:expr) ),* ) => {
             {
-                let mut map = std::collections::HashMap::new();
+                let mut map = $crate::term::IndexMap::new();
                 $(
                     map.insert($id.into(), $body.into());
                 )*
@@ -1325,7 +1551,7 @@ pub mod make {
     macro_rules! mk_switch {
         ( $exp:expr, $( ($id:expr, $body:expr) ),* ; $default:expr ) => {
             {
-                let mut map = std::collections::HashMap::new();
+                let mut map = $crate::term::IndexMap::new();
                 $(
                     map.insert($id.into(), $body.into());
                 )*
@@ -1333,7 +1559,7 @@ pub mod make {
             }
         };
         ( $exp:expr, $( ($id:expr, $body:expr) ),*) => {
-                let mut map = std::collections::HashMap::new();
+                let mut map = $crate::term::IndexMap::new();
                 $(
                     map.insert($id.into(), $body.into());
                 )*
@@ -1365,7 +1591,10 @@ pub mod make {
         I: Into<Ident>,
     {
         match pat.into() {
-            d @ (Destruct::Record { .. } | Destruct::Array { .. }) => {
+            d @ (Destruct::Record { .. }
+            | Destruct::Array { .. }
+            | Destruct::Constant { .. }
+            | Destruct::Variant { .. }) => {
                 Term::LetPattern(id.map(|i| i.into()), d, t1.into(), t2.into()).into()
             }
             Destruct::Empty => {
@@ -1378,7 +1607,6 @@ pub mod make {
         }
     }
 
-    #[cfg(test)]
     pub fn if_then_else<T1, T2, T3>(cond: T1, t1: T2, t2: T3) -> RichTerm
     where
         T1: Into<RichTerm>,
@@ -1435,7 +1663,7 @@ pub mod make {
     where
         S: Into<OsString>,
     {
-        Term::Import(path.into()).into()
+        Term::Import(path.into(), None).into()
     }
 }
 
@@ -18,6 +18,51 @@
 //! ...
 //! ```
 //!
+//! ## The array pattern:
+//! ```text
+//! let [a, b ? 0, ..rest] = [1, 2, 3] in ...
+//! ```
+//! will be transformed to:
+//! ```text
+//! let x = [1, 2, 3] in
+//! let a = %elem_at% x 0 in
+//! let b = $array_pattern_elem 1 0 x in
+//! let rest = $array_pattern_rest 2 x in
+//! ...
+//! ```
+//! where `$array_pattern_elem` returns the default value if the array is too short, and
+//! `$array_pattern_rest` returns the elements after the matched ones.
+//!
+//! ## The match expression
+//! ```text
+//! match { {a, b = `foo} => a, [x, ..] if x > 0 => x, _ => 0 } exp
+//! ```
+//! will be transformed to a chain of conditionals:
+//! ```text
+//! let x = exp in
+//! let next1 = (
+//!   let next2 = (
+//!     let next3 = %non_exhaustive_match% x in
+//!     0
+//!   ) in
+//!   if %is_array% x && %length% x >= 1 then (let [x, ..] = x in if x > 0 then x else next2)
+//!   else next2
+//! ) in
+//! if %is_record% x && %has_field% "a" x && ... then (let {a, b = `foo} = x in a) else next1
+//! ```
+//!
+//! ## The enum variant pattern
+//! ```text
+//! let `Port p = `Port 8080 in ...
+//! ```
+//! will be transformed to:
+//! ```text
+//! let x = `Port 8080 in
+//! let p = %enum_get_arg% x in
+//! ...
+//! ```
+//! where the contract of the pattern checks beforehand that `x` is a variant with the tag `Port`.
+//!
 //! ## The function pattern
 //! ```text
 //! let f = fun x@{a, b=c} {d ? 2, ..w} => <do_something> in ...
@@ -30,13 +75,19 @@
 //!     <do_something>
 //! ) in ...
 //! ```
-use crate::destruct::{Destruct, Match};
+use crate::destruct::{ArrayMatch, Destruct, Match, MatchCase};
 use crate::identifier::Ident;
-use crate::match_sharedterm;
-use crate::term::make::{op1, op2};
+use crate::number::Number;
+use crate::term::make::{self as mk_term, if_then_else, let_in, op1, op2};
 use crate::term::{
-    BinaryOp::DynRemove, BindingType, MetaValue, RichTerm, Term, UnaryOp::StaticAccess,
+    BinaryOp::{ArrayElemAt, DynRemove, Eq, GreaterOrEq, HasField, LessOrEq},
+    BindingType, MetaValue, RichTerm, Term,
+    UnaryOp::{
+        ArrayLength, BoolAnd, BoolNot, BoolOr, EnumGetArg, EnumGetTag, EnumIsVariant, IsArray,
+        IsRecord, NonExhaustiveMatch, StaticAccess,
+    },
 };
+use crate::{match_sharedterm, mk_app};
 
 /// Entry point of the patterns desugaring.
 /// It desugar a `RichTerm` if possible (the term is a let pattern or a function with patterns in
@@ -47,6 +98,7 @@ pub fn transform_one(rt: RichTerm) -> RichTerm {
     match *rt.term {
         Term::LetPattern(..) => desugar_with_contract(rt),
         Term::FunPattern(..) => desugar_fun(rt),
+        Term::Match(..) => desugar_match(rt),
         _ => rt,
     }
 }
@@ -145,11 +197,32 @@ fn bind_open_field(x: Ident, pat: &Destruct, body: RichTerm) -> RichTerm {
             rest: None,
             ..
         } => (matches, super::fresh_var()),
+        Destruct::Array {
+            matches,
+            open: true,
+            rest: Some(rest),
+            ..
+        } => {
+            return Term::Let(
+                rest.clone(),
+                mk_app!(
+                    mk_term::var("$array_pattern_rest"),
+                    Term::Num(Number::from(matches.len())),
+                    Term::Var(x)
+                ),
+                body,
+                BindingType::Normal,
+            )
+            .into()
+        }
         Destruct::Record {
             open: false,
             rest: None,
             ..
         }
+        | Destruct::Array { rest: None, .. }
+        | Destruct::Constant { .. }
+        | Destruct::Variant { .. }
         | Destruct::Empty => return body,
         _ => panic!("A closed pattern can not have a rest binding"),
     };
@@ -191,6 +264,198 @@ fn destruct_term(x: Ident, pat: &Destruct, body: RichTerm) -> RichTerm {
                 pos,
             )),
         }),
+        Destruct::Array { matches, .. } => {
+            matches
+                .iter()
+                .enumerate()
+                .fold(body, move |t, (i, m)| match m {
+                    ArrayMatch {
+                        id: Some(id),
+                        pattern: Destruct::Empty,
+                        ..
+                    } => RichTerm::new(
+                        Term::Let(id.clone(), array_elem(&x, i, m), t, BindingType::Normal),
+                        pos,
+                    ),
+                    ArrayMatch { id, pattern, .. } => desugar_with_contract(RichTerm::new(
+                        Term::LetPattern(id.clone(), pattern.clone(), array_elem(&x, i, m), t),
+                        pos,
+                    )),
+                })
+        }
+        Destruct::Variant { arg, .. } => {
+            let (id, pat) = arg.as_ref();
+            desugar(RichTerm::new(
+                Term::LetPattern(
+                    id.clone(),
+                    pat.clone(),
+                    op1(EnumGetArg(), Term::Var(x)),
+                    body,
+                ),
+                pos,
+            ))
+        }
         _ => body,
     }
 }
+
+/// Generate the term extracting the element at position `i` of the array `x` matched by the
+/// element `m` of an array pattern. Fall back to the default value of `m` if the array is too
+/// short, and wrap the result with the contracts of `m`.
+fn array_elem(x: &Ident, i: usize, m: &ArrayMatch) -> RichTerm {
+    let index = Term::Num(Number::from(i));
+    let value = match &m.meta.value {
+        Some(default) => mk_app!(
+            mk_term::var("$array_pattern_elem"),
+            index,
+            default.clone(),
+            Term::Var(x.clone())
+        ),
+        None => op2(ArrayElemAt(), Term::Var(x.clone()), index),
+    };
+
+    Term::MetaValue(MetaValue {
+        types: m.meta.types.clone(),
+        contracts: m.meta.contracts.clone(),
+        value: Some(value),
+        ..Default::default()
+    })
+    .into()
+}
+
+/// Desugar a `match` expression to a chain of conditionals. Each case first tests if the value
+/// matches its pattern. If it does, the variables of the pattern are bound by a destructuring
+/// let-binding, and the guard, if any, is evaluated. If any of those tests fails, the next case is
+/// tried. If no case matches, a non-exhaustive match error is raised.
+pub fn desugar_match(rt: RichTerm) -> RichTerm {
+    match_sharedterm!(rt.term,
+        with {
+            Term::Match(exp, cases) => {
+                let pos = rt.pos;
+                let x = super::fresh_var();
+                let no_match = RichTerm::new(Term::Op1(NonExhaustiveMatch(), Term::Var(x.clone()).into()), pos);
+
+                let cases = cases.into_iter().rev().fold(no_match, |next, case| {
+                    let MatchCase {
+                        pattern: (id, pat),
+                        guard,
+                        body,
+                    } = case;
+                    let next_var = super::fresh_var();
+                    let test = match_test(&pat, Term::Var(x.clone()).into());
+
+                    let body = match guard {
+                        Some(guard) => if_then_else(guard, body, Term::Var(next_var.clone())),
+                        None => body,
+                    };
+                    let body = match (id, pat) {
+                        (None, Destruct::Empty) => body,
+                        (Some(id), Destruct::Empty) => let_in(id, Term::Var(x.clone()), body),
+                        (id, pat) => Term::LetPattern(id, pat, Term::Var(x.clone()).into(), body).into(),
+                    };
+                    let body = match test {
+                        Some(test) => if_then_else(test, body, Term::Var(next_var.clone())),
+                        None => body,
+                    };
+
+                    let_in(next_var, next, body)
+                });
+
+                RichTerm::new(Term::Let(x, exp, cases, BindingType::Normal), pos)
+            }
+        } else rt
+    )
+}
+
+/// Generate a boolean term testing if `x` matches the pattern `pat`, or `None` if any value
+/// matches. The contracts and the type annotations of the pattern are not tested: they are applied
+/// when binding the variables of the pattern.
+fn match_test(pat: &Destruct, x: RichTerm) -> Option<RichTerm> {
+    let and = |t1, t2| mk_app!(op1(BoolAnd(), t1), t2);
+    let or = |t1, t2| mk_app!(op1(BoolOr(), t1), t2);
+
+    let tests = match pat {
+        Destruct::Empty => return None,
+        Destruct::Constant { value, .. } => return Some(op2(Eq(), x, value.clone())),
+        Destruct::Record { matches, open, .. } => {
+            let mut tests = vec![op1(IsRecord(), x.clone())];
+
+            for m in matches {
+                let (id, meta, sub_pat) = match m {
+                    Match::Simple(id, meta) => (id, meta, &Destruct::Empty),
+                    Match::Assign(id, meta, (_, sub_pat)) => (id, meta, sub_pat),
+                };
+                let has_field = op2(HasField(), Term::Str(id.to_string()), x.clone());
+                let sub_test = match_test(sub_pat, op1(StaticAccess(id.clone()), x.clone()));
+
+                // A field with a default value may be missing.
+                match (meta.value.is_some(), sub_test) {
+                    (true, Some(sub_test)) => tests.push(or(op1(BoolNot(), has_field), sub_test)),
+                    (true, None) => (),
+                    (false, Some(sub_test)) => tests.push(and(has_field, sub_test)),
+                    (false, None) => tests.push(has_field),
+                }
+            }
+
+            if !open {
+                let names = matches
+                    .iter()
+                    .map(|m| match m {
+                        Match::Simple(id, _) | Match::Assign(id, _, _) => {
+                            Term::Str(id.to_string()).into()
+                        }
+                    })
+                    .collect();
+                tests.push(mk_app!(
+                    mk_term::var("$record_pattern_closed"),
+                    Term::Array(names),
+                    x.clone()
+                ));
+            }
+
+            tests
+        }
+        Destruct::Array { matches, .. } => {
+            let (min, max) = pat.length_bounds().unwrap();
+            let length = || op1(ArrayLength(), x.clone());
+            let num = |n: usize| Term::Num(Number::from(n));
+            let mut tests = vec![
+                op1(IsArray(), x.clone()),
+                op2(GreaterOrEq(), length(), num(min)),
+            ];
+
+            if let Some(max) = max {
+                tests.push(op2(LessOrEq(), length(), num(max)));
+            }
+
+            for (i, m) in matches.iter().enumerate() {
+                let elem = op2(ArrayElemAt(), x.clone(), num(i));
+
+                if let Some(sub_test) = match_test(&m.pattern, elem) {
+                    // An element with a default value may be missing.
+                    if m.has_default() {
+                        tests.push(or(op2(LessOrEq(), length(), num(i)), sub_test));
+                    } else {
+                        tests.push(sub_test);
+                    }
+                }
+            }
+
+            tests
+        }
+        Destruct::Variant { tag, arg, .. } => {
+            let mut tests = vec![
+                op1(EnumIsVariant(), x.clone()),
+                op2(Eq(), op1(EnumGetTag(), x.clone()), Term::Enum(tag.clone())),
+            ];
+
+            if let Some(sub_test) = match_test(&arg.1, op1(EnumGetArg(), x)) {
+                tests.push(sub_test);
+            }
+
+            tests
+        }
+    };
+
+    tests.into_iter().reduce(and)
+}
@@ -4,7 +4,7 @@
 //! the recursive fields that actually appear in the definition of each field when computing the
 //! fixpoint.
 use crate::{
-    destruct::{Destruct, Match},
+    destruct::{Destruct, Match, MatchCase},
     identifier::Ident,
     term::{RecordDeps, RichTerm, SharedTerm, StrChunk, Term},
     types::{AbsType, Types},
@@ -32,7 +32,7 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
         | Term::Lbl(_)
         | Term::Sym(_)
         | Term::Enum(_)
-        | Term::Import(_)
+        | Term::Import(..)
         | Term::ResolvedImport(_) => (),
         Term::Fun(id, t) => {
             let mut fresh = HashSet::new();
@@ -84,6 +84,27 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
                 collect_free_vars(t, free_vars);
             }
         }
+        Term::Match(t, cases) => {
+            collect_free_vars(t, free_vars);
+            for MatchCase {
+                pattern: (id, dest_pat),
+                guard,
+                body,
+            } in cases.iter_mut()
+            {
+                let mut fresh = HashSet::new();
+
+                for t in guard.iter_mut().chain(std::iter::once(body)) {
+                    collect_free_vars(t, &mut fresh);
+                }
+                bind_pattern(dest_pat, &mut fresh);
+                if let Some(id) = id {
+                    fresh.remove(id);
+                }
+
+                free_vars.extend(fresh);
+            }
+        }
         Term::Op1(_, t) => collect_free_vars(t, free_vars),
         Term::Op2(_, t1, t2) => {
             collect_free_vars(t1, free_vars);
@@ -94,7 +115,7 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
                 collect_free_vars(t, free_vars);
             }
         }
-        Term::Wrapped(_, t) => collect_free_vars(t, free_vars),
+        Term::Wrapped(_, t) | Term::EnumVariant(_, t) => collect_free_vars(t, free_vars),
         Term::Record(map, _) => {
             for t in map.values_mut() {
                 collect_free_vars(t, free_vars);
@@ -205,12 +226,27 @@ fn bind_pattern(dest_pat: &Destruct, free_vars: &mut HashSet<Ident>) {
                 free_vars.remove(rest);
             }
         }
-        Destruct::Array { matches, .. } => {
+        Destruct::Array { matches, rest, .. } => {
             for m in matches {
-                bind_match(m, free_vars);
+                if let Some(id) = &m.id {
+                    free_vars.remove(id);
+                }
+                bind_pattern(&m.pattern, free_vars);
+            }
+
+            if let Some(rest) = rest {
+                free_vars.remove(rest);
+            }
+        }
+        Destruct::Variant { arg, .. } => {
+            let (id, pat) = arg.as_ref();
+
+            if let Some(id) = id {
+                free_vars.remove(id);
             }
+            bind_pattern(pat, free_vars);
         }
-        Destruct::Empty => {}
+        Destruct::Constant { .. } | Destruct::Empty => {}
     }
 }
 
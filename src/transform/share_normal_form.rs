@@ -15,8 +15,8 @@
 //! two times here.
 //!
 //! The transformation replaces such subexpressions, namely the content of the fields
-//! of records and the elements of arrays - `(1 + 1)` in our example -, with fresh variables
-//! introduced by `let`  added at the head of the term:
+//! of records, the elements of arrays and the arguments of enum variants - `(1 + 1)` in our
+//! example -, with fresh variables introduced by `let`  added at the head of the term:
 //!
 //! ```text
 //! let x = (let var = 1 + 1 in {a = var}) in x.a + x.a
@@ -32,8 +32,9 @@ use crate::{
     identifier::Ident,
     match_sharedterm,
     position::TermPos,
-    term::{BindingType, RichTerm, Term},
+    term::{BindingType, RecordAttrs, RichTerm, Term},
 };
+use indexmap::IndexMap;
 
 use std::{collections::HashSet, rc::Rc};
 
@@ -50,6 +51,7 @@ pub fn transform_one(rt: RichTerm) -> RichTerm {
     match_sharedterm! {rt.term,
         with {
             Term::Record(map, attrs) => {
+                let attrs = collect_field_attrs(attrs, &map);
                 let mut bindings = Vec::with_capacity(map.len());
 
                 let map = map
@@ -83,6 +85,7 @@ pub fn transform_one(rt: RichTerm) -> RichTerm {
                 // fields of recursive records contain either a constant or a *generated* variable,
                 // but never a user-supplied variable directly (the former starts with a special
                 // marker). See comments inside [`crate::RichTerm::closurize`] for more details.
+                let attrs = collect_field_attrs(attrs, &map);
                 let mut bindings = Vec::with_capacity(map.len());
 
                 fn mk_binding_type(field_deps: Option<HashSet<Ident>>) -> BindingType {
@@ -158,6 +161,12 @@ pub fn transform_one(rt: RichTerm) -> RichTerm {
 
                 with_bindings(Term::Array(ts), bindings, pos)
             },
+            Term::EnumVariant(tag, t) if should_share(&t.term) => {
+                let fresh_var = fresh_var();
+                let pos_t = t.pos;
+                let inner = RichTerm::new(Term::EnumVariant(tag, RichTerm::new(Term::Var(fresh_var.clone()), pos_t)), pos);
+                RichTerm::new(Term::Let(fresh_var, t, inner, BindingType::Normal), pos)
+            },
             Term::MetaValue(meta) if meta.value.as_ref().map(|t| should_share(&t.term)).unwrap_or(false) => {
                     let mut meta = meta;
                     let fresh_var = fresh_var();
@@ -171,6 +180,32 @@ pub fn transform_one(rt: RichTerm) -> RichTerm {
     }
 }
 
+/// Record the fields annotated with `not_exported`, and the optional fields without a value, in
+/// the attributes of a record. Once the fields are replaced by variables, their metadata is not
+/// accessible from the record anymore.
+fn collect_field_attrs(mut attrs: RecordAttrs, map: &IndexMap<Ident, RichTerm>) -> RecordAttrs {
+    fn is_not_exported(rt: &RichTerm) -> bool {
+        match rt.as_ref() {
+            Term::MetaValue(meta) => meta.not_exported,
+            // Look through the bindings introduced by the transformation of a metavalue.
+            Term::Let(_, _, body, _) => is_not_exported(body),
+            _ => false,
+        }
+    }
+
+    attrs.not_exported.extend(
+        map.iter()
+            .filter(|(_, rt)| is_not_exported(rt))
+            .map(|(id, _)| id.clone()),
+    );
+    attrs.empty_optional.extend(
+        map.iter()
+            .filter(|(_, rt)| rt.as_ref().is_empty_optional())
+            .map(|(id, _)| id.clone()),
+    );
+    attrs
+}
+
 /// Determine if a subterm of a WHNF should be wrapped in a thunk in order to be shared.
 ///
 /// Sharing is typically useless if the subterm is already a WHNF which can be copied without
@@ -1,5 +1,6 @@
 //! Helpers for building `TypeWrapper`s.
 use super::{AbsType, TypeWrapper};
+use crate::identifier::Ident;
 
 /// Multi-ary arrow constructor for types implementing `Into<TypeWrapper>`.
 #[macro_export]
@@ -107,6 +108,19 @@ where
     TypeWrapper::Concrete(AbsType::Array(Box::new(ty.into())))
 }
 
+/// Build an enum row whose first tag carries an argument of type `ty`: `[| tag ty ; tail |]`.
+pub fn enum_variant_row<T, U>(tag: Ident, ty: T, tail: U) -> TypeWrapper
+where
+    T: Into<TypeWrapper>,
+    U: Into<TypeWrapper>,
+{
+    TypeWrapper::Concrete(AbsType::RowExtend(
+        tag,
+        Some(Box::new(ty.into())),
+        Box::new(tail.into()),
+    ))
+}
+
 // dyn is a reserved keyword
 generate_builder!(dynamic, Dyn);
 generate_builder!(str, Str);
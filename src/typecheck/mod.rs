@@ -44,6 +44,7 @@ use crate::cache::ImportResolver;
 use crate::environment::Environment as GenericEnvironment;
 use crate::error::TypecheckError;
 use crate::identifier::Ident;
+use crate::position::TermPos;
 use crate::term::{Contract, MetaValue, RichTerm, StrChunk, Term};
 use crate::types::{AbsType, Types};
 use crate::{mk_tyw_arrow, mk_tyw_enum, mk_tyw_enum_row, mk_tyw_record, mk_tyw_row};
@@ -281,25 +282,101 @@ fn type_check_<L: Linearizer>(
     ty: TypeWrapper,
 ) -> Result<(), TypecheckError> {
     use crate::destruct::*;
-    // TODO: The insertion of values in the type environment is done but everything is
-    // typed as `Dyn`.
-    fn inject_pat_vars(pat: &Destruct, envs: &mut Envs) {
-        if let Destruct::Record { matches, rest, .. } = pat {
-            if let Some(id) = rest {
-                envs.insert(id.clone(), TypeWrapper::Concrete(AbsType::Dyn()));
+    // TODO: The insertion of values in the type environment is done but the variables of record
+    // patterns are typed as `Dyn`. Variables of array patterns are typed as elements of the array.
+    //
+    // `ty` is the type of the matched value, or `None` if it is unknown (inside a record pattern),
+    // in which case everything is typed as `Dyn`.
+    fn inject_pat_vars(
+        state: &mut State,
+        strict: bool,
+        pat: &Destruct,
+        ty: Option<TypeWrapper>,
+        envs: &mut Envs,
+        pos: TermPos,
+    ) -> Result<(), TypecheckError> {
+        match pat {
+            Destruct::Record { matches, rest, .. } => {
+                if let Some(id) = rest {
+                    envs.insert(id.clone(), TypeWrapper::Concrete(AbsType::Dyn()));
+                }
+                matches.iter().try_for_each(|m| match m {
+                    Match::Simple(id, ..) => {
+                        envs.insert(id.clone(), TypeWrapper::Concrete(AbsType::Dyn()));
+                        Ok(())
+                    }
+                    Match::Assign(id, _, (bind_id, pat)) => {
+                        let id = bind_id.as_ref().unwrap_or(id);
+                        envs.insert(id.clone(), TypeWrapper::Concrete(AbsType::Dyn()));
+                        inject_pat_vars(state, strict, pat, None, envs, pos)
+                    }
+                })
             }
-            matches.iter().for_each(|m| match m {
-                Match::Simple(id, ..) => {
-                    envs.insert(id.clone(), TypeWrapper::Concrete(AbsType::Dyn()))
+            Destruct::Array { matches, rest, .. } => {
+                let ty_elts = match ty {
+                    Some(ty) if strict => {
+                        let ty_elts = state.table.fresh_unif_var();
+                        unify(state, strict, ty, mk_typewrapper::array(ty_elts.clone()))
+                            .map_err(|err| err.into_typecheck_err(state, pos))?;
+                        Some(ty_elts)
+                    }
+                    _ => None,
+                };
+                let elt_or_dyn = || ty_elts.clone().unwrap_or_else(mk_typewrapper::dynamic);
+
+                if let Some(id) = rest {
+                    envs.insert(id.clone(), mk_typewrapper::array(elt_or_dyn()));
                 }
-                Match::Assign(id, _, (bind_id, pat)) => {
-                    let id = bind_id.as_ref().unwrap_or(id);
-                    envs.insert(id.clone(), TypeWrapper::Concrete(AbsType::Dyn()));
-                    if !pat.is_empty() {
-                        inject_pat_vars(&pat, envs);
+                matches.iter().try_for_each(|m| {
+                    if let Some(id) = &m.id {
+                        envs.insert(id.clone(), elt_or_dyn());
+                    }
+                    inject_pat_vars(state, strict, &m.pattern, ty_elts.clone(), envs, pos)
+                })
+            }
+            Destruct::Constant { value, .. } => {
+                let ty_constant = match value.as_ref() {
+                    Term::Num(_) => mk_typewrapper::num(),
+                    Term::Str(_) => mk_typewrapper::str(),
+                    Term::Bool(_) => mk_typewrapper::bool(),
+                    Term::Enum(tag) => {
+                        mk_tyw_enum!(mk_tyw_enum_row!(tag.clone(), state.table.fresh_unif_var()))
+                    }
+                    _ => return Ok(()),
+                };
+
+                match ty {
+                    Some(ty) => unify(state, strict, ty, ty_constant)
+                        .map_err(|err| err.into_typecheck_err(state, pos)),
+                    None => Ok(()),
+                }
+            }
+            Destruct::Variant { tag, arg, .. } => {
+                let (id, pat) = arg.as_ref();
+                let ty_arg = match ty {
+                    Some(ty) if strict => {
+                        let ty_arg = state.table.fresh_unif_var();
+                        let row = mk_typewrapper::enum_variant_row(
+                            tag.clone(),
+                            ty_arg.clone(),
+                            state.table.fresh_unif_var(),
+                        );
+                        unify(state, strict, ty, mk_tyw_enum!(row))
+                            .map_err(|err| err.into_typecheck_err(state, pos))?;
+                        Some(ty_arg)
                     }
+                    _ => None,
+                };
+
+                if let Some(id) = id {
+                    envs.insert(
+                        id.clone(),
+                        ty_arg.clone().unwrap_or_else(mk_typewrapper::dynamic),
+                    );
                 }
-            });
+                inject_pat_vars(state, strict, pat, ty_arg, envs, pos)
+            }
+            Destruct::Empty => Ok(()),
         }
     }
 
@@ -360,9 +437,9 @@ fn type_check_<L: Linearizer>(
             let arr = mk_tyw_arrow!(src.clone(), trg.clone());
             if let Some(x) = x {
                 linearizer.retype_ident(lin, x, src.clone());
-                envs.insert(x.clone(), src);
+                envs.insert(x.clone(), src.clone());
             }
-            inject_pat_vars(pat, &mut envs);
+            inject_pat_vars(state, strict, pat, Some(src), &mut envs, rt.pos)?;
             unify(state, strict, ty, arr).map_err(|err| err.into_typecheck_err(state, rt.pos))?;
             type_check_(state, envs, lin, linearizer, strict, t, trg)
         }
@@ -422,9 +499,9 @@ fn type_check_<L: Linearizer>(
 
             if let Some(x) = x {
                 linearizer.retype_ident(lin, x, ty_let.clone());
-                envs.insert(x.clone(), ty_let);
+                envs.insert(x.clone(), ty_let.clone());
             }
-            inject_pat_vars(pat, &mut envs);
+            inject_pat_vars(state, strict, pat, Some(ty_let), &mut envs, re.pos)?;
             type_check_(state, envs, lin, linearizer, strict, rt, ty)
         }
         Term::App(e, t) => {
@@ -475,6 +552,135 @@ fn type_check_<L: Linearizer>(
             unify(state, strict, ty, res).map_err(|err| err.into_typecheck_err(state, rt.pos))?;
             type_check_(state, envs, lin, linearizer, strict, exp, mk_tyw_enum!(row))
         }
+        Term::Match(exp, cases) => {
+            let ty_exp = state.table.fresh_unif_var();
+            let res = state.table.fresh_unif_var();
+
+            type_check_(
+                state,
+                envs.clone(),
+                lin,
+                linearizer.scope(),
+                strict,
+                exp,
+                ty_exp.clone(),
+            )?;
+
+            // When matching on enum tags or variants without a catch-all case, the tested
+            // expression must be an enum containing only the tags handled by an unguarded case.
+            // A variant case only handles its tag if its sub-pattern is irrefutable. The second
+            // component indicates if the case may not handle its tag, and the last one if the tag
+            // carries an argument.
+            let tags: Option<Vec<(&Ident, bool, bool)>> = cases
+                .iter()
+                .filter(|case| !case.pattern.1.is_empty())
+                .map(|case| match &case.pattern.1 {
+                    Destruct::Constant { value, .. } => match value.as_ref() {
+                        Term::Enum(tag) => Some((tag, case.guard.is_some(), false)),
+                        _ => None,
+                    },
+                    Destruct::Variant { tag, arg, .. } => {
+                        Some((tag, case.guard.is_some() || !arg.1.is_irrefutable(), true))
+                    }
+                    _ => None,
+                })
+                .collect();
+
+            match tags {
+                Some(tags)
+                    if strict && !tags.is_empty() && !cases.iter().any(MatchCase::is_catch_all) =>
+                {
+                    let mut handled: Vec<Ident> = Vec::new();
+                    let mut with_arg: Vec<Ident> = Vec::new();
+                    for (tag, _, has_arg) in tags.iter().filter(|(_, partial, _)| !partial) {
+                        if !handled.contains(tag) {
+                            handled.push((*tag).clone());
+                        }
+                        if *has_arg && !with_arg.contains(tag) {
+                            with_arg.push((*tag).clone());
+                        }
+                    }
+
+                    let mut missing: Vec<Ident> = Vec::new();
+                    for (tag, ..) in tags.iter() {
+                        if !handled.contains(tag) && !missing.contains(tag) {
+                            missing.push((*tag).clone());
+                        }
+                    }
+
+                    if !missing.is_empty() {
+                        return Err(TypecheckError::NonExhaustiveMatch(missing, rt.pos));
+                    }
+
+                    let row = handled
+                        .iter()
+                        .rev()
+                        .fold(mk_typewrapper::row_empty(), |acc, tag| {
+                            if with_arg.contains(tag) {
+                                let ty_arg = state.table.fresh_unif_var();
+                                mk_typewrapper::enum_variant_row(tag.clone(), ty_arg, acc)
+                            } else {
+                                mk_tyw_enum_row!(tag.clone(), acc)
+                            }
+                        });
+
+                    if let Err(err) = unify(state, strict, ty_exp.clone(), mk_tyw_enum!(row)) {
+                        return Err(match enum_tags(state, ty_exp.clone()) {
+                            Some(tags) if tags.iter().any(|tag| !handled.contains(tag)) => {
+                                let missing = tags
+                                    .into_iter()
+                                    .filter(|tag| !handled.contains(tag))
+                                    .collect();
+                                TypecheckError::NonExhaustiveMatch(missing, rt.pos)
+                            }
+                            _ => err.into_typecheck_err(state, exp.pos),
+                        });
+                    }
+                }
+                _ => (),
+            };
+
+            unify(state, strict, ty, res.clone())
+                .map_err(|err| err.into_typecheck_err(state, rt.pos))?;
+
+            cases.iter().try_for_each(
+                |MatchCase {
+                     pattern: (id, pat),
+                     guard,
+                     body,
+                 }|
+                 -> Result<(), TypecheckError> {
+                    let mut envs = envs.clone();
+
+                    if let Some(id) = id {
+                        envs.insert(id.clone(), ty_exp.clone());
+                    }
+                    inject_pat_vars(state, strict, pat, Some(ty_exp.clone()), &mut envs, exp.pos)?;
+
+                    if let Some(guard) = guard {
+                        type_check_(
+                            state,
+                            envs.clone(),
+                            lin,
+                            linearizer.scope(),
+                            strict,
+                            guard,
+                            mk_typewrapper::bool(),
+                        )?;
+                    }
+
+                    type_check_(
+                        state,
+                        envs,
+                        lin,
+                        linearizer.scope(),
+                        strict,
+                        body,
+                        res.clone(),
+                    )
+                },
+            )
+        }
         Term::Var(x) => {
             let x_ty = envs
                 .get(x)
@@ -489,6 +695,17 @@ fn type_check_<L: Linearizer>(
             unify(state, strict, ty, mk_tyw_enum!(id.clone(), row))
                 .map_err(|err| err.into_typecheck_err(state, rt.pos))
         }
+        Term::EnumVariant(id, t) => {
+            let ty_arg = state.table.fresh_unif_var();
+            let row = mk_typewrapper::enum_variant_row(
+                id.clone(),
+                ty_arg.clone(),
+                state.table.fresh_unif_var(),
+            );
+            unify(state, strict, ty, mk_tyw_enum!(row))
+                .map_err(|err| err.into_typecheck_err(state, rt.pos))?;
+            type_check_(state, envs, lin, linearizer, strict, t, ty_arg)
+        }
         // If some fields are defined dynamically, the only potential type that works is `{_ : a}`
         // for some `a`
         Term::RecRecord(stat_map, dynamic, ..) if !dynamic.is_empty() => {
@@ -572,7 +789,18 @@ fn type_check_<L: Linearizer>(
                             ty.clone(),
                         )?;
 
-                        Ok(mk_tyw_row!((id.clone(), ty); acc))
+                        // An optional field without a value may be absent: it can't be part of
+                        // the static type of the record.
+                        if let Term::MetaValue(MetaValue {
+                            optional: true,
+                            value: None,
+                            ..
+                        }) = field.as_ref()
+                        {
+                            Ok(acc)
+                        } else {
+                            Ok(mk_tyw_row!((id.clone(), ty); acc))
+                        }
                     },
                 )?;
 
@@ -687,7 +915,7 @@ fn type_check_<L: Linearizer>(
         // sense. In any case, we infer it to be of type `Dyn` for now.
         Term::MetaValue(_) => unify(state, strict, ty, mk_typewrapper::dynamic())
             .map_err(|err| err.into_typecheck_err(state, rt.pos)),
-        Term::Import(_) => unify(state, strict, ty, mk_typewrapper::dynamic())
+        Term::Import(..) => unify(state, strict, ty, mk_typewrapper::dynamic())
             .map_err(|err| err.into_typecheck_err(state, rt.pos)),
         // We use the apparent type of the import for checking. This function doesn't recursively
         // typecheck imports: this is the responsibility of the caller.
@@ -733,6 +961,31 @@ fn binding_type(
     }
 }
 
+/// Return the tags of a type if it is, after resolution of unification variables, an enum type.
+/// The tail of the enum row, if any, is ignored.
+fn enum_tags(state: &State, ty: TypeWrapper) -> Option<Vec<Ident>> {
+    let root = |ty| match ty {
+        TypeWrapper::Ptr(p) => state.table.root(p),
+        ty => ty,
+    };
+
+    let mut row = match root(ty) {
+        TypeWrapper::Concrete(AbsType::Enum(row)) => *row,
+        _ => return None,
+    };
+    let mut tags = Vec::new();
+
+    loop {
+        match root(row) {
+            TypeWrapper::Concrete(AbsType::RowExtend(tag, _, tail)) => {
+                tags.push(tag);
+                row = *tail;
+            }
+            _ => return Some(tags),
+        }
+    }
+}
+
 /// Different kinds of apparent types (see [`apparent_type`]).
 ///
 /// Indicate the nature of an apparent type. In particular, when in strict mode, the typechecker
@@ -56,6 +56,9 @@ pub fn get_uop_type(
         }
         // This should not happen, as Switch() is only produced during evaluation.
         UnaryOp::Switch(_) => panic!("cannot typecheck Switch()"),
+        // This should not happen, as NonExhaustiveMatch() is only produced by the desugaring of
+        // `match`, which happens after typechecking.
+        UnaryOp::NonExhaustiveMatch() => panic!("cannot typecheck NonExhaustiveMatch()"),
         // Dyn -> Dyn
         UnaryOp::ChangePolarity() | UnaryOp::GoDom() | UnaryOp::GoCodom() | UnaryOp::GoArray() => {
             (mk_typewrapper::dynamic(), mk_typewrapper::dynamic())
@@ -172,6 +175,18 @@ pub fn get_uop_type(
             mk_typewrapper::str(),
             mk_tyw_enum!(mk_typewrapper::dynamic()),
         ),
+        // forall a. a -> Bool
+        UnaryOp::EnumIsVariant() => {
+            let inp = TypeWrapper::Ptr(state.table.fresh_var());
+            (inp, mk_typewrapper::bool())
+        }
+        // Dyn -> < | Dyn>
+        UnaryOp::EnumGetTag() => (
+            mk_typewrapper::dynamic(),
+            mk_tyw_enum!(mk_typewrapper::dynamic()),
+        ),
+        // Dyn -> Dyn
+        UnaryOp::EnumGetArg() => (mk_typewrapper::dynamic(), mk_typewrapper::dynamic()),
     })
 }
 
@@ -237,6 +252,12 @@ pub fn get_bop_type(
             mk_typewrapper::dynamic(),
             mk_typewrapper::dynamic(),
         ),
+        // < | Dyn> -> Dyn -> Dyn
+        BinaryOp::GoVariant() => (
+            mk_tyw_enum!(mk_typewrapper::dynamic()),
+            mk_typewrapper::dynamic(),
+            mk_typewrapper::dynamic(),
+        ),
         // forall a. Str -> { _ : a} -> a
         BinaryOp::DynAccess() => {
             let res = TypeWrapper::Ptr(state.table.fresh_var());
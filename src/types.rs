@@ -44,6 +44,13 @@
 //! An enum type is also a row type, but each list element only contains an identifier without an
 //! associated type. It indicates which tag the enum can contain.
 //!
+//! A tag may also carry data, such as `` `Port 8080 ``. The corresponding row element then has an
+//! associated type, the type of the data. For example, the type `[| Port Num, Tls {cert: Str},
+//! Plain |]` is a sum type, whose values are either `` `Port `` together with a number, `` `Tls ``
+//! together with a record, or the bare tag `` `Plain ``. As for records, enum types are row
+//! polymorphic: `[| Port Num ; a |]` is the type of enums which are either `` `Port `` with a
+//! number, or any other tag allowed by the row `a`.
+//!
 //! # Contracts
 //!
 //! To each type corresponds a contract, which is a Nickel function which checks at runtime that
@@ -54,7 +61,7 @@
 use crate::error::{ParseError, ParseErrors, TypecheckError};
 use crate::identifier::Ident;
 use crate::term::make as mk_term;
-use crate::term::{RichTerm, Term};
+use crate::term::{BinaryOp, RichTerm, Term, UnaryOp};
 use crate::{mk_app, mk_fun, mk_switch};
 use std::collections::HashMap;
 use std::fmt;
@@ -245,16 +252,45 @@ impl Types {
             AbsType::RowEmpty() | AbsType::RowExtend(..) => contract::fail(),
             AbsType::Enum(ref r) => {
                 fn form(
+                    sy: &mut i32,
+                    pol: bool,
                     ty: Types,
                     h: HashMap<Ident, (RichTerm, RichTerm)>,
                 ) -> Result<RichTerm, UnboundTypeVariableError> {
                     let ctr = match ty.0 {
                         AbsType::RowEmpty() => contract::fail(),
-                        AbsType::RowExtend(_, Some(_), _) => {
-                            panic!("It should be a row without type")
+                        // A variant carrying data. The contract of the argument is applied lazily,
+                        // by rebuilding the variant with the argument wrapped in an assume.
+                        AbsType::RowExtend(id, Some(ty), rest) => {
+                            let rest_contract = form(sy, pol, *rest, h.clone())?;
+                            let arg_contract = ty.subcontract(h, pol, sy)?;
+                            let wrap = mk_fun!(
+                                "c",
+                                "l",
+                                "x",
+                                Term::EnumVariant(
+                                    id.clone(),
+                                    mk_app!(
+                                        mk_term::op2(
+                                            BinaryOp::Assume(),
+                                            mk_term::var("c"),
+                                            mk_term::var("l")
+                                        ),
+                                        mk_term::op1(UnaryOp::EnumGetArg(), mk_term::var("x"))
+                                    )
+                                )
+                            );
+
+                            mk_app!(
+                                contract::row_extend_variant(),
+                                rest_contract,
+                                Term::Enum(id),
+                                arg_contract,
+                                wrap
+                            )
                         }
                         AbsType::RowExtend(id, None, rest) => {
-                            let rest_contract = form(*rest, h)?;
+                            let rest_contract = form(sy, pol, *rest, h)?;
 
                             mk_app!(
                                 contract::row_extend(),
@@ -276,7 +312,7 @@ impl Types {
                     Ok(ctr)
                 }
 
-                form(*r.clone(), h)?
+                form(sy, pol, *r.clone(), h)?
             }
             AbsType::StaticRecord(ref ty) => {
                 fn form(
@@ -366,6 +402,44 @@ impl Types {
             _ => false,
         }
     }
+
+    /// Determine if the type is an atom or is delimited by its own brackets, such that it doesn't
+    /// need parentheses when written as the argument of a variant in an enum type.
+    pub fn fmt_is_delimited(&self) -> bool {
+        self.fmt_is_atom()
+            || matches!(
+                self.0,
+                AbsType::Enum(_) | AbsType::StaticRecord(_) | AbsType::DynRecord(_)
+            )
+    }
+}
+
+/// Format the row of an enum type. Contrary to record rows, the type of a variant carrying data is
+/// written after the tag without a colon: `Port Num`.
+fn fmt_enum_row(f: &mut fmt::Formatter, row: &Types) -> fmt::Result {
+    match &row.0 {
+        AbsType::RowExtend(id, ty_opt, tail) => {
+            write!(f, "{}", id)?;
+
+            if let Some(ty) = ty_opt {
+                if ty.fmt_is_delimited() {
+                    write!(f, " {}", ty)?;
+                } else {
+                    write!(f, " ({})", ty)?;
+                }
+            }
+
+            match tail.0 {
+                AbsType::RowEmpty() => Ok(()),
+                AbsType::Var(_) | AbsType::Dyn() => write!(f, " ; {}", tail),
+                _ => {
+                    write!(f, ", ")?;
+                    fmt_enum_row(f, tail)
+                }
+            }
+        }
+        _ => write!(f, "{}", row),
+    }
 }
 
 impl fmt::Display for Types {
@@ -396,7 +470,11 @@ impl fmt::Display for Types {
                 }
                 write!(f, ". {}", curr)
             }
-            AbsType::Enum(row) => write!(f, "[|{}|]", row),
+            AbsType::Enum(row) => {
+                write!(f, "[|")?;
+                fmt_enum_row(f, row)?;
+                write!(f, "|]")
+            }
             AbsType::StaticRecord(row) => write!(f, "{{{}}}", row),
             AbsType::DynRecord(ty) => write!(f, "{{_: {}}}", ty),
             AbsType::RowEmpty() => Ok(()),
@@ -478,6 +556,8 @@ mod test {
 
         assert_format_eq("[|a, b, c, d|]");
         assert_format_eq("forall r. [|tag1, tag2, tag3 ; r|]");
+        assert_format_eq("[|Port Num, Tls {cert: Str}, Plain|]");
+        assert_format_eq("forall r. [|Some (Array Num), None ; r|]");
 
         assert_format_eq("Array Num");
         assert_format_eq("Array (Array Num)");
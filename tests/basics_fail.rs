@@ -11,6 +11,22 @@ fn div_by_zero() {
     );
 }
 
+#[test]
+fn number_size_limits() {
+    assert_matches!(
+        eval("%pow% 10 1000000000"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("string.to_num \"1e1000000000\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("let sq = fun x => x * x in sq (sq (sq (sq (sq (%pow% 10 100000)))))"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+}
+
 #[test]
 fn comparisons() {
     assert_matches!(
@@ -62,3 +78,15 @@ fn string_chunks() {
         Err(Error::EvalError(EvalError::TypeError(..)))
     );
 }
+
+#[test]
+fn match_non_exhaustive() {
+    assert_matches!(
+        eval("match { `foo => 1, {a} => a } `bar"),
+        Err(Error::EvalError(EvalError::NonExhaustiveMatch(..)))
+    );
+    assert_matches!(
+        eval("match { [x, ..] if x > 0 => x } [0]"),
+        Err(Error::EvalError(EvalError::NonExhaustiveMatch(..)))
+    );
+}
@@ -37,6 +37,40 @@ fn enum_simple() {
     assert_raise_blame!("`foo | [| |]");
 }
 
+#[test]
+fn enum_variants() {
+    assert_raise_blame!("let x = (`Port \"80\" | [| Port Num, None |]) in x == x");
+    assert_raise_blame!("`Port | [| Port Num, None |]");
+    assert_raise_blame!("`None 1 | [| Port Num, None |]");
+    assert_raise_blame!("let x = (`Tls {cert = 1} | [| Tls {cert: Str} |]) in x == x");
+    assert_raise_blame!("let `Port p = `Addr \"localhost\" in p");
+}
+
+#[test]
+fn enum_variants_path() {
+    use nickel_lang::label::ty_path::Elem;
+
+    let mut files = Files::new();
+    let res = eval("%deep_seq% (`Tls {cert = 1} | [| Plain, Tls {cert: Str} |]) false");
+    match &res {
+        Err(Error::EvalError(EvalError::BlameError(ref l, _))) => {
+            assert_matches!(l.path.as_slice(), [Elem::Variant(tag), Elem::Field(id)] if &tag.to_string() == "Tls" && &id.to_string() == "cert")
+        }
+        err => panic!("expected blame error, got {:?}", err),
+    }
+    // Check that reporting doesn't panic, as for arrays.
+    res.unwrap_err().to_diagnostic(&mut files, None);
+
+    let res = eval("%deep_seq% (`Port [1, \"x\"] | [| Port (Array Num) |]) false");
+    match &res {
+        Err(Error::EvalError(EvalError::BlameError(ref l, _))) => {
+            assert_matches!(l.path.as_slice(), [Elem::Variant(tag), Elem::Array] if &tag.to_string() == "Port")
+        }
+        err => panic!("expected blame error, got {:?}", err),
+    }
+    res.unwrap_err().to_diagnostic(&mut files, None);
+}
+
 #[test]
 fn metavalue_contract_default_fail() {
     assert_raise_blame!("true | default | Num");
@@ -180,6 +214,28 @@ fn records_contracts_closed() {
     assert_raise_blame!("let Contract = {a | Num} & {b | Num} in ({a=1, b=2, c=3} | Contract)");
 }
 
+#[test]
+fn optional_fields() {
+    assert_raise_blame!("({a = \"a\", b = 1} | {a | optional | Num, b | Num}).a");
+    assert_matches!(
+        eval("({b = 1} | {a | optional | Num, b | Num}).a"),
+        Err(Error::EvalError(EvalError::MissingFieldDef(..)))
+    );
+    assert_matches!(
+        eval("builtin.deep_seq ({a | optional} & {a | Num}) true"),
+        Err(Error::EvalError(EvalError::MissingFieldDef(..)))
+    );
+    // A field referring to an absent optional field is not absent itself.
+    assert_matches!(
+        eval("builtin.deep_seq {a | optional | Num, b = a} true"),
+        Err(Error::EvalError(EvalError::MissingFieldDef(..)))
+    );
+    assert_matches!(
+        eval("builtin.serialize `Json {a | optional | Num, b = a}"),
+        Err(Error::EvalError(EvalError::MissingFieldDef(..)))
+    );
+}
+
 // #[test]
 // fn enum_complex() {
 //     eval(
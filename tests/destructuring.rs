@@ -88,3 +88,80 @@ fn typecontract_fail() {
 fn fun() {
     assert_eq!(eval_file("destructuring/fun.ncl"), Ok(Term::Bool(true)));
 }
+
+#[test]
+fn array() {
+    assert_eq!(eval_file("destructuring/array.ncl"), Ok(Term::Bool(true)));
+}
+
+#[test]
+fn array_rest() {
+    assert_eq!(
+        eval_file("destructuring/array_rest.ncl"),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn array_default() {
+    assert_eq!(
+        eval_file("destructuring/array_default.ncl"),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn array_nested() {
+    assert_eq!(
+        eval_file("destructuring/array_nested.ncl"),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn array_fun() {
+    assert_eq!(
+        eval_file("destructuring/array_fun.ncl"),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn array_typecontract() {
+    assert_eq!(
+        eval_file("destructuring/array_typecontract.ncl"),
+        Ok(Term::Bool(true))
+    );
+}
+
+#[test]
+fn array_closed_fail() {
+    assert_matches!(
+        eval_file("destructuring/array_closed_fail.ncl"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn array_missing_fail() {
+    assert_matches!(
+        eval_file("destructuring/array_missing_fail.ncl"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn array_typecontract_fail() {
+    assert_matches!(
+        eval_file("destructuring/array_typecontract_fail.ncl"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn array_typecheck_fail() {
+    assert_matches!(
+        eval_file("destructuring/array_typecheck_fail.ncl"),
+        Err(Error::TypecheckError(TypecheckError::TypeMismatch(..)))
+    );
+}
@@ -0,0 +1,76 @@
+use assert_matches::assert_matches;
+use nickel_lang::error::{Error, EvalError};
+use nickel_lang::program::{Explanation, Program};
+use nickel_lang::term::{MergePriority, Term};
+
+fn explain(source: &str, path: &str) -> Result<Explanation, Error> {
+    Program::new_from_source(source.as_bytes(), "explain")
+        .unwrap()
+        .explain(String::from(path))
+}
+
+#[test]
+pub fn merge_operands() {
+    let source = "let Port = contract.from_predicate (fun x => x > 0) in
+{
+  server = { port | Num | default = 80 },
+} & {
+  server = { port = 8080 },
+} & {
+  server = { port | doc \"The port\" | Port },
+}";
+    let explanation = explain(source, "server.port").unwrap();
+    let operands = explanation.operands;
+
+    assert_eq!(operands.len(), 3);
+
+    assert_eq!(operands[0].location.as_deref(), Some("explain:3:37"));
+    assert_eq!(operands[0].priority, MergePriority::Default);
+    assert_eq!(operands[0].contracts, vec![String::from("Num")]);
+    assert_eq!(operands[0].value.as_deref(), Some("80"));
+
+    assert_eq!(operands[1].location.as_deref(), Some("explain:5:21"));
+    assert_eq!(operands[1].priority, MergePriority::normal());
+    assert!(operands[1].contracts.is_empty());
+    assert_eq!(operands[1].value.as_deref(), Some("8080"));
+
+    assert_eq!(operands[2].priority, MergePriority::normal());
+    assert_eq!(operands[2].contracts, vec![String::from("Port")]);
+    assert_eq!(operands[2].value, None);
+
+    match explanation.value {
+        Term::MetaValue(meta) => {
+            assert_eq!(meta.doc.as_deref(), Some("The port"));
+            assert_eq!(meta.contracts.len(), 2);
+            assert_eq!(
+                meta.value.map(|t| t.as_ref().shallow_repr()).as_deref(),
+                Some("8080")
+            );
+        }
+        t => panic!("expected a metavalue, got {:?}", t),
+    }
+}
+
+#[test]
+pub fn single_operand() {
+    let explanation = explain("{a = {b : Num = 1 + 1}}", "a.b").unwrap();
+
+    assert_eq!(explanation.operands.len(), 1);
+    assert_eq!(explanation.operands[0].types.as_deref(), Some("Num"));
+    assert_eq!(
+        explanation.operands[0].location.as_deref(),
+        Some("explain:1:17")
+    );
+}
+
+#[test]
+pub fn missing_field() {
+    assert_matches!(
+        explain("{a = {b = 1}}", "a.c"),
+        Err(Error::EvalError(EvalError::FieldMissing(..)))
+    );
+    assert_matches!(
+        explain("{a = 1}", "a.b"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
@@ -1,5 +1,7 @@
 use assert_matches::assert_matches;
-use nickel_lang::error::{Error, EvalError, TypecheckError};
+use nickel_lang::error::{Error, EvalError, ImportError, TypecheckError};
+use nickel_lang::number::Number;
+use nickel_lang::package::Packages;
 use nickel_lang::program::Program;
 use nickel_lang::term::Term;
 use std::io::BufReader;
@@ -21,7 +23,7 @@ fn nested() {
         "should_be = 3",
     )
     .unwrap();
-    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(3.)));
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(Number::from(3))));
 }
 
 #[test]
@@ -31,7 +33,7 @@ fn root_path() {
         "should_be = 44",
     )
     .unwrap();
-    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(44.)));
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(Number::from(44))));
 }
 
 #[test]
@@ -41,7 +43,7 @@ fn multi_imports() {
         "should_be = 5",
     )
     .unwrap();
-    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(5.)));
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(Number::from(5))));
 }
 
 #[test]
@@ -100,6 +102,201 @@ fn serialize() {
     assert_eq!(prog.eval().map(Term::from), Ok(Term::Bool(true)));
 }
 
+#[test]
+fn json_schema() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(
+            format!(
+                "let service = {{name = \"web\", ports = [{{port = 80}}]}} | {} in \
+                [service.protocol, (array.head service.ports).host] == [\"tcp\", \"0.0.0.0\"]",
+                mk_import("service.schema.json")
+            )
+            .as_bytes(),
+        ),
+        "should_be = true",
+    )
+    .unwrap();
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Bool(true)));
+}
+
+#[test]
+fn json_schema_optional_property() {
+    // Properties which are neither required nor have a default value are checked when present.
+    let mut prog = Program::new_from_source(
+        BufReader::new(
+            format!(
+                "builtin.deep_seq ({{name = \"web\", ports = [], replicas = \"two\"}} | {}) null",
+                mk_import("service.schema.json")
+            )
+            .as_bytes(),
+        ),
+        "should_fail",
+    )
+    .unwrap();
+    assert_matches!(
+        prog.eval(),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn json_schema_fail() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(
+            format!(
+                "builtin.deep_seq ({{name = \"web\", ports = [{{port = 80.5}}]}} | {}) null",
+                mk_import("service.schema.json")
+            )
+            .as_bytes(),
+        ),
+        "should_fail",
+    )
+    .unwrap();
+    assert_matches!(
+        prog.eval(),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn import_path() {
+    let mut prog = Program::new_from_source(
+        BufReader::new("(import \"lib.ncl\").value".as_bytes()),
+        "should_be = 3",
+    )
+    .unwrap();
+    prog.add_import_paths(vec![
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/imports/nonexistent"),
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/imports/search_path"),
+    ]);
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(Number::from(3))));
+}
+
+#[test]
+fn import_path_fail() {
+    let search_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/imports/search_path");
+
+    // Explicitly relative paths are not looked up in the import search path.
+    let mut prog = Program::new_from_source(
+        BufReader::new("import \"./lib.ncl\"".as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+    prog.add_import_paths(vec![search_path.clone()]);
+    assert_matches!(
+        prog.eval(),
+        Err(Error::ImportError(ImportError::IOError(..)))
+    );
+
+    let mut prog = Program::new_from_source(
+        BufReader::new("import \"missing.ncl\"".as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+    prog.add_import_paths(vec![search_path.clone()]);
+    match prog.eval() {
+        Err(Error::ImportError(ImportError::IOError(_, msg, _))) => assert!(
+            msg.contains(&*search_path.join("missing.ncl").to_string_lossy()),
+            "the search path is missing from the error message: {}",
+            msg
+        ),
+        res => panic!("expected an import error, got {:?}", res),
+    }
+}
+
+fn mk_packages(manifest_dir: &str) -> Packages {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push(format!(
+        "tests/imports/packages/{}/nickel.toml",
+        manifest_dir
+    ));
+    Packages::load(path).unwrap()
+}
+
+#[test]
+fn package() {
+    let mut prog = Program::new_from_source(
+        BufReader::new("(import \"k8s/deployment.ncl\").port".as_bytes()),
+        "should_be = 8080",
+    )
+    .unwrap();
+    prog.set_packages(mk_packages("ok"));
+    assert_eq!(
+        prog.eval().map(Term::from),
+        Ok(Term::Num(Number::from(8080)))
+    );
+}
+
+#[test]
+fn package_lockfile_mismatch() {
+    let mut prog = Program::new_from_source(
+        BufReader::new("(import \"k8s/deployment.ncl\").port".as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+    prog.set_packages(mk_packages("tampered"));
+    assert_matches!(
+        prog.eval(),
+        Err(Error::ImportError(ImportError::LockfileMismatch(..)))
+    );
+}
+
+#[test]
+fn text() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/imports/script.sh");
+    let content = std::fs::read_to_string(&path).unwrap();
+
+    let mut prog = Program::new_from_source(
+        BufReader::new(format!("({}) : Str", mk_import("script.sh")).as_bytes()),
+        "should_be_the_script",
+    )
+    .unwrap();
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Str(content)));
+}
+
+#[test]
+fn explicit_format() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(
+            format!(
+                "let config = {} as yaml in \
+                let raw = {} as text in \
+                [config.port, %str_length% raw] == [8080, 27]",
+                mk_import("config.gen"),
+                mk_import("config.gen")
+            )
+            .as_bytes(),
+        ),
+        "should_be = true",
+    )
+    .unwrap();
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Bool(true)));
+}
+
+#[test]
+fn directory() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(mk_import("directory.ncl").as_bytes()),
+        "should_be = true",
+    )
+    .unwrap();
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Bool(true)));
+}
+
+#[test]
+fn directory_collision_fail() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(mk_import("collision").as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+    assert_matches!(
+        prog.eval(),
+        Err(Error::ImportError(ImportError::DirectoryCollision(..)))
+    );
+}
+
 #[test]
 fn circular_imports_fail() {
     let mut prog = Program::new_from_source(
@@ -0,0 +1,52 @@
+use assert_matches::assert_matches;
+use nickel_lang::error::{Error, EvalError};
+use nickel_lang::program::Program;
+use nickel_lang::term::RichTerm;
+use serde_json::json;
+
+fn path(file: &str) -> String {
+    format!("{}/tests/inputs/{}", env!("CARGO_MANIFEST_DIR"), file)
+}
+
+fn eval_files(files: &[&str]) -> Result<RichTerm, Error> {
+    Program::new_from_files(files.iter().map(|file| path(file)))
+        .unwrap()
+        .eval_full()
+}
+
+#[test]
+fn merge_inputs() {
+    let result = eval_files(&["base.ncl", "prod.ncl", "local.ncl"]).unwrap();
+
+    assert_eq!(
+        serde_json::to_value(&result).unwrap(),
+        json!({
+            "server": {
+                "host": "127.0.0.1",
+                "port": 443,
+                "url": "127.0.0.1:443",
+            },
+            "replicas": 3,
+        })
+    );
+}
+
+#[test]
+fn errors_in_inputs() {
+    let result = eval_files(&["base.ncl", "invalid.ncl"]);
+
+    // The contract is defined in the first file, while the value breaking it comes from the
+    // second one.
+    match result {
+        Err(Error::EvalError(EvalError::BlameError(label, _))) => {
+            let arg_span = label.arg_pos.as_opt_ref().unwrap();
+            assert_ne!(label.span.src_id, arg_span.src_id);
+        }
+        err => panic!("expected a blame error, got {:?}", err),
+    }
+
+    assert_matches!(
+        Program::new_from_files(Vec::<String>::new()).map(|_| ()),
+        Err(err) if err.kind() == std::io::ErrorKind::InvalidInput
+    );
+}
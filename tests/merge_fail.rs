@@ -1,8 +1,9 @@
 use assert_matches::assert_matches;
 use nickel_lang::error::{Error, EvalError};
+use nickel_lang::number::Number;
 use nickel_lang::position::TermPos;
 use nickel_lang::program::Program;
-use nickel_lang::term::RichTerm;
+use nickel_lang::term::{MergePriority, RichTerm};
 use std::io::Cursor;
 
 fn eval_full(s: &str) -> Result<RichTerm, Error> {
@@ -30,3 +31,47 @@ macro_rules! assert_merge_fails {
 fn merge_conflict_inside_metavalue() {
     assert_merge_fails!("{ foo = (fun x => x) (1 | default), foo = (fun x => x) (1 | default) } & {foo | default = 2 }");
 }
+
+#[test]
+fn merge_conflict_same_priority() {
+    assert_merge_fails!("{a = 1} & {a = 2}");
+    assert_merge_fails!("{a | priority 10 = 1} & {a | priority 10 = 2}");
+    assert_merge_fails!("{a | force = 1} & {a | force = 2}");
+
+    assert_matches!(
+        eval_full("{a | priority 10 = 1} & {a | priority 10 = 2}"),
+        Err(Error::EvalError(EvalError::MergeIncompatibleArgs(.., MergePriority::Numeral(n))))
+            if n == Number::from(10)
+    );
+    assert_matches!(
+        eval_full("({a | force = \"a\"} & {a | force = \"b\"}).a"),
+        Err(Error::EvalError(EvalError::MergeIncompatibleArgs(
+            ..,
+            MergePriority::Top
+        )))
+    );
+    // Values without annotation have the normal priority.
+    assert_matches!(
+        eval_full("{a = 1} & {a = 2}"),
+        Err(Error::EvalError(EvalError::MergeIncompatibleArgs(.., p)))
+            if p == MergePriority::normal()
+    );
+    assert_matches!(
+        eval_full("({a = {b = 1}} & {a = [2]}).a"),
+        Err(Error::EvalError(EvalError::MergeIncompatibleArgs(.., p)))
+            if p == MergePriority::normal()
+    );
+}
+
+#[test]
+fn merge_arrays_without_strategy() {
+    assert_merge_fails!("{a = [1]} & {a = [2]}");
+    assert_matches!(
+        eval_full("{a | array_merge concat = [1]} & {a = 2}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval_full("{a | array_merge merge = [1]}"),
+        Err(Error::ParseErrors(..))
+    );
+}
@@ -117,3 +117,13 @@ fn importing() {
 fn overriding() {
     check_file("overriding.ncl");
 }
+
+#[test]
+fn match_expression() {
+    check_file("match.ncl");
+}
+
+#[test]
+fn enum_variants() {
+    check_file("enum-variants.ncl");
+}
@@ -106,6 +106,23 @@ fn enum_complex() {
     );
 }
 
+#[test]
+fn enum_variants() {
+    assert_typecheck_fails!("`Port \"80\" : [| Port Num |]");
+    assert_typecheck_fails!("`Port : [| Port Num |]");
+    assert_typecheck_fails!("`Port 80 : [| Port |]");
+    assert_typecheck_fails!(
+        "(fun x => switch {`Port p => p ++ \"\", `None => \"\"} x) : [| Port Num, None |] -> Str"
+    );
+    assert_typecheck_fails!(
+        "(fun x => switch {`Port => 1, `None => 0} x) : [| Port Num, None |] -> Num"
+    );
+    assert_matches!(
+        type_check_expr("(fun x => switch {`Port p => p} x) : [| Port Num, None |] -> Num"),
+        Err(TypecheckError::NonExhaustiveMatch(..))
+    );
+}
+
 #[test]
 fn static_record_simple() {
     assert_typecheck_fails!("{bla = true} : {bla : Num}");
@@ -230,3 +247,65 @@ fn piecewise_signature() {
         Err(TypecheckError::TypeMismatch(..))
     );
 }
+
+#[test]
+fn optional_field() {
+    assert_matches!(
+        type_check_expr("{foo | optional | Num, bar = 1} : {foo: Num, bar: Num}"),
+        Err(TypecheckError::MissingRow(..))
+    );
+    assert_matches!(
+        type_check_expr("{foo | optional | Num, bar = 1} : {bar: Num}"),
+        Ok(_)
+    );
+}
+
+#[test]
+fn match_non_exhaustive() {
+    assert_matches!(
+        type_check_expr("(fun x => match {`foo => 1, `bar => 2} x) : [| foo, bar, baz |] -> Num"),
+        Err(TypecheckError::NonExhaustiveMatch(..))
+    );
+    assert_matches!(
+        type_check_expr(
+            "(fun x => match {`foo => 1, `bar if true => 2} x) : [| foo, bar |] -> Num"
+        ),
+        Err(TypecheckError::NonExhaustiveMatch(..))
+    );
+    assert_matches!(
+        type_check_expr("(fun x => match {`foo => 1, _ => 2} x) : [| foo, bar, baz |] -> Num"),
+        Ok(_)
+    );
+
+    // A variant case only covers its tag if its sub-pattern matches any argument
+    assert_matches!(
+        type_check_expr(
+            "let f : [| A Num, B |] -> Num = fun x => match { `A 1 => 1, `B => 2 } x in f (`A 2)"
+        ),
+        Err(TypecheckError::NonExhaustiveMatch(..))
+    );
+    assert_matches!(
+        type_check_expr(
+            "let f : [| A {a: Num}, B |] -> Num = fun x => match { `A {a = 1, ..} => 1, `B => 2 } x in f (`A {a = 2})"
+        ),
+        Err(TypecheckError::NonExhaustiveMatch(..))
+    );
+    assert_matches!(
+        type_check_expr(
+            "let f : [| A Num, B |] -> Num = fun x => match { `A 1 => 1, `A n => n, `B => 2 } x in f (`A 2)"
+        ),
+        Ok(_)
+    );
+    assert_matches!(
+        type_check_expr(
+            "let f : [| A {a: Num}, B |] -> Num = fun x => match { `A {a, ..} => 1, `B => 2 } x in f (`A {a = 2})"
+        ),
+        Ok(_)
+    );
+    assert_matches!(
+        type_check_expr(
+            "let f : [| A Num, B |] -> Num = fun x => match { `A 1 => 1, _ => 2 } x in f (`A 2)"
+        ),
+        Ok(_)
+    );
+}
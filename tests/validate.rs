@@ -0,0 +1,52 @@
+use assert_matches::assert_matches;
+use nickel_lang::error::{Error, EvalError};
+use nickel_lang::program::Program;
+
+fn path(file: &str) -> String {
+    format!("{}/tests/validate/{}", env!("CARGO_MANIFEST_DIR"), file)
+}
+
+fn validate(data: &str, field: Option<&str>) -> Result<(), Error> {
+    let mut prog = Program::new_from_file(path("contract.ncl")).unwrap();
+    prog.validate(path(data), field.map(String::from))
+}
+
+#[test]
+fn valid_data() {
+    assert_eq!(validate("valid.yaml", Some("Config")), Ok(()));
+    assert_eq!(validate("valid.toml", Some("Config")), Ok(()));
+}
+
+#[test]
+fn invalid_data() {
+    assert_matches!(
+        validate("invalid.json", Some("Config")),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+    assert_matches!(
+        validate("server.ncl", Some("Server")),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn whole_program_contract() {
+    // The contract file itself is a record contract, which doesn't accept extra fields.
+    assert_matches!(
+        validate("valid.yaml", None),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn invalid_field() {
+    assert_matches!(
+        validate("valid.yaml", Some("Missing")),
+        Err(Error::EvalError(EvalError::FieldMissing(..)))
+    );
+}
+
+#[test]
+fn stdlib_in_contract() {
+    assert_eq!(validate("positive.json", Some("Positive")), Ok(()));
+}